@@ -0,0 +1,128 @@
+use crate::constants::*;
+use crate::board::get_all_occupation;
+use crate::map::{CROSS_MOVE_MAP, DIAGONAL_MOVE_MAP, L_MOVE_MAP, SQUARE_MOVE_MAP};
+use crate::map::MoveMap;
+use crate::split_state::split_slice_into_slices;
+use crate::pawn_move::{wpawn_right, wpawn_left, bpawn_right, bpawn_left};
+use std::sync::Mutex;
+
+fn piece_attacks(board: [u64; 13], slice_index: u8, map: &Mutex<MoveMap>) -> u64 {
+  let occ: u64 = get_all_occupation(board);
+  let mut attacked: u64 = 0;
+  for piece in split_slice_into_slices(board[slice_index as usize]).iter() {
+    attacked |= map.lock().unwrap().get_value(*piece, occ);
+  }
+  attacked
+}
+
+// squares one piece on `piece` attacks, for the piece kind stored in `slice_index`
+pub fn single_piece_attacks(slice_index: u8, piece: u64, occ: u64) -> u64 {
+  let kind: u8 = slice_index % 6;
+  if kind == WROOK {
+    CROSS_MOVE_MAP.lock().unwrap().get_value(piece, occ)
+  } else if kind == WBISHOP {
+    DIAGONAL_MOVE_MAP.lock().unwrap().get_value(piece, occ)
+  } else if kind == WQUEEN {
+    CROSS_MOVE_MAP.lock().unwrap().get_value(piece, occ) | DIAGONAL_MOVE_MAP.lock().unwrap().get_value(piece, occ)
+  } else if kind == WKNIGHT {
+    L_MOVE_MAP.lock().unwrap().get_value(piece, occ)
+  } else if kind == WKING {
+    SQUARE_MOVE_MAP.lock().unwrap().get_value(piece, occ)
+  } else if slice_index == WPAWN {
+    white_pawn_attacks(piece)
+  } else {
+    black_pawn_attacks(piece)
+  }
+}
+
+pub fn white_pawn_attacks(pawns: u64) -> u64 {
+  wpawn_right(pawns, WHOLE_BOARD) | wpawn_left(pawns, WHOLE_BOARD)
+}
+
+pub fn black_pawn_attacks(pawns: u64) -> u64 {
+  bpawn_right(pawns, WHOLE_BOARD) | bpawn_left(pawns, WHOLE_BOARD)
+}
+
+pub fn white_attacks(board: [u64; 13]) -> u64 {
+  piece_attacks(board, WROOK, &CROSS_MOVE_MAP)
+    | piece_attacks(board, WBISHOP, &DIAGONAL_MOVE_MAP)
+    | piece_attacks(board, WQUEEN, &CROSS_MOVE_MAP)
+    | piece_attacks(board, WQUEEN, &DIAGONAL_MOVE_MAP)
+    | piece_attacks(board, WKNIGHT, &L_MOVE_MAP)
+    | piece_attacks(board, WKING, &SQUARE_MOVE_MAP)
+    | white_pawn_attacks(board[WPAWN as usize])
+}
+
+pub fn black_attacks(board: [u64; 13]) -> u64 {
+  piece_attacks(board, BROOK, &CROSS_MOVE_MAP)
+    | piece_attacks(board, BBISHOP, &DIAGONAL_MOVE_MAP)
+    | piece_attacks(board, BQUEEN, &CROSS_MOVE_MAP)
+    | piece_attacks(board, BQUEEN, &DIAGONAL_MOVE_MAP)
+    | piece_attacks(board, BKNIGHT, &L_MOVE_MAP)
+    | piece_attacks(board, BKING, &SQUARE_MOVE_MAP)
+    | black_pawn_attacks(board[BPAWN as usize])
+}
+
+// squares the side that is *not* to move on `turn` attacks
+pub fn enemy_attacks(board: [u64; 13], turn: u8) -> u64 {
+  if turn.is_multiple_of(2) {
+    white_attacks(board)
+  } else {
+    black_attacks(board)
+  }
+}
+
+pub fn is_in_check(board: [u64; 13], turn: u8) -> bool {
+  let king: u64 = if turn.is_multiple_of(2) { board[BKING as usize] } else { board[WKING as usize] };
+  king != 0 && (enemy_attacks(board, turn) & king) != 0
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn single_piece_attacks_stop_at_blockers() {
+    let occ: u64 = 1 | (1 << 3) | (1 << 24);
+    let rook: u64 = single_piece_attacks(BROOK, 1, occ);
+    assert!(rook == (1 << 1) | (1 << 2) | (1 << 3) | (1 << 8) | (1 << 16) | (1 << 24));
+    assert!(single_piece_attacks(WKNIGHT, 1, occ) == (1 << 10) | (1 << 17));
+    assert!(single_piece_attacks(BPAWN, 1 << 9, occ) == 1 | (1 << 2));
+  }
+
+  #[test]
+  fn finds_rook_check_along_rank() {
+    let mut board: [u64; 13] = [0; 13];
+    board[BKING as usize] = 1 << 63;
+    board[WROOK as usize] = 1 << 56;
+    assert!(is_in_check(board, 0));
+    assert!(!is_in_check(board, 1));
+  }
+
+  #[test]
+  fn blocked_rook_does_not_give_check() {
+    let mut board: [u64; 13] = [0; 13];
+    board[BKING as usize] = 1 << 63;
+    board[WROOK as usize] = 1 << 56;
+    board[BPAWN as usize] = 1 << 60;
+    assert!(!is_in_check(board, 0));
+  }
+
+  #[test]
+  fn finds_pawn_and_knight_checks() {
+    let mut board: [u64; 13] = [0; 13];
+    board[WKING as usize] = 1 << 4;
+    board[BPAWN as usize] = 1 << 13;
+    assert!(is_in_check(board, 1));
+    board[BPAWN as usize] = 0;
+    board[BKNIGHT as usize] = 1 << 21;
+    assert!(is_in_check(board, 1));
+  }
+
+  #[test]
+  fn missing_king_is_not_in_check() {
+    let mut board: [u64; 13] = [0; 13];
+    board[WROOK as usize] = 1 << 56;
+    assert!(!is_in_check(board, 0));
+  }
+}
@@ -8,12 +8,90 @@ use crate::rand::prelude::SliceRandom;
 use crate::rand::prelude::IteratorRandom;
 use crate::utility::{greater_than, less_than, min_f, max_f, number_of_bits};
 use crate::board::*;
+use crate::attacks::is_in_check;
 use crate::{print_board, print_board_pieces};
+use crate::evaluator::{Evaluator, Position, make_position};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::Instant;
+use std::sync::Mutex;
 
 
 pub struct Bot {
-  eval_fn: fn([u64; 13]) -> f64,
-  depth: u8
+  // behind a lock so a shared bot can still search through `&self`
+  evaluator: Mutex<Box<dyn Evaluator>>,
+  depth: u8,
+  config: SearchConfig,
+  nodes: AtomicU64,
+  // a search gives up once the node count reaches the limit or the clock the deadline
+  node_limit: AtomicU64,
+  deadline: Mutex<Option<Instant>>,
+  stopped: AtomicBool
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct SearchConfig {
+  // extend by one ply when the move played gives a new check
+  pub check_extension: bool,
+  // extend by one ply when a capture lands on the square the previous move captured on
+  pub recapture_extension: bool,
+  // extend the best move when a reduced search finds it clearly better than every alternative
+  pub singular_extension: bool,
+  pub singular_min_depth: u8,
+  pub singular_margin: f64,
+  // most plies that may be added along any one path from the root
+  pub max_extensions: u8,
+  // resolve captures at the horizon instead of evaluating mid-exchange
+  pub quiescence: bool,
+  pub quiescence_max_depth: u8,
+  // skip quiet moves at depth 1-2 when the static eval plus a margin can't reach alpha
+  pub futility_pruning: bool,
+  pub futility_margins: [f64; 3],
+  // return the static eval at depth 1-2 when it beats beta by a margin per ply
+  pub reverse_futility_pruning: bool,
+  pub reverse_futility_margin: f64,
+  // drop into quiescence at depth 1-2 when the static eval is far below alpha
+  pub razoring: bool,
+  pub razor_margin: f64,
+  // try moves in the order of the evaluator's move priors, at nodes with at least
+  // `policy_min_depth` plies left where the priors cost less than they save
+  pub policy_ordering: bool,
+  pub policy_min_depth: u8
+}
+
+pub fn default_search_config() -> SearchConfig {
+  SearchConfig {
+    check_extension: true,
+    recapture_extension: false,
+    singular_extension: false,
+    singular_min_depth: 3,
+    singular_margin: 1.0,
+    max_extensions: 4,
+    quiescence: true,
+    quiescence_max_depth: 8,
+    futility_pruning: true,
+    futility_margins: [0.0, 1.0, 3.0],
+    reverse_futility_pruning: true,
+    reverse_futility_margin: 1.2,
+    razoring: true,
+    razor_margin: 3.0,
+    policy_ordering: true,
+    policy_min_depth: 2
+  }
+}
+
+// fixed-horizon minimax with alpha-beta only
+pub fn plain_search_config() -> SearchConfig {
+  SearchConfig {
+    check_extension: false,
+    recapture_extension: false,
+    singular_extension: false,
+    quiescence: false,
+    futility_pruning: false,
+    reverse_futility_pruning: false,
+    razoring: false,
+    policy_ordering: false,
+    ..default_search_config()
+  }
 }
 
 lazy_static! {
@@ -44,11 +122,6 @@ pub fn basic_eval(state: [u64; 13]) -> f64 {
   collect_points(state)
 }
 
-pub fn random_eval(_state: [u64; 13]) -> f64 {
-  let mut rng = rand::thread_rng();
-  rng.gen_range(-50f64..50f64)
-}
-
 pub fn center_squares_worth(state: [u64; 13]) -> f64 {
   let occ_fns: Vec<fn([u64; 13]) -> u64> = vec![get_black_occupation_except_king, get_white_occupation_except_king, get_black_occupation_except_king, get_white_occupation_except_king];
   let squares: Vec<u64> = vec![CENTER_FOUR_SQUARES, CENTER_FOUR_SQUARES, SECOND_CENTER_SQUARES, SECOND_CENTER_SQUARES];
@@ -57,22 +130,140 @@ pub fn center_squares_worth(state: [u64; 13]) -> f64 {
   collect_points(state) + center_squares_value
 }
 
-pub fn make_bot(eval_function: fn([u64; 13]) -> f64, d: u8) -> Bot {
+pub fn make_bot(evaluator: Box<dyn Evaluator>, d: u8) -> Bot {
+  make_bot_with_config(evaluator, d, default_search_config())
+}
+
+pub fn make_bot_with_config(evaluator: Box<dyn Evaluator>, d: u8, search_config: SearchConfig) -> Bot {
   Bot {
-    eval_fn: eval_function,
-    depth: d
+    evaluator: Mutex::new(evaluator),
+    depth: d,
+    config: search_config,
+    nodes: AtomicU64::new(0),
+    node_limit: AtomicU64::new(u64::MAX),
+    deadline: Mutex::new(None),
+    stopped: AtomicBool::new(false)
   }
 }
 
+// bit of the square the side moving on `turn` captured on, 0 if the move was quiet
+fn capture_square(parent: [u64; 13], child: [u64; 13], turn: u8) -> u64 {
+  get_enemy_occupation(parent, turn) & !get_enemy_occupation(child, turn)
+}
+
+// whether the side moving on `turn` turned a pawn into another piece
+fn promotes(parent: [u64; 13], child: [u64; 13], turn: u8) -> bool {
+  let pawn: usize = if turn % 2 == 1 { WPAWN as usize } else { BPAWN as usize };
+  child[pawn].count_ones() < parent[pawn].count_ones()
+}
+
+// the move generator has no legality checks, so the game ends when a king is taken
+pub fn king_captured(state: [u64; 13]) -> bool {
+  state[WKING as usize] == 0 || state[BKING as usize] == 0
+}
+
+// most valuable victim first, least valuable attacker breaking ties
+const CAPTURE_ORDER_VALUES: [u8; 6] = [5, 3, 9, 1, 3, 50];
+
+fn slice_on_square(state: [u64; 13], square: u64) -> Option<usize> {
+  (0..12).find(|slice_index| state[*slice_index] & square != 0)
+}
+
+fn ordered_captures(state: [u64; 13], turn_number: u8) -> Vec<[u64; 13]> {
+  let mut captures: Vec<(u16, [u64; 13])> = states_for_turn(state, turn_number).into_iter()
+    .filter_map(|p_state| {
+      let square: u64 = capture_square(state, p_state, turn_number);
+      if square == 0 {
+        return None;
+      }
+      let victim: u16 = slice_on_square(state, square).map(|slice| CAPTURE_ORDER_VALUES[slice % 6]).unwrap_or(0) as u16;
+      let attacker: u16 = slice_on_square(p_state, square).map(|slice| CAPTURE_ORDER_VALUES[slice % 6]).unwrap_or(0) as u16;
+      Some((victim * 64 - attacker, p_state))
+    })
+    .collect();
+  captures.sort_by_key(|capture| std::cmp::Reverse(capture.0));
+  captures.into_iter().map(|(_, p_state)| p_state).collect()
+}
+
 impl Bot {
+  // positions visited by searches since the last reset
+  pub fn nodes(&self) -> u64 {
+    self.nodes.load(Ordering::Relaxed)
+  }
+
+  pub fn reset_nodes(&self) {
+    self.nodes.store(0, Ordering::Relaxed);
+  }
+
+  // stops searches once the node count since the last reset reaches `nodes`, or at
+  // `deadline`. a stopped search returns whatever it had found, which may not be sound
+  pub fn set_limits(&self, nodes: Option<u64>, deadline: Option<Instant>) {
+    self.node_limit.store(nodes.unwrap_or(u64::MAX), Ordering::Relaxed);
+    *self.deadline.lock().unwrap() = deadline;
+    self.stopped.store(false, Ordering::Relaxed);
+  }
+
+  // whether a search ran into the limits since they were last set
+  pub fn stopped(&self) -> bool {
+    self.stopped.load(Ordering::Relaxed)
+  }
+
+  // counts a node, reporting whether the search is over its limits. the clock is only
+  // read every 1024 nodes
+  fn count_node(&self) -> bool {
+    let nodes: u64 = self.nodes.fetch_add(1, Ordering::Relaxed) + 1;
+    if self.stopped() {
+      return true;
+    }
+    let over: bool = nodes >= self.node_limit.load(Ordering::Relaxed)
+      || (nodes.is_multiple_of(1024) && self.deadline.lock().unwrap().is_some_and(|deadline| Instant::now() >= deadline));
+    if over {
+      self.stopped.store(true, Ordering::Relaxed);
+    }
+    over
+  }
+
+  // whether no capture changes the static eval, so the eval is a fair label for the position
+  pub fn is_quiet(&self, state: [u64; 13], turn_number: u8) -> bool {
+    self.quiescence(state, turn_number, self.config.quiescence_max_depth, -10000.0, 10000.0) == self.evaluate(state, turn_number)
+  }
+
+  pub fn set_depth(&mut self, depth: u8) {
+    self.depth = depth;
+  }
+
+  fn evaluate(&self, state: [u64; 13], turn_number: u8) -> f64 {
+    self.evaluator.lock().unwrap().evaluate(&make_position(state, turn_number))
+  }
+
+  fn make(&self, parent: [u64; 13], child: [u64; 13], turn_number: u8) {
+    self.evaluator.lock().unwrap().on_make(&make_position(parent, turn_number), &make_position(child, turn_number + 1));
+  }
+
+  fn unmake(&self, parent: [u64; 13], child: [u64; 13], turn_number: u8) {
+    self.evaluator.lock().unwrap().on_unmake(&make_position(child, turn_number + 1), &make_position(parent, turn_number));
+  }
+
+  // most probable move first, when the evaluator has priors for them
+  fn order_by_priors(&self, state: [u64; 13], turn_number: u8, depth_left: u8, children: &mut Vec<[u64; 13]>) {
+    if !self.config.policy_ordering || depth_left < self.config.policy_min_depth || children.len() < 2 {
+      return;
+    }
+    if let Some(priors) = self.evaluator.lock().unwrap().move_priors(&make_position(state, turn_number), children) {
+      let mut ranked: Vec<(f64, [u64; 13])> = priors.into_iter().zip(children.iter().copied()).collect();
+      ranked.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+      *children = ranked.into_iter().map(|(_, child)| child).collect();
+    }
+  }
+
   pub fn get_state(&self, state: [u64; 13], turn_number: u8) -> [u64; 13] {
     if turn_number % 2 == 0 {
       let new_state: [u64; 13] = self.get_state_black(state);
-      println!("Evaluation: {:.32}", (self.eval_fn)(new_state));
+      println!("Evaluation: {:.32}", self.evaluate(new_state, turn_number + 1));
       new_state
     } else {
       let new_state: [u64; 13] = self.get_state_white(state);
-      println!("Evaluation: {:.32}", (self.eval_fn)(new_state));
+      println!("Evaluation: {:.32}", self.evaluate(new_state, turn_number + 1));
       new_state
     }
   }
@@ -88,19 +279,39 @@ impl Bot {
 
   fn get_state_general(&self, state: [u64; 13], turn_number: u8, more_or_less: fn(f64, f64) -> bool, starting_value: f64) -> [u64; 13] {
     let mut possible_states: Vec<[u64; 13]> = states_for_turn(state, turn_number);
+    possible_states.shuffle(&mut thread_rng());
+    self.search_states(state, possible_states, turn_number, more_or_less, starting_value).1
+  }
+
+  // evaluation and resulting state of the best move for the side moving on `turn_number`
+  pub fn search(&self, state: [u64; 13], turn_number: u8) -> (f64, [u64; 13]) {
+    let mut possible_states: Vec<[u64; 13]> = states_for_turn(state, turn_number);
+    self.order_by_priors(state, turn_number, self.depth + 1, &mut possible_states);
+    if turn_number % 2 == 1 {
+      self.search_states(state, possible_states, turn_number, greater_than, -10000.0)
+    } else {
+      self.search_states(state, possible_states, turn_number, less_than, 10000.0)
+    }
+  }
+
+  fn search_states(&self, state: [u64; 13], possible_states: Vec<[u64; 13]>, turn_number: u8, more_or_less: fn(f64, f64) -> bool, starting_value: f64) -> (f64, [u64; 13]) {
     let mut candidate_state: [u64; 13] = [0; 13];
     let mut best_eval: f64 = starting_value;
-    possible_states.shuffle(&mut thread_rng());
     let mut evaluation: f64;
-    for possible_state in possible_states.iter() {
-      evaluation = self.minimax(*possible_state, turn_number + 1, 0, -10000.0, 10000.0);
+    let singular: Option<usize> = self.singular_child(state, &possible_states, turn_number, self.depth + 1, self.config.max_extensions);
+    for (index, possible_state) in possible_states.iter().enumerate() {
+      let extension: u8 = self.extension(state, *possible_state, turn_number, 0, singular == Some(index), self.config.max_extensions);
+      let (alpha, beta): (f64, f64) = if turn_number % 2 == 1 { (best_eval, 10000.0) } else { (-10000.0, best_eval) };
+      self.make(state, *possible_state, turn_number);
+      evaluation = self.minimax(*possible_state, turn_number + 1, self.depth + extension, self.config.max_extensions - extension, capture_square(state, *possible_state, turn_number), alpha, beta);
+      self.unmake(state, *possible_state, turn_number);
       if more_or_less(evaluation, best_eval) {
         candidate_state = *possible_state;
         best_eval = evaluation;
       }
     }
 
-    candidate_state
+    (best_eval, candidate_state)
   }
 
   fn get_state_white(&self, state: [u64; 13]) -> [u64; 13] {
@@ -111,18 +322,89 @@ impl Bot {
     self.get_state_general(state, 0, less_than, 10000.0)
   }
 
-  fn minimax(&self, state: [u64; 13], turn_number: u8, depth_gone: u8, mut alpha: f64, mut beta: f64) -> f64 {
-    if depth_gone == self.depth {
-      return (self.eval_fn)(state)
+  // plies to add to the search of `child`, reached from `parent` by the side moving on `turn_number`
+  fn extension(&self, parent: [u64; 13], child: [u64; 13], turn_number: u8, last_capture: u64, singular: bool, extensions_left: u8) -> u8 {
+    if extensions_left == 0 {
+      return 0;
+    }
+    if self.config.check_extension && is_in_check(child, turn_number + 1) && !is_in_check(parent, turn_number + 1) {
+      return 1;
+    }
+    if self.config.recapture_extension && last_capture != 0 && capture_square(parent, child, turn_number) == last_capture {
+      return 1;
+    }
+    if singular {
+      return 1;
+    }
+    0
+  }
+
+  // index of the only move from a node at `depth_left` whose reduced-depth score beats every sibling by `singular_margin`
+  fn singular_child(&self, state: [u64; 13], possible_states: &[[u64; 13]], turn_number: u8, depth_left: u8, extensions_left: u8) -> Option<usize> {
+    if !self.config.singular_extension || extensions_left == 0 || depth_left < self.config.singular_min_depth || possible_states.len() < 2 {
+      return None;
     }
-    let possible_states: Vec<[u64; 13]> = states_for_turn(state, turn_number);
+    let reduced_depth: u8 = (depth_left - 1) / 2;
+    let mover_is_white: bool = turn_number % 2 == 1;
+    let mut scores: Vec<(f64, usize)> = possible_states.iter().enumerate()
+      .map(|(index, p_state)| {
+        self.make(state, *p_state, turn_number);
+        let score: f64 = self.minimax(*p_state, turn_number + 1, reduced_depth, 0, 0, -10000.0, 10000.0);
+        self.unmake(state, *p_state, turn_number);
+        (if mover_is_white { score } else { -score }, index)
+      })
+      .collect();
+    scores.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap());
+    if scores[0].0 - scores[1].0 >= self.config.singular_margin {
+      Some(scores[0].1)
+    } else {
+      None
+    }
+  }
+
+  #[allow(clippy::too_many_arguments)]
+  fn minimax(&self, state: [u64; 13], turn_number: u8, depth_left: u8, extensions_left: u8, last_capture: u64, mut alpha: f64, mut beta: f64) -> f64 {
+    if depth_left == 0 || king_captured(state) {
+      if self.config.quiescence {
+        return self.quiescence(state, turn_number, self.config.quiescence_max_depth, alpha, beta)
+      }
+      self.count_node();
+      return self.evaluate(state, turn_number)
+    }
+    if self.count_node() {
+      return self.evaluate(state, turn_number);
+    }
+
+    let frontier: bool = depth_left <= 2 && !is_in_check(state, turn_number);
+    let static_eval: f64 = if frontier && self.prunes_frontier() { self.evaluate(state, turn_number) } else { 0.0 };
+    if frontier {
+      if let Some(value) = self.prune_frontier_node(state, turn_number, depth_left, static_eval, alpha, beta) {
+        return value;
+      }
+    }
+    let futile: bool = frontier && self.config.futility_pruning && if turn_number % 2 == 1 {
+      static_eval + self.config.futility_margins[depth_left as usize] <= alpha
+    } else {
+      static_eval - self.config.futility_margins[depth_left as usize] >= beta
+    };
+
+    let mut possible_states: Vec<[u64; 13]> = states_for_turn(state, turn_number);
+    self.order_by_priors(state, turn_number, depth_left, &mut possible_states);
+    let singular: Option<usize> = self.singular_child(state, &possible_states, turn_number, depth_left, extensions_left);
 
     if turn_number % 2 == 1 {
       let mut max: f64 = -10000.0;
       let mut current_value: f64;
       
-      for p_state in possible_states {
-        current_value = self.minimax(p_state, turn_number + 1, depth_gone + 1, alpha, beta);
+      for (index, p_state) in possible_states.into_iter().enumerate() {
+        let extension: u8 = self.extension(state, p_state, turn_number, last_capture, singular == Some(index), extensions_left);
+        if futile && extension == 0 && capture_square(state, p_state, turn_number) == 0 && !promotes(state, p_state, turn_number) {
+          max = max_f(max, static_eval + self.config.futility_margins[depth_left as usize]);
+          continue;
+        }
+        self.make(state, p_state, turn_number);
+        current_value = self.minimax(p_state, turn_number + 1, depth_left - 1 + extension, extensions_left - extension, capture_square(state, p_state, turn_number), alpha, beta);
+        self.unmake(state, p_state, turn_number);
         max = max_f(max, current_value);
         alpha = max_f(alpha, max);
 
@@ -136,8 +418,15 @@ impl Bot {
       let mut min: f64 = 10000.0;
       let mut current_value: f64;
 
-      for p_state in possible_states {
-        current_value = self.minimax(p_state, turn_number + 1, depth_gone + 1, alpha, beta);
+      for (index, p_state) in possible_states.into_iter().enumerate() {
+        let extension: u8 = self.extension(state, p_state, turn_number, last_capture, singular == Some(index), extensions_left);
+        if futile && extension == 0 && capture_square(state, p_state, turn_number) == 0 && !promotes(state, p_state, turn_number) {
+          min = min_f(min, static_eval - self.config.futility_margins[depth_left as usize]);
+          continue;
+        }
+        self.make(state, p_state, turn_number);
+        current_value = self.minimax(p_state, turn_number + 1, depth_left - 1 + extension, extensions_left - extension, capture_square(state, p_state, turn_number), alpha, beta);
+        self.unmake(state, p_state, turn_number);
         min = min_f(min, current_value);
         beta = min_f(beta, min);
 
@@ -148,4 +437,291 @@ impl Bot {
       min
     }    
   }
+
+  fn prunes_frontier(&self) -> bool {
+    self.config.futility_pruning || self.config.reverse_futility_pruning || self.config.razoring
+  }
+
+  // reverse futility and razoring cut-offs for a node at depth 1-2 that isn't in check
+  fn prune_frontier_node(&self, state: [u64; 13], turn_number: u8, depth_left: u8, static_eval: f64, alpha: f64, beta: f64) -> Option<f64> {
+    let rfp_margin: f64 = self.config.reverse_futility_margin * depth_left as f64;
+    let razor_margin: f64 = self.config.razor_margin * depth_left as f64;
+    if turn_number % 2 == 1 {
+      if self.config.reverse_futility_pruning && static_eval - rfp_margin >= beta {
+        return Some(static_eval);
+      }
+      if self.config.razoring && static_eval + razor_margin < alpha {
+        let value: f64 = self.quiescence(state, turn_number, self.config.quiescence_max_depth, alpha, beta);
+        if value < alpha {
+          return Some(value);
+        }
+      }
+    } else {
+      if self.config.reverse_futility_pruning && static_eval + rfp_margin <= alpha {
+        return Some(static_eval);
+      }
+      if self.config.razoring && static_eval - razor_margin > beta {
+        let value: f64 = self.quiescence(state, turn_number, self.config.quiescence_max_depth, alpha, beta);
+        if value > beta {
+          return Some(value);
+        }
+      }
+    }
+    None
+  }
+
+  // searches captures only, letting the side to move stand pat on the static eval
+  fn quiescence(&self, state: [u64; 13], turn_number: u8, depth_left: u8, mut alpha: f64, mut beta: f64) -> f64 {
+    let stopped: bool = self.count_node();
+    let stand_pat: f64 = self.evaluate(state, turn_number);
+    if stopped || depth_left == 0 || king_captured(state) {
+      return stand_pat;
+    }
+    let captures: Vec<[u64; 13]> = ordered_captures(state, turn_number);
+
+    if turn_number % 2 == 1 {
+      let mut max: f64 = stand_pat;
+      alpha = max_f(alpha, max);
+      if alpha >= beta {
+        return max;
+      }
+      for p_state in captures {
+        self.make(state, p_state, turn_number);
+        max = max_f(max, self.quiescence(p_state, turn_number + 1, depth_left - 1, alpha, beta));
+        self.unmake(state, p_state, turn_number);
+        alpha = max_f(alpha, max);
+        if alpha >= beta {
+          break;
+        }
+      }
+      max
+    } else {
+      let mut min: f64 = stand_pat;
+      beta = min_f(beta, min);
+      if alpha >= beta {
+        return min;
+      }
+      for p_state in captures {
+        self.make(state, p_state, turn_number);
+        min = min_f(min, self.quiescence(p_state, turn_number + 1, depth_left - 1, alpha, beta));
+        self.unmake(state, p_state, turn_number);
+        beta = min_f(beta, min);
+        if alpha >= beta {
+          break;
+        }
+      }
+      min
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::evaluator::BasicEvaluator;
+
+  // white: Kc1, Ra1; black: Kh8, pawns f7 g7 h7. Ra8 wins the king on the next move.
+  fn back_rank_position() -> [u64; 13] {
+    let mut board: [u64; 13] = [0; 13];
+    board[WKING as usize] = 1 << 2;
+    board[WROOK as usize] = 1;
+    board[BKING as usize] = 1 << 63;
+    board[BPAWN as usize] = (1 << 53) | (1 << 54) | (1 << 55);
+    board
+  }
+
+  // checks that every `on_unmake` undoes the `on_make` before it and that the search
+  // only evaluates the position it has made its way to
+  struct TrackingEvaluator {
+    path: Vec<Position>,
+    makes: std::sync::Arc<AtomicU64>
+  }
+
+  impl Evaluator for TrackingEvaluator {
+    fn evaluate(&mut self, position: &Position) -> f64 {
+      assert!(self.path.last() == Some(position));
+      basic_eval(position.state)
+    }
+
+    fn on_make(&mut self, parent: &Position, child: &Position) {
+      assert!(self.path.last() == Some(parent));
+      self.path.push(*child);
+      self.makes.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn on_unmake(&mut self, child: &Position, parent: &Position) {
+      assert!(self.path.pop().as_ref() == Some(child));
+      assert!(self.path.last() == Some(parent));
+    }
+  }
+
+  #[test]
+  fn make_and_unmake_hooks_follow_the_search() {
+    let root: Position = make_position(back_rank_position(), 1);
+    let makes: std::sync::Arc<AtomicU64> = std::sync::Arc::new(AtomicU64::new(0));
+    let evaluator: TrackingEvaluator = TrackingEvaluator { path: vec![root], makes: makes.clone() };
+    let bot: Bot = make_bot_with_config(Box::new(evaluator), 2, default_search_config());
+    bot.search(root.state, root.turn_number);
+    assert!(makes.load(Ordering::Relaxed) > 0);
+  }
+
+  // material as the eval, and priors that favour whatever wins the most material
+  struct CapturePriorEvaluator;
+
+  impl Evaluator for CapturePriorEvaluator {
+    fn evaluate(&mut self, position: &Position) -> f64 {
+      basic_eval(position.state)
+    }
+
+    fn move_priors(&mut self, parent: &Position, children: &[[u64; 13]]) -> Option<Vec<f64>> {
+      let sign: f64 = if parent.turn_number % 2 == 1 { 1.0 } else { -1.0 };
+      Some(children.iter().map(|child| sign * basic_eval(*child)).collect())
+    }
+  }
+
+  #[test]
+  fn priors_order_the_moves() {
+    let mut config: SearchConfig = plain_search_config();
+    config.policy_ordering = true;
+    let bot: Bot = make_bot_with_config(Box::new(CapturePriorEvaluator), 2, config);
+    let mut children: Vec<[u64; 13]> = states_for_turn(back_rank_position(), 1);
+    bot.order_by_priors(back_rank_position(), 1, 1, &mut children);
+    assert!(children == states_for_turn(back_rank_position(), 1));
+    bot.order_by_priors(back_rank_position(), 1, 2, &mut children);
+    // Ra8 takes nothing, but every first move does at least as well as the last
+    assert!(basic_eval(children[0]) >= basic_eval(children[children.len() - 1]));
+
+    let plain: Bot = make_bot_with_config(Box::new(CapturePriorEvaluator), 3, plain_search_config());
+    let ordered: Bot = make_bot_with_config(Box::new(CapturePriorEvaluator), 3, config);
+    let mut hanging_queen: [u64; 13] = crate::game::setup_board();
+    hanging_queen[BQUEEN as usize] = 1 << 36;
+    hanging_queen[BPAWN as usize] ^= 1 << 52;
+    // the order changes what gets cut, never the score
+    assert!(plain.search(hanging_queen, 1).0 == ordered.search(hanging_queen, 1).0);
+  }
+
+  mod extension_tests {
+    use super::*;
+
+    #[test]
+    fn check_extension_finds_back_rank_mate() {
+      let mut config: SearchConfig = plain_search_config();
+      config.check_extension = true;
+      let bot: Bot = make_bot_with_config(Box::new(BasicEvaluator), 1, config);
+      let (evaluation, state) = bot.search(back_rank_position(), 1);
+      assert!(state[WROOK as usize] == 1 << 56);
+      assert!(evaluation > 40.0);
+    }
+
+    #[test]
+    fn plain_search_misses_back_rank_mate() {
+      let bot: Bot = make_bot_with_config(Box::new(BasicEvaluator), 1, plain_search_config());
+      let (evaluation, _state) = bot.search(back_rank_position(), 1);
+      assert!(evaluation < 40.0);
+    }
+
+    #[test]
+    fn extensions_are_bounded_per_path() {
+      let mut config: SearchConfig = plain_search_config();
+      config.check_extension = true;
+      config.max_extensions = 0;
+      let bot: Bot = make_bot_with_config(Box::new(BasicEvaluator), 1, config);
+      let (evaluation, _state) = bot.search(back_rank_position(), 1);
+      assert!(evaluation < 40.0);
+    }
+
+    #[test]
+    fn recapture_extension_sees_the_recapture() {
+      // white: Kh1, Rd1, Nc3; black: Ka8, Nd5 defended by pawn e6. After Nxd5 exd5 only the extension sees Rxd5.
+      let mut board: [u64; 13] = [0; 13];
+      board[WKING as usize] = 1 << 7;
+      board[WROOK as usize] = 1 << 3;
+      board[WKNIGHT as usize] = 1 << 18;
+      board[BKING as usize] = 1 << 56;
+      board[BKNIGHT as usize] = 1 << 35;
+      board[BPAWN as usize] = 1 << 44;
+      let mut config: SearchConfig = plain_search_config();
+      let plain_evaluation: f64 = make_bot_with_config(Box::new(BasicEvaluator), 1, config).search(board, 1).0;
+      config.recapture_extension = true;
+      let (evaluation, state) = make_bot_with_config(Box::new(BasicEvaluator), 1, config).search(board, 1);
+      assert!(evaluation > plain_evaluation);
+      assert!(state[WKNIGHT as usize] == 1 << 35);
+    }
+  }
+
+  mod pruning_tests {
+    use super::*;
+    use crate::game::setup_board;
+
+    fn no_pruning_config() -> SearchConfig {
+      SearchConfig {
+        futility_pruning: false,
+        reverse_futility_pruning: false,
+        razoring: false,
+        ..default_search_config()
+      }
+    }
+
+    fn bench_positions() -> Vec<[u64; 13]> {
+      let mut hanging_queen: [u64; 13] = setup_board();
+      hanging_queen[BQUEEN as usize] = 1 << 36;
+      hanging_queen[BPAWN as usize] ^= 1 << 52;
+      vec![setup_board(), back_rank_position(), hanging_queen]
+    }
+
+    fn total_nodes(bot: &Bot) -> u64 {
+      bot.reset_nodes();
+      for position in bench_positions() {
+        bot.search(position, 1);
+      }
+      bot.nodes()
+    }
+
+    #[test]
+    fn promotions_are_not_quiet() {
+      let mut before: [u64; 13] = [0; 13];
+      before[WPAWN as usize] = 1 << 52;
+      let mut queening: [u64; 13] = [0; 13];
+      queening[WQUEEN as usize] = 1 << 60;
+      let mut push: [u64; 13] = [0; 13];
+      push[WPAWN as usize] = 1 << 60;
+      assert!(promotes(before, queening, 1));
+      assert!(!promotes(before, push, 1));
+      assert!(!promotes(before, queening, 2));
+    }
+
+    #[test]
+    fn quiescence_resolves_captures_at_the_horizon() {
+      // white: Kh1, Qd1; black: Ka8, pawn d5 defended by pawn e6
+      let mut board: [u64; 13] = [0; 13];
+      board[WKING as usize] = 1 << 7;
+      board[WQUEEN as usize] = 1 << 3;
+      board[BKING as usize] = 1 << 56;
+      board[BPAWN as usize] = (1 << 35) | (1 << 44);
+      let (plain_evaluation, plain_state) = make_bot_with_config(Box::new(BasicEvaluator), 0, plain_search_config()).search(board, 1);
+      assert!(plain_state[WQUEEN as usize] == 1 << 35);
+      assert!(plain_evaluation > 6.0);
+      let mut config: SearchConfig = plain_search_config();
+      config.quiescence = true;
+      let (evaluation, state) = make_bot_with_config(Box::new(BasicEvaluator), 0, config).search(board, 1);
+      assert!(state[WQUEEN as usize] != 1 << 35);
+      assert!(evaluation < 7.0);
+    }
+
+    #[test]
+    fn frontier_pruning_searches_fewer_nodes() {
+      for depth in 1..3 {
+        let pruned: u64 = total_nodes(&make_bot_with_config(Box::new(BasicEvaluator), depth, default_search_config()));
+        let unpruned: u64 = total_nodes(&make_bot_with_config(Box::new(BasicEvaluator), depth, no_pruning_config()));
+        assert!(pruned < unpruned);
+      }
+    }
+
+    #[test]
+    fn frontier_pruning_keeps_tactical_results() {
+      let (evaluation, state) = make_bot_with_config(Box::new(BasicEvaluator), 2, default_search_config()).search(back_rank_position(), 1);
+      assert!(state[WROOK as usize] == 1 << 56);
+      assert!(evaluation > 40.0);
+    }
+  }
 }
\ No newline at end of file
@@ -0,0 +1,566 @@
+use crate::bot::{make_bot, make_bot_with_config, plain_search_config};
+use crate::evaluator::{make_hand_crafted_evaluator, BasicEvaluator, CenterSquaresEvaluator, Evaluator};
+use crate::game::{make_engine, one_bot_game, two_bot_game, two_bot_game_learn_bot, two_console_game, EngineKind};
+use crate::mcts::{default_mcts_config, MctsConfig};
+use crate::suite::{run_epd_file, SearchBudget};
+use crate::evaluation::{eval_trace_with_params, print_eval_trace};
+use crate::evaluation::params::{default_eval_params, load_eval_params, save_eval_params, EvalParams};
+use crate::fen::state_from_fen;
+use crate::tuner::{default_tune_config, fit_scaling_constant, read_tuning_file, tune, TuneConfig, TuningPosition};
+use crate::uci::{make_uci, run_uci};
+use crate::bench::{run_bench_with_evaluator, print_bench, BENCH_POSITIONS, DEFAULT_BENCH_DEPTH};
+use crate::network::encoding::InputEncoder;
+use crate::network::nnue::{load_nnue_evaluator, make_nnue_evaluator, make_random_nnue_weights, quantise_nnue, read_nnue_file, NnueWeights, DEFAULT_NNUE_HEAD, DEFAULT_NNUE_HIDDEN, DEFAULT_NNUE_PATH};
+use crate::network::binary_storage::{convert_text_network, Precision};
+use crate::network::network_storage::network_options;
+use crate::network::dataset::{dataset_from_pgn_files, dataset_from_self_play, default_filter_config, default_self_play_config, print_dataset_stats, DatasetStats, FilterConfig, SelfPlayConfig};
+use crate::network::td::{default_td_config, train_by_self_play, TdConfig};
+use crate::network::train::{default_run_config, default_training_config, train_network_on_dataset, train_nnue_on_dataset, train_policy_on_games, FitSummary, RunConfig, TrainingConfig};
+use std::sync::Arc;
+use std::time::Duration;
+
+// value following `flag` in the arguments, if the flag is present
+fn flag_value<'a>(args: &'a [String], flag: &str) -> Option<&'a str> {
+  args.iter()
+      .position(|arg| arg == flag)
+      .and_then(|index| args.get(index + 1))
+      .map(|value| value.as_str())
+}
+
+fn parse_flag<T: std::str::FromStr>(args: &[String], flag: &str, default: T) -> Result<T, String> {
+  match flag_value(args, flag) {
+    Some(value) => value.parse::<T>().map_err(|_| format!("invalid value '{}' for {}", value, flag)),
+    None => Ok(default)
+  }
+}
+
+// the arguments before the first flag
+fn positional_args(args: &[String]) -> Vec<String> {
+  args.iter().take_while(|arg| !arg.starts_with("--")).cloned().collect()
+}
+
+// the hand-crafted evaluation's parameters from --params PATH, as written by tune, or the
+// defaults
+fn eval_params(args: &[String]) -> Result<EvalParams, String> {
+  match flag_value(args, "--params") {
+    Some(params_path) => load_eval_params(params_path),
+    None => Ok(default_eval_params())
+  }
+}
+
+// the evaluator named by --eval material|hand-crafted|nnue[:ENCODER], `default` without the
+// flag. the hand-crafted one reads --params, the nnue its weights from --nnue-file PATH or,
+// with --random-net, random weights for the encoder named (king buckets by default)
+fn evaluator_flag(args: &[String], default: &str) -> Result<Box<dyn Evaluator>, String> {
+  Ok(match flag_value(args, "--eval").unwrap_or(default) {
+    "material" => Box::new(BasicEvaluator),
+    "hand-crafted" => Box::new(make_hand_crafted_evaluator(eval_params(args)?)),
+    eval if eval.starts_with("nnue") => {
+      let encoder: InputEncoder = match eval.strip_prefix("nnue:") {
+        Some(name) => InputEncoder::from_name(name).ok_or(format!("unknown encoder '{}'", name))?,
+        None => InputEncoder::KingBuckets
+      };
+      match flag_value(args, "--nnue-file") {
+        Some(path) => Box::new(load_nnue_evaluator(path)?),
+        None if args.iter().any(|arg| arg == "--random-net") => {
+          let weights = make_random_nnue_weights(encoder, DEFAULT_NNUE_HIDDEN, DEFAULT_NNUE_HEAD, 0);
+          Box::new(make_nnue_evaluator(Arc::new(quantise_nnue(&weights))))
+        },
+        None => return Err(String::from("the nnue evaluation needs --nnue-file PATH, or --random-net for random weights"))
+      }
+    },
+    eval => return Err(format!("unknown evaluation '{}'", eval))
+  })
+}
+
+// epd <file> [--nodes N | --time-ms T] [--depth D] [--eval E] [--params PATH], with the
+// evaluations of `evaluator_flag`
+fn epd_command(args: &[String]) -> Result<(), String> {
+  let path: &str = match args.first() {
+    Some(path) => path,
+    None => return Err(String::from("usage: epd <file> [--nodes N | --time-ms T] [--depth D] [--eval E] [--params PATH]"))
+  };
+  let budget: SearchBudget = if flag_value(args, "--nodes").is_some() {
+    SearchBudget::Nodes(parse_flag(args, "--nodes", 0u64)?)
+  } else {
+    SearchBudget::Time(Duration::from_millis(parse_flag(args, "--time-ms", 1000u64)?))
+  };
+  let max_depth: u8 = parse_flag(args, "--depth", 4u8)?;
+  run_epd_file(path, &mut make_bot(evaluator_flag(args, "material")?, 0), budget, max_depth).map(|_results| ())
+}
+
+// bench [--depth D] [--eval E] [--params PATH] [--nnue-file PATH] [--random-net], with the
+// evaluations of `evaluator_flag`. a random nnue measures the cost of the incremental network
+// against the material eval, not its strength
+fn bench_command(args: &[String]) -> Result<(), String> {
+  let depth: u8 = parse_flag(args, "--depth", DEFAULT_BENCH_DEPTH)?;
+  print_bench(&run_bench_with_evaluator(&BENCH_POSITIONS, depth, evaluator_flag(args, "material")?));
+  Ok(())
+}
+
+// eval [FEN] [--params PATH], prints the evaluation breakdown of the position (the starting
+// position by default)
+fn eval_command(args: &[String]) -> Result<(), String> {
+  let fen: Vec<String> = positional_args(args);
+  let state: [u64; 13] = if fen.is_empty() {
+    crate::game::setup_board()
+  } else {
+    state_from_fen(&fen.join(" "))?.0
+  };
+  print_eval_trace(&eval_trace_with_params(state, &eval_params(args)?));
+  Ok(())
+}
+
+// tune <file> [--iterations N] [--step S] [--params start.txt] [--out tuned.txt]
+fn tune_command(args: &[String]) -> Result<(), String> {
+  let path: &str = match args.first() {
+    Some(path) => path,
+    None => return Err(String::from("usage: tune <file> [--iterations N] [--step S] [--params start.txt] [--out tuned.txt]"))
+  };
+  let defaults: TuneConfig = default_tune_config();
+  let config: TuneConfig = TuneConfig {
+    max_iterations: parse_flag(args, "--iterations", defaults.max_iterations)?,
+    step: parse_flag(args, "--step", defaults.step)?
+  };
+  let start: EvalParams = eval_params(args)?;
+  let out: &str = flag_value(args, "--out").unwrap_or("tuned_params.txt");
+
+  let positions: Vec<TuningPosition> = read_tuning_file(path)?;
+  println!("Positions: {}", positions.len());
+  let k: f64 = fit_scaling_constant(&positions, &start);
+  println!("K: {:.3}", k);
+  let tuned: EvalParams = tune(&positions, &start, k, config);
+  save_eval_params(&tuned, out)?;
+  println!("Wrote {}", out);
+  Ok(())
+}
+
+// uci [--params PATH] [--eval-file PATH], speaks the protocol on stdin and stdout with the
+// hand-crafted evaluation, or the network or nnue from --eval-file as the EvalFile option
+fn uci_command(args: &[String]) -> Result<(), String> {
+  let mut uci = make_uci(eval_params(args)?);
+  if let Some(path) = flag_value(args, "--eval-file") {
+    uci.set_eval_file(Some(path))?;
+  }
+  run_uci(&mut uci, &mut std::io::stdin().lock(), &mut std::io::stdout())
+}
+
+// what MCTS divides the evaluation's scores by to get -1..1: learned evaluations are trained
+// on targets in that range, material scores are in pawns and ten of them is as good as won
+fn value_scale(args: &[String], default: &str) -> f64 {
+  match flag_value(args, "--eval").unwrap_or(default) {
+    eval if eval.starts_with("nnue") || eval == "net" => 1.0,
+    _ => 10.0
+  }
+}
+
+// the engine `flag` names, or the one --engine names, or minimax. minimax searches to
+// --depth; mcts reads [--playouts N] [--exploration C] [--batch N] [--temperature T]
+// [--noise F] [--no-reuse] [--seed S], and divides scores by `value_scale`
+fn engine_kind(args: &[String], flag: &str, default_depth: u8, value_scale: f64) -> Result<EngineKind, String> {
+  match flag_value(args, flag).or(flag_value(args, "--engine")).unwrap_or("minimax") {
+    "minimax" => Ok(EngineKind::Minimax(parse_flag(args, "--depth", default_depth)?)),
+    "mcts" => {
+      let defaults: MctsConfig = default_mcts_config();
+      Ok(EngineKind::Mcts(MctsConfig {
+        playouts: parse_flag(args, "--playouts", defaults.playouts)?,
+        exploration: parse_flag(args, "--exploration", defaults.exploration)?,
+        leaves_per_batch: parse_flag(args, "--batch", defaults.leaves_per_batch)?,
+        temperature: parse_flag(args, "--temperature", defaults.temperature)?,
+        noise_fraction: parse_flag(args, "--noise", defaults.noise_fraction)?,
+        reuse_tree: !args.iter().any(|arg| arg == "--no-reuse"),
+        seed: parse_flag(args, "--seed", defaults.seed)?,
+        value_scale,
+        ..defaults
+      }))
+    },
+    engine => Err(format!("unknown engine '{}'", engine))
+  }
+}
+
+// play <console | one-bot | two-bot | learn-bot> [--engine minimax|mcts] [--white-engine E]
+//   [--black-engine E] [--depth D] with the mcts flags of `engine_kind`. one-bot takes the
+//   --eval of `evaluator_flag` and its flags, as does two-bot for both sides in place of
+//   centre squares against material. learn-bot is the game without a command
+fn play_command(args: &[String]) -> Result<(), String> {
+  let usage: &str = "usage: play <console | one-bot | two-bot | learn-bot> [--engine minimax|mcts] [--white-engine E] [--black-engine E] [--depth D] [--playouts N] [--eval E]";
+  match positional_args(args).first().map(|arg| arg.as_str()) {
+    Some("console") => {
+      two_console_game();
+      Ok(())
+    },
+    Some("one-bot") => {
+      let kind: EngineKind = engine_kind(args, "--engine", 3, value_scale(args, "hand-crafted"))?;
+      one_bot_game(make_engine(kind, evaluator_flag(args, "hand-crafted")?).as_mut());
+      Ok(())
+    },
+    Some("two-bot") => {
+      let (white_evaluator, black_evaluator): (Box<dyn Evaluator>, Box<dyn Evaluator>) = if flag_value(args, "--eval").is_some() {
+        (evaluator_flag(args, "material")?, evaluator_flag(args, "material")?)
+      } else {
+        (Box::new(CenterSquaresEvaluator), Box::new(BasicEvaluator))
+      };
+      let scale: f64 = value_scale(args, "material");
+      let mut white = make_engine(engine_kind(args, "--white-engine", 3, scale)?, white_evaluator);
+      let mut black = make_engine(engine_kind(args, "--black-engine", 3, scale)?, black_evaluator);
+      two_bot_game(white.as_mut(), black.as_mut());
+      Ok(())
+    },
+    Some("learn-bot") => learn_bot_game_command(args),
+    _ => Err(String::from(usage))
+  }
+}
+
+// [--eval net|nnue] [--eval-file PATH] [--random-net] with the engine flags of `play`, two
+// learned-eval bots playing each other. the network path falls back to $EVAL_FILE and then
+// text_network_storage.txt; the nnue reads --nnue-file like `evaluator_flag`
+fn learn_bot_game_command(args: &[String]) -> Result<(), String> {
+  let white: EngineKind = engine_kind(args, "--white-engine", 0, 1.0)?;
+  let black: EngineKind = engine_kind(args, "--black-engine", 0, 1.0)?;
+  match flag_value(args, "--eval").unwrap_or("net") {
+    "net" => {
+      let options = network_options(flag_value(args, "--eval-file"), args.iter().any(|arg| arg == "--random-net"));
+      two_bot_game_learn_bot(&options, white, black)
+    },
+    eval if eval.starts_with("nnue") => {
+      let mut white_engine = make_engine(white, evaluator_flag(args, "nnue")?);
+      let mut black_engine = make_engine(black, evaluator_flag(args, "nnue")?);
+      two_bot_game(white_engine.as_mut(), black_engine.as_mut());
+      Ok(())
+    },
+    eval => Err(format!("learned games play the net or the nnue, not '{}'", eval))
+  }
+}
+
+// train-nnue <dataset> [--out PATH] [--epochs N] [--lr X] [--validation F] [--patience N]
+//   [--log PATH] [--result-weight W] [--encoder NAME] [--hidden N] [--head N] [--seed S],
+//   trains the NNUE at the out path, or new random weights of the given shape when there's
+//   none, and keeps the best weights there for --nnue-file. the shape flags can't change
+//   weights already at the out path
+fn train_nnue_command(args: &[String]) -> Result<(), String> {
+  let path: String = match positional_args(args).into_iter().next() {
+    Some(path) => path,
+    None => return Err(String::from("usage: train-nnue <dataset> [--out PATH] [--epochs N] [--lr X] [--validation F] [--patience N] [--log PATH] [--result-weight W] [--encoder NAME] [--hidden N] [--head N] [--seed S]"))
+  };
+  let encoder_name: &str = flag_value(args, "--encoder").unwrap_or(InputEncoder::KingBuckets.name());
+  let encoder: InputEncoder = InputEncoder::from_name(encoder_name).ok_or(format!("unknown encoder '{}'", encoder_name))?;
+  let (hidden, head): (usize, usize) = (parse_flag(args, "--hidden", DEFAULT_NNUE_HIDDEN)?, parse_flag(args, "--head", DEFAULT_NNUE_HEAD)?);
+  if hidden == 0 || head == 0 {
+    return Err(String::from("--hidden and --head must be positive"));
+  }
+  let seed: u64 = parse_flag(args, "--seed", 0u64)?;
+  let run_defaults: RunConfig = default_run_config();
+  let run: RunConfig = RunConfig {
+    epochs: parse_flag(args, "--epochs", run_defaults.epochs)?,
+    validation_fraction: parse_flag(args, "--validation", run_defaults.validation_fraction)?,
+    patience: parse_flag(args, "--patience", run_defaults.patience)?,
+    log_path: flag_value(args, "--log").map(|path| path.to_string()),
+    resume: false
+  };
+  let out: &str = flag_value(args, "--out").unwrap_or(DEFAULT_NNUE_PATH);
+  let continuing: bool = std::path::Path::new(out).exists();
+  let weights: NnueWeights = if continuing {
+    let weights: NnueWeights = read_nnue_file(out)?;
+    let conflicts: bool = (flag_value(args, "--encoder").is_some() && encoder != weights.encoder)
+      || (flag_value(args, "--hidden").is_some() && hidden != weights.hidden)
+      || (flag_value(args, "--head").is_some() && head != weights.head)
+      || flag_value(args, "--seed").is_some();
+    if conflicts {
+      return Err(format!("{} already holds {} weights with {} hidden and {} head neurons; --encoder, --hidden, --head and --seed only shape new weights", out, weights.encoder.name(), weights.hidden, weights.head));
+    }
+    weights
+  } else {
+    make_random_nnue_weights(encoder, hidden, head, seed)
+  };
+  let summary: FitSummary = train_nnue_on_dataset(&path, out, weights, continuing, parse_flag(args, "--lr", 0.001f32)?, &run, parse_flag(args, "--result-weight", 0.5f64)?)?;
+  println!("Best loss {:.5} at epoch {}, written to {}", summary.best_loss, summary.best_epoch, out);
+  Ok(())
+}
+
+// convert-net <text file> <binary file> [--precision f32|f64|i16]
+fn convert_net_command(args: &[String]) -> Result<(), String> {
+  let usage: &str = "usage: convert-net <text file> <binary file> [--precision f32|f64|i16]";
+  let (text_path, binary_path) = match (args.first(), args.get(1)) {
+    (Some(text_path), Some(binary_path)) if !text_path.starts_with("--") && !binary_path.starts_with("--") => (text_path, binary_path),
+    _ => return Err(String::from(usage))
+  };
+  let precision_name: &str = flag_value(args, "--precision").unwrap_or("f32");
+  let precision: Precision = Precision::from_name(precision_name).ok_or(format!("unknown precision '{}'", precision_name))?;
+  convert_text_network(text_path, binary_path, precision)?;
+  println!("Wrote {}", binary_path);
+  Ok(())
+}
+
+fn filter_config(args: &[String]) -> Result<FilterConfig, String> {
+  let defaults: FilterConfig = default_filter_config();
+  Ok(FilterConfig {
+    skip_checks: !args.iter().any(|arg| arg == "--keep-checks"),
+    skip_captures: !args.iter().any(|arg| arg == "--keep-captures"),
+    min_ply: parse_flag(args, "--min-ply", defaults.min_ply)?
+  })
+}
+
+// dataset pgn <out file> <pgn file>... | dataset selfplay <out file> [--games N] [--depth D]
+//   [--random-plies N] [--max-plies N] [--seed S], either with [--min-ply N] [--keep-checks]
+//   [--keep-captures]
+fn dataset_command(args: &[String]) -> Result<(), String> {
+  let usage: &str = "usage: dataset pgn <out file> <pgn file>... | dataset selfplay <out file> [--games N] [--depth D] [--random-plies N] [--max-plies N] [--seed S]";
+  let positional: Vec<String> = positional_args(args);
+  let filter: FilterConfig = filter_config(args)?;
+  let stats: DatasetStats = match (positional.first().map(|arg| arg.as_str()), positional.get(1)) {
+    (Some("pgn"), Some(out)) if positional.len() > 2 => dataset_from_pgn_files(&positional[2..], filter, out)?,
+    (Some("selfplay"), Some(out)) => {
+      let defaults: SelfPlayConfig = default_self_play_config();
+      let config: SelfPlayConfig = SelfPlayConfig {
+        games: parse_flag(args, "--games", defaults.games)?,
+        random_plies: parse_flag(args, "--random-plies", defaults.random_plies)?,
+        max_plies: parse_flag(args, "--max-plies", defaults.max_plies)?,
+        seed: parse_flag(args, "--seed", defaults.seed)?,
+        ..defaults
+      };
+      let bot = make_bot_with_config(Box::new(BasicEvaluator), parse_flag(args, "--depth", 1u8)?, plain_search_config());
+      dataset_from_self_play(&bot, config, filter, out)?
+    },
+    _ => return Err(String::from(usage))
+  };
+  print_dataset_stats(&stats);
+  Ok(())
+}
+
+// train-net <dataset> [--epochs N] [--batch N] [--lr X] [--result-weight W] [--validation F]
+//   [--patience N] [--log PATH] [--resume] [--eval-file PATH] [--random-net], trains the network
+//   at the eval file path and keeps the best checkpoint there
+fn train_net_command(args: &[String]) -> Result<(), String> {
+  let path: String = match positional_args(args).into_iter().next() {
+    Some(path) => path,
+    None => return Err(String::from("usage: train-net <dataset> [--epochs N] [--batch N] [--lr X] [--result-weight W] [--validation F] [--patience N] [--log PATH] [--resume] [--eval-file PATH] [--random-net]"))
+  };
+  let defaults: TrainingConfig = default_training_config();
+  let config: TrainingConfig = TrainingConfig {
+    batch_size: parse_flag(args, "--batch", defaults.batch_size)?,
+    learning_rate: parse_flag(args, "--lr", defaults.learning_rate)?,
+    ..defaults
+  };
+  let run_defaults: RunConfig = default_run_config();
+  let run: RunConfig = RunConfig {
+    epochs: parse_flag(args, "--epochs", run_defaults.epochs)?,
+    validation_fraction: parse_flag(args, "--validation", run_defaults.validation_fraction)?,
+    patience: parse_flag(args, "--patience", run_defaults.patience)?,
+    log_path: flag_value(args, "--log").map(|path| path.to_string()),
+    resume: args.iter().any(|arg| arg == "--resume")
+  };
+  let options = network_options(flag_value(args, "--eval-file"), args.iter().any(|arg| arg == "--random-net"));
+  let summary: FitSummary = train_network_on_dataset(&path, config, &run, parse_flag(args, "--result-weight", 0.5f64)?, &options)?;
+  println!("Best loss {:.5} at epoch {}, written to {}", summary.best_loss, summary.best_epoch, options.path);
+  Ok(())
+}
+
+// train-policy <pgn file>... [--epochs N] [--batch N] [--lr X] [--with-value] [--eval-file PATH]
+//   [--random-net], fits the policy head of the network at the eval file path to the moves
+//   played in the games, adding a head to a network without one
+fn train_policy_command(args: &[String]) -> Result<(), String> {
+  let paths: Vec<String> = positional_args(args);
+  if paths.is_empty() {
+    return Err(String::from("usage: train-policy <pgn file>... [--epochs N] [--batch N] [--lr X] [--with-value] [--eval-file PATH] [--random-net]"));
+  }
+  let defaults: TrainingConfig = default_training_config();
+  let config: TrainingConfig = TrainingConfig {
+    batch_size: parse_flag(args, "--batch", defaults.batch_size)?,
+    learning_rate: parse_flag(args, "--lr", defaults.learning_rate)?,
+    ..defaults
+  };
+  let options = network_options(flag_value(args, "--eval-file"), args.iter().any(|arg| arg == "--random-net"));
+  train_policy_on_games(&paths, parse_flag(args, "--epochs", 1usize)?, config, args.iter().any(|arg| arg == "--with-value"), &options)
+}
+
+// td-train [--games N] [--depth D] [--lambda L] [--exploration E] [--generation N]
+//   [--gate-games N] [--gate-threshold X] [--lr X] [--batch N] [--seed S] [--eval-file PATH]
+//   [--random-net], self-play training that promotes the network at the eval file path when
+//   the trained one wins its gating match
+fn td_train_command(args: &[String]) -> Result<(), String> {
+  let defaults: TdConfig = default_td_config();
+  let config: TdConfig = TdConfig {
+    games: parse_flag(args, "--games", defaults.games)?,
+    depth: parse_flag(args, "--depth", defaults.depth)?,
+    lambda: parse_flag(args, "--lambda", defaults.lambda)?,
+    exploration: parse_flag(args, "--exploration", defaults.exploration)?,
+    games_per_generation: parse_flag(args, "--generation", defaults.games_per_generation)?,
+    gate_games: parse_flag(args, "--gate-games", defaults.gate_games)?,
+    gate_threshold: parse_flag(args, "--gate-threshold", defaults.gate_threshold)?,
+    seed: parse_flag(args, "--seed", defaults.seed)?,
+    ..defaults
+  };
+  if !(0.0..=1.0).contains(&config.lambda) {
+    return Err(format!("lambda must be between 0 and 1, not {}", config.lambda));
+  }
+  let training_defaults: TrainingConfig = default_training_config();
+  let training: TrainingConfig = TrainingConfig {
+    batch_size: parse_flag(args, "--batch", training_defaults.batch_size)?,
+    learning_rate: parse_flag(args, "--lr", training_defaults.learning_rate)?,
+    ..training_defaults
+  };
+  let options = network_options(flag_value(args, "--eval-file"), args.iter().any(|arg| arg == "--random-net"));
+  let promotions: usize = train_by_self_play(config, training, &options)?;
+  println!("Promoted {} of {} generations", promotions, config.games / config.games_per_generation.max(1));
+  Ok(())
+}
+
+pub fn run(args: Vec<String>) -> Result<(), String> {
+  match args.first().map(|arg| arg.as_str()) {
+    Some("epd") => epd_command(&args[1..]),
+    Some("bench") => bench_command(&args[1..]),
+    Some("eval") => eval_command(&args[1..]),
+    Some("tune") => tune_command(&args[1..]),
+    Some("uci") => uci_command(&args[1..]),
+    Some("train-nnue") => train_nnue_command(&args[1..]),
+    Some("play") => play_command(&args[1..]),
+    Some("convert-net") => convert_net_command(&args[1..]),
+    Some("dataset") => dataset_command(&args[1..]),
+    Some("train-net") => train_net_command(&args[1..]),
+    Some("td-train") => td_train_command(&args[1..]),
+    Some("train-policy") => train_policy_command(&args[1..]),
+    Some(flag) if flag.starts_with("--") => learn_bot_game_command(&args),
+    Some(command) => Err(format!("unknown command '{}'", command)),
+    None => learn_bot_game_command(&args)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  fn args(text: &str) -> Vec<String> {
+    text.split_whitespace().map(String::from).collect()
+  }
+
+  #[test]
+  fn reads_flag_values() {
+    assert!(flag_value(&args("suite.epd --nodes 500"), "--nodes") == Some("500"));
+    assert!(flag_value(&args("suite.epd --nodes"), "--nodes").is_none());
+    assert!(parse_flag(&args("--depth 3"), "--depth", 4u8) == Ok(3));
+    assert!(parse_flag(&args("--depth x"), "--depth", 4u8).is_err());
+  }
+
+  #[test]
+  fn reads_tuned_params() {
+    let path = std::env::temp_dir().join("command_params.txt");
+    save_eval_params(&default_eval_params(), path.to_str().unwrap()).unwrap();
+    let params: Vec<String> = vec![String::from("--params"), path.to_str().unwrap().to_string()];
+    assert!(eval_params(&params).unwrap() == default_eval_params());
+    assert!(run([vec![String::from("eval")], params].concat()).is_ok());
+  }
+
+  #[test]
+  fn trains_the_nnue_on_a_dataset() {
+    use crate::network::dataset::{create_dataset_file, Sample};
+    let dataset = std::env::temp_dir().join("command_nnue_dataset.bin");
+    let out = std::env::temp_dir().join("command_nnue.bin");
+    let _ = std::fs::remove_file(&out);
+    let mut writer = create_dataset_file(dataset.to_str().unwrap()).unwrap();
+    let (state, turn_number) = state_from_fen("4k3/8/8/3q4/8/8/8/3RK3 w - -").unwrap();
+    writer.write(&Sample { state, turn_number, result: 0.0, score: Some(-8.0), ply: 30 }).unwrap();
+    writer.write(&Sample { state: crate::game::setup_board(), turn_number: 1, result: 0.5, score: None, ply: 0 }).unwrap();
+    writer.finish().unwrap();
+    let command: String = format!("train-nnue {} --out {} --epochs 2 --hidden 8 --head 4", dataset.to_str().unwrap(), out.to_str().unwrap());
+    assert!(run(args(&command)).is_ok());
+    assert!(read_nnue_file(out.to_str().unwrap()).unwrap().hidden == 8);
+    // the weights are there now, so only the same shape can carry on training them
+    assert!(run(args(&command)).is_ok());
+    let reshaped: String = format!("train-nnue {} --out {} --hidden 16", dataset.to_str().unwrap(), out.to_str().unwrap());
+    assert!(run(args(&reshaped)).unwrap_err().contains("already holds"));
+    let _ = std::fs::remove_file(&out);
+    let _ = std::fs::remove_file(&dataset);
+  }
+
+  #[test]
+  fn rejects_unknown_commands() {
+    assert!(run(args("frobnicate")).unwrap_err().contains("unknown command 'frobnicate'"));
+  }
+
+  #[test]
+  fn rejects_bad_epd_arguments() {
+    assert!(run(args("epd")).unwrap_err().contains("usage: epd"));
+    assert!(run(args("epd positions.epd --eval psychic")).unwrap_err().contains("unknown evaluation 'psychic'"));
+  }
+
+  #[test]
+  fn rejects_bad_bench_arguments() {
+    assert!(run(args("bench --eval psychic")).unwrap_err().contains("unknown evaluation 'psychic'"));
+    assert!(run(args("bench --eval nnue:v9-unknown --random-net")).unwrap_err().contains("unknown encoder 'v9-unknown'"));
+    assert!(run(args("bench --eval nnue")).unwrap_err().contains("needs --nnue-file"));
+    assert!(run(args("bench --eval hand-crafted --params no_such_params.txt")).unwrap_err().contains("couldn't read no_such_params.txt"));
+  }
+
+  #[test]
+  fn rejects_bad_eval_arguments() {
+    assert!(run(args("eval not/a/fen w")).unwrap_err().contains("expected 8 ranks"));
+    assert!(run(args("eval --params no_such_params.txt")).unwrap_err().contains("couldn't read no_such_params.txt"));
+  }
+
+  #[test]
+  fn rejects_bad_uci_arguments() {
+    assert!(run(args("uci --params no_such_params.txt")).unwrap_err().contains("couldn't read no_such_params.txt"));
+    assert!(run(args("uci --eval-file no_such_network.txt")).unwrap_err().contains("couldn't read network no_such_network.txt"));
+  }
+
+  #[test]
+  fn rejects_bad_tune_arguments() {
+    assert!(run(args("tune")).unwrap_err().contains("usage: tune"));
+  }
+
+  #[test]
+  fn rejects_bad_train_nnue_arguments() {
+    assert!(run(args("train-nnue")).unwrap_err().contains("usage: train-nnue"));
+    assert!(run(args("train-nnue data.bin --hidden 0")).unwrap_err().contains("must be positive"));
+    assert!(run(args("train-nnue no_such_dataset.bin --out no_such_nnue.bin")).unwrap_err().contains("couldn't open no_such_dataset.bin"));
+  }
+
+  #[test]
+  fn rejects_bad_convert_net_arguments() {
+    assert!(run(args("convert-net only_one.txt")).unwrap_err().contains("usage: convert-net"));
+    assert!(run(args("convert-net a.txt b.bin --precision f16")).unwrap_err().contains("unknown precision 'f16'"));
+  }
+
+  #[test]
+  fn rejects_bad_learn_bot_game_arguments() {
+    assert!(run(args("--eval-file no_such_network.txt")).unwrap_err().contains("couldn't read network no_such_network.txt"));
+    assert!(run(args("--eval psychic")).unwrap_err().contains("not 'psychic'"));
+    assert!(run(args("--eval nnue --nnue-file no_such_nnue.bin")).unwrap_err().contains("couldn't read NNUE no_such_nnue.bin"));
+    assert!(run(args("--black-engine mcts --eval-file no_such_network.txt")).unwrap_err().contains("couldn't read network no_such_network.txt"));
+  }
+
+  #[test]
+  fn rejects_bad_dataset_arguments() {
+    assert!(run(args("dataset pgn only_out.bin")).unwrap_err().contains("usage: dataset"));
+    assert!(run(args("dataset shuffle out.bin")).unwrap_err().contains("usage: dataset"));
+  }
+
+  #[test]
+  fn rejects_bad_train_net_arguments() {
+    assert!(run(args("train-net")).unwrap_err().contains("usage: train-net"));
+    assert!(run(args("train-net no_such_dataset.bin --random-net --eval-file no_such_network.txt")).unwrap_err().contains("couldn't open no_such_dataset.bin"));
+  }
+
+  #[test]
+  fn rejects_bad_td_train_arguments() {
+    assert!(run(args("td-train --lambda 1.5")).unwrap_err().contains("lambda must be between 0 and 1"));
+    assert!(run(args("td-train --games many")).unwrap_err().contains("invalid value 'many' for --games"));
+  }
+
+  #[test]
+  fn rejects_bad_train_policy_arguments() {
+    assert!(run(args("train-policy")).unwrap_err().contains("usage: train-policy"));
+    assert!(run(args("train-policy no_such_games.pgn --random-net --eval-file no_such_network.txt")).unwrap_err().contains("couldn't read no_such_games.pgn"));
+  }
+
+  #[test]
+  fn rejects_bad_play_arguments() {
+    assert!(run(args("play")).unwrap_err().contains("usage: play"));
+    assert!(run(args("play chess960")).unwrap_err().contains("usage: play"));
+    assert!(run(args("play one-bot --params no_such_params.txt")).unwrap_err().contains("couldn't read no_such_params.txt"));
+    assert!(run(args("play one-bot --eval nnue")).unwrap_err().contains("needs --nnue-file"));
+    assert!(run(args("play learn-bot --eval nnue --nnue-file no_such_nnue.bin")).unwrap_err().contains("couldn't read NNUE no_such_nnue.bin"));
+    assert!(run(args("play two-bot --engine alphazero")).unwrap_err().contains("unknown engine 'alphazero'"));
+    assert!(run(args("play one-bot --engine mcts --playouts lots")).unwrap_err().contains("invalid value 'lots' for --playouts"));
+  }
+}
@@ -0,0 +1,122 @@
+use crate::fen::state_from_fen;
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct EpdEntry {
+  pub state: [u64; 13],
+  pub turn_number: u8,
+  // "bm": any of these moves solves the position
+  pub best_moves: Vec<String>,
+  // "am": playing any of these moves fails the position
+  pub avoid_moves: Vec<String>,
+  pub id: String
+}
+
+// splits the operations on semicolons that aren't inside a quoted string
+fn split_operations(text: &str) -> Vec<String> {
+  let mut operations: Vec<String> = Vec::new();
+  let mut current: String = String::new();
+  let mut quoted: bool = false;
+  for ch in text.chars() {
+    match ch {
+      '"' => {
+        quoted = !quoted;
+        current.push(ch);
+      },
+      ';' if !quoted => {
+        operations.push(current.trim().to_string());
+        current = String::new();
+      },
+      _ => current.push(ch)
+    }
+  }
+  if !current.trim().is_empty() {
+    operations.push(current.trim().to_string());
+  }
+  operations.into_iter().filter(|operation| !operation.is_empty()).collect()
+}
+
+pub fn parse_epd_line(line: &str) -> Result<EpdEntry, String> {
+  let fields: Vec<&str> = line.split_whitespace().collect();
+  if fields.len() < 4 {
+    return Err(format!("'{}' needs four position fields", line));
+  }
+  let (state, turn_number) = state_from_fen(&fields[0..4].join(" "))?;
+
+  // the operations start after the fourth field
+  let mut rest: &str = line.trim_start();
+  for _field in 0..4 {
+    rest = rest.trim_start();
+    rest = &rest[rest.find(char::is_whitespace).unwrap_or(rest.len())..];
+  }
+
+  let mut entry: EpdEntry = EpdEntry {
+    state,
+    turn_number,
+    best_moves: Vec::new(),
+    avoid_moves: Vec::new(),
+    id: String::new()
+  };
+  for operation in split_operations(rest) {
+    let (opcode, operands) = match operation.find(char::is_whitespace) {
+      Some(index) => (&operation[..index], operation[index..].trim()),
+      None => (&operation[..], "")
+    };
+    match opcode {
+      "bm" => entry.best_moves = operands.split_whitespace().map(String::from).collect(),
+      "am" => entry.avoid_moves = operands.split_whitespace().map(String::from).collect(),
+      "id" => entry.id = operands.trim_matches('"').to_string(),
+      _ => {}
+    }
+  }
+  if entry.best_moves.is_empty() && entry.avoid_moves.is_empty() {
+    return Err(format!("'{}' has neither a bm nor an am operation", line));
+  }
+  Ok(entry)
+}
+
+pub fn parse_epd(text: &str) -> Result<Vec<EpdEntry>, String> {
+  text.lines()
+      .map(|line| line.trim())
+      .filter(|line| !line.is_empty() && !line.starts_with('#'))
+      .enumerate()
+      .map(|(index, line)| parse_epd_line(line).map_err(|error| format!("line {}: {}", index + 1, error)))
+      .collect()
+}
+
+pub fn read_epd_file(path: &str) -> Result<Vec<EpdEntry>, String> {
+  match std::fs::read_to_string(path) {
+    Ok(text) => parse_epd(&text),
+    Err(error) => Err(format!("couldn't read {}: {}", path, error))
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::constants::*;
+
+  #[test]
+  fn parses_best_move_and_id() {
+    let entry: EpdEntry = parse_epd_line("7k/5ppp/8/8/8/8/8/R1K5 w - - bm Ra8#; id \"back rank; mate\";").unwrap();
+    assert!(entry.best_moves == vec!["Ra8#".to_string()]);
+    assert!(entry.avoid_moves.is_empty());
+    assert!(entry.id == "back rank; mate");
+    assert!(entry.state[WROOK as usize] == 1);
+    assert!(entry.turn_number == 1);
+  }
+
+  #[test]
+  fn parses_several_avoid_moves() {
+    let entry: EpdEntry = parse_epd_line("7k/5ppp/8/8/8/8/8/R1K5 b - - am g6 h6; id \"x\";").unwrap();
+    assert!(entry.avoid_moves == vec!["g6".to_string(), "h6".to_string()]);
+    assert!(entry.turn_number.is_multiple_of(2));
+  }
+
+  #[test]
+  fn skips_comments_and_reports_bad_lines() {
+    let entries: Vec<EpdEntry> = parse_epd("# suite\n\n7k/5ppp/8/8/8/8/8/R1K5 w - - bm Ra8;\n").unwrap();
+    assert!(entries.len() == 1);
+    assert!(parse_epd("7k/5ppp/8/8/8/8/8/R1K5 w - - id \"no moves\";").is_err());
+    assert!(parse_epd("7k/5ppp w").is_err());
+  }
+}
@@ -0,0 +1,151 @@
+use super::params::{EvalParams, PIECE_KINDS};
+use super::phase::side_phase;
+use super::score::TaperedScore;
+use super::squares_of;
+use crate::attacks::single_piece_attacks;
+use crate::board::get_all_occupation;
+use crate::constants::*;
+use crate::mask_for_square::for_file;
+
+// the king's square and every square next to it
+fn king_zone(king_square: usize) -> u64 {
+  single_piece_attacks(WKING, 1 << king_square, 0) | (1 << king_square)
+}
+
+// ranks between the king and the closest pawn in front of it, from the king's side
+fn nearest_pawn_ahead(pawns: u64, king_square: usize, white: bool) -> Option<usize> {
+  let king_rank: i32 = (king_square / 8) as i32;
+  squares_of(pawns)
+    .map(|square| if white { (square / 8) as i32 - king_rank } else { king_rank - (square / 8) as i32 })
+    .filter(|distance| *distance > 0)
+    .min()
+    .map(|distance| distance as usize)
+}
+
+// pawn shield, pawn storm and open files on the king's file and the two beside it
+fn shelter(state: [u64; 13], king_square: usize, white: bool, params: &EvalParams) -> TaperedScore {
+  let mut score: TaperedScore = TaperedScore::default();
+  let (own_pawns, enemy_pawns) = if white {
+    (state[WPAWN as usize], state[BPAWN as usize])
+  } else {
+    (state[BPAWN as usize], state[WPAWN as usize])
+  };
+  // a king on the edge still looks at three files
+  let centre_file: usize = (king_square % 8).clamp(1, 6);
+
+  for file in centre_file - 1..=centre_file + 1 {
+    let own: u64 = own_pawns & for_file(file as u8);
+    let enemy: u64 = enemy_pawns & for_file(file as u8);
+    score += match nearest_pawn_ahead(own, king_square, white) {
+      Some(distance) if distance <= 2 => params.pawn_shield[distance],
+      _ => params.pawn_shield[0]
+    };
+    if let Some(distance) = nearest_pawn_ahead(enemy, king_square, white) {
+      if distance <= 3 {
+        score += params.pawn_storm[distance];
+      }
+    }
+    if own == 0 {
+      score += if enemy == 0 { params.king_open_file } else { params.king_semi_open_file };
+    }
+  }
+  score
+}
+
+// grows with the square of the attack units once two or more enemy pieces hit the king zone
+fn king_attack(state: [u64; 13], king_square: usize, white: bool, params: &EvalParams) -> TaperedScore {
+  let zone: u64 = king_zone(king_square);
+  let occ: u64 = get_all_occupation(state);
+  let first_enemy_slice: usize = if white { PIECE_KINDS } else { 0 };
+  let mut attackers: u32 = 0;
+  let mut units: f64 = 0.0;
+
+  for kind in 0..PIECE_KINDS {
+    let weight: f64 = params.king_attack_units[kind];
+    if weight == 0.0 {
+      continue;
+    }
+    let slice_index: usize = first_enemy_slice + kind;
+    for square in squares_of(state[slice_index]) {
+      let hits: u64 = single_piece_attacks(slice_index as u8, 1 << square, occ) & zone;
+      if hits != 0 {
+        attackers += 1;
+        units += weight * hits.count_ones() as f64;
+      }
+    }
+  }
+
+  if attackers < 2 {
+    return TaperedScore::default();
+  }
+  let units: f64 = units.min(params.king_attack_unit_cap);
+  params.king_danger * (units * units)
+}
+
+// a king only needs shelter while the other side has pieces to attack it with
+pub fn side_king_safety(state: [u64; 13], white: bool, params: &EvalParams) -> TaperedScore {
+  let king: u64 = if white { state[WKING as usize] } else { state[BKING as usize] };
+  if king == 0 {
+    return TaperedScore::default();
+  }
+  let king_square: usize = king.trailing_zeros() as usize;
+  (shelter(state, king_square, white, params) + king_attack(state, king_square, white, params))
+    * side_phase(state, !white)
+}
+
+// white's king safety minus black's
+pub fn king_safety(state: [u64; 13], params: &EvalParams) -> TaperedScore {
+  side_king_safety(state, true, params) - side_king_safety(state, false, params)
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use super::super::params::DEFAULT_EVAL_PARAMS;
+  use crate::fen::state_from_fen;
+
+  fn safety(fen: &str) -> TaperedScore {
+    king_safety(state_from_fen(fen).unwrap().0, &DEFAULT_EVAL_PARAMS)
+  }
+
+  #[test]
+  fn starting_position_is_balanced() {
+    let score: TaperedScore = safety("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w - -");
+    assert!(score.mg.abs() < 1e-9 && score.eg.abs() < 1e-9);
+  }
+
+  #[test]
+  fn intact_shield_beats_pushed_pawns() {
+    let intact: TaperedScore = safety("rnbq1rk1/pppppppp/8/8/8/8/PPPPPPPP/RNBQ1RK1 w - -");
+    let pushed: TaperedScore = safety("rnbq1rk1/pppppppp/8/8/6P1/7P/PPPPPP2/RNBQ1RK1 w - -");
+    assert!(intact.mg > pushed.mg);
+  }
+
+  #[test]
+  fn open_file_next_to_the_king_hurts() {
+    // the same pawn is missing in both, but only one leaves the king's file half open
+    let far: TaperedScore = safety("rnbq1rk1/pppppppp/8/8/8/8/1PPPPPPP/RNBQ1RK1 w - -");
+    let open: TaperedScore = safety("rnbq1rk1/pppppppp/8/8/8/8/PPPPPP1P/RNBQ1RK1 w - -");
+    assert!(open.mg < far.mg);
+  }
+
+  #[test]
+  fn lone_attacker_does_not_count() {
+    let state: [u64; 13] = state_from_fen("6k1/8/8/8/8/8/5PPP/3q2K1 w - -").unwrap().0;
+    assert!(king_attack(state, 6, true, &DEFAULT_EVAL_PARAMS) == TaperedScore::default());
+  }
+
+  #[test]
+  fn two_attackers_raise_the_danger() {
+    let state: [u64; 13] = state_from_fen("6k1/8/8/8/8/5n2/5PPP/3q2K1 w - -").unwrap().0;
+    assert!(king_attack(state, 6, true, &DEFAULT_EVAL_PARAMS).mg < 0.0);
+  }
+
+  #[test]
+  fn danger_fades_as_the_attacker_trades_pieces() {
+    let armed: TaperedScore = safety("rnbqkbnr/pppppppp/8/8/8/8/8/4K3 w - -");
+    let disarmed: TaperedScore = safety("4k3/pppppppp/8/8/8/8/8/4K3 w - -");
+    assert!(armed.mg < disarmed.mg);
+    assert!(disarmed.mg.abs() < 1e-9);
+  }
+}
@@ -0,0 +1,150 @@
+use super::params::{EvalParams, PIECE_KINDS, ROOK, BISHOP, QUEEN, KNIGHT};
+use super::pawns::{adjacent_files, ranks_ahead, relative_rank};
+use super::score::TaperedScore;
+use super::squares_of;
+use crate::attacks::{single_piece_attacks, white_pawn_attacks, black_pawn_attacks};
+use crate::board::{get_all_occupation, get_white_occupation, get_black_occupation};
+use crate::constants::*;
+use crate::mask_for_square::{for_file, for_rank};
+
+// safe squares a piece of each kind usually has, so mobility scores around zero
+const TYPICAL_MOBILITY: [f64; PIECE_KINDS] = [7.0, 7.0, 14.0, 0.0, 4.0, 0.0];
+
+// the pieces mobility and the trapped penalty look at
+const MOBILE_KINDS: [usize; 4] = [KNIGHT, BISHOP, ROOK, QUEEN];
+
+fn rook_bonuses(square: usize, own_pawns: u64, enemy_pawns: u64, enemy_king: u64, white: bool, params: &EvalParams) -> TaperedScore {
+  let mut score: TaperedScore = TaperedScore::default();
+  let file: u64 = for_file((square % 8) as u8);
+  if own_pawns & file == 0 {
+    score += if enemy_pawns & file == 0 { params.rook_open_file } else { params.rook_semi_open_file };
+  }
+  // the seventh only matters while there are pawns to eat there or a king to cut off behind it
+  let seventh: u64 = for_rank(if white { 6 } else { 1 });
+  let eighth: u64 = for_rank(if white { 7 } else { 0 });
+  if relative_rank(square, white) == 6 && (enemy_pawns & seventh != 0 || enemy_king & eighth != 0) {
+    score += params.rook_on_seventh;
+  }
+  score
+}
+
+// a knight on the enemy's half, guarded by a pawn, that no enemy pawn can ever chase away
+fn is_outpost(square: usize, own_pawn_attacks: u64, enemy_pawns: u64, white: bool) -> bool {
+  let rank: usize = relative_rank(square, white);
+  (3..=5).contains(&rank)
+    && own_pawn_attacks & (1 << square) != 0
+    && enemy_pawns & adjacent_files(square % 8) & ranks_ahead(square / 8, white) == 0
+}
+
+pub fn side_activity(state: [u64; 13], white: bool, params: &EvalParams) -> TaperedScore {
+  let mut score: TaperedScore = TaperedScore::default();
+  let first_slice: usize = if white { 0 } else { PIECE_KINDS };
+  let occ: u64 = get_all_occupation(state);
+  let own_pieces: u64 = if white { get_white_occupation(state) } else { get_black_occupation(state) };
+  let (own_pawns, enemy_pawns, enemy_king) = if white {
+    (state[WPAWN as usize], state[BPAWN as usize], state[BKING as usize])
+  } else {
+    (state[BPAWN as usize], state[WPAWN as usize], state[WKING as usize])
+  };
+  let own_pawn_attacks: u64 = if white { white_pawn_attacks(own_pawns) } else { black_pawn_attacks(own_pawns) };
+  let enemy_pawn_attacks: u64 = if white { black_pawn_attacks(enemy_pawns) } else { white_pawn_attacks(enemy_pawns) };
+  let safe: u64 = !own_pieces & !enemy_pawn_attacks;
+
+  for kind in MOBILE_KINDS {
+    let slice_index: usize = first_slice + kind;
+    for square in squares_of(state[slice_index]) {
+      let reachable: u32 = (single_piece_attacks(slice_index as u8, 1 << square, occ) & safe).count_ones();
+      score += params.mobility[kind] * (reachable as f64 - TYPICAL_MOBILITY[kind]);
+      if reachable == 0 {
+        score += params.trapped_piece[kind];
+      }
+      if kind == ROOK {
+        score += rook_bonuses(square, own_pawns, enemy_pawns, enemy_king, white, params);
+      }
+      if kind == KNIGHT && is_outpost(square, own_pawn_attacks, enemy_pawns, white) {
+        score += params.knight_outpost;
+      }
+    }
+  }
+
+  if state[first_slice + BISHOP].count_ones() >= 2 {
+    score += params.bishop_pair;
+  }
+  score
+}
+
+// mobility and piece activity, white minus black
+pub fn piece_activity(state: [u64; 13], params: &EvalParams) -> TaperedScore {
+  side_activity(state, true, params) - side_activity(state, false, params)
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use super::super::params::DEFAULT_EVAL_PARAMS;
+  use crate::fen::state_from_fen;
+
+  fn activity(fen: &str) -> TaperedScore {
+    piece_activity(state_from_fen(fen).unwrap().0, &DEFAULT_EVAL_PARAMS)
+  }
+
+  #[test]
+  fn starting_position_is_balanced() {
+    let score: TaperedScore = activity("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w - -");
+    assert!(score.mg.abs() < 1e-9 && score.eg.abs() < 1e-9);
+  }
+
+  #[test]
+  fn centralised_knight_is_more_mobile() {
+    let centre: TaperedScore = activity("4k3/8/8/8/3N4/8/8/4K3 w - -");
+    let corner: TaperedScore = activity("4k3/8/8/8/8/8/8/N3K3 w - -");
+    assert!(centre.mg > corner.mg);
+  }
+
+  #[test]
+  fn squares_hit_by_enemy_pawns_are_not_safe() {
+    let free: TaperedScore = activity("4k3/8/8/8/3N4/8/8/4K3 w - -");
+    let watched: TaperedScore = activity("4k3/8/2p5/3p4/3N4/8/8/4K3 w - -");
+    assert!(watched.mg < free.mg);
+  }
+
+  #[test]
+  fn rook_prefers_open_files() {
+    let open: TaperedScore = activity("4k3/p7/8/8/8/8/P3R3/4K3 w - -");
+    let semi_open: TaperedScore = activity("4k3/4p3/8/8/8/8/P3R3/4K3 w - -");
+    let closed: TaperedScore = activity("4k3/p7/8/8/4P3/8/4R3/4K3 w - -");
+    assert!(open.mg > semi_open.mg);
+    assert!(semi_open.mg > closed.mg);
+  }
+
+  #[test]
+  fn rook_on_seventh_with_targets() {
+    let state: [u64; 13] = state_from_fen("6k1/R4ppp/8/8/8/8/8/6K1 w - -").unwrap().0;
+    let bonus: TaperedScore = rook_bonuses(48, 0, state[BPAWN as usize], state[BKING as usize], true, &DEFAULT_EVAL_PARAMS);
+    assert!(bonus == DEFAULT_EVAL_PARAMS.rook_open_file + DEFAULT_EVAL_PARAMS.rook_on_seventh);
+  }
+
+  #[test]
+  fn counts_the_bishop_pair() {
+    let pair: TaperedScore = activity("4k3/8/8/8/8/8/8/2B1KB2 w - -");
+    let one: TaperedScore = activity("4k3/8/8/8/8/8/8/2B1K3 w - -");
+    assert!(pair.mg - one.mg > DEFAULT_EVAL_PARAMS.bishop_pair.mg - 1e-9);
+  }
+
+  #[test]
+  fn finds_knight_outposts() {
+    // e5 is guarded by d4 and no black pawn on the d or f file can challenge it
+    assert!(is_outpost(36, white_pawn_attacks(1 << 27), 1 << 48, true));
+    assert!(!is_outpost(36, white_pawn_attacks(1 << 27), 1 << 53, true));
+    assert!(!is_outpost(36, 0, 1 << 48, true));
+  }
+
+  #[test]
+  fn boxed_in_piece_is_trapped() {
+    let state: [u64; 13] = state_from_fen("4k3/8/8/8/8/1P6/PPP5/BK6 w - -").unwrap().0;
+    let score: TaperedScore = side_activity(state, true, &DEFAULT_EVAL_PARAMS);
+    let expected: TaperedScore = DEFAULT_EVAL_PARAMS.trapped_piece[BISHOP]
+      + DEFAULT_EVAL_PARAMS.mobility[BISHOP] * -TYPICAL_MOBILITY[BISHOP];
+    assert!((score.mg - expected.mg).abs() < 1e-9);
+  }
+}
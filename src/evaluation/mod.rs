@@ -0,0 +1,96 @@
+mod score;
+pub mod params;
+pub mod phase;
+mod psqt;
+pub mod pawns;
+mod king_safety;
+mod mobility;
+mod trace;
+
+pub use score::{tapered, TaperedScore};
+pub use trace::{eval_trace, eval_trace_with_params, format_eval_trace, print_eval_trace, EvalTrace, TraceTerm};
+use params::{EvalParams, DEFAULT_EVAL_PARAMS};
+
+// indices of the set bits of a bitboard, lowest first
+pub struct Squares(u64);
+
+impl Iterator for Squares {
+  type Item = usize;
+  fn next(&mut self) -> Option<usize> {
+    if self.0 == 0 {
+      return None;
+    }
+    let square: usize = self.0.trailing_zeros() as usize;
+    self.0 &= self.0 - 1;
+    Some(square)
+  }
+}
+
+pub fn squares_of(board: u64) -> Squares {
+  Squares(board)
+}
+
+// middlegame and endgame totals of every term, white minus black
+pub fn tapered_terms(state: [u64; 13], params: &EvalParams) -> TaperedScore {
+  psqt::material_and_placement(state, params)
+    + pawns::pawn_structure(state, params)
+    + king_safety::king_safety(state, params)
+    + mobility::piece_activity(state, params)
+}
+
+pub fn evaluate_with_params(state: [u64; 13], params: &EvalParams) -> f64 {
+  tapered_terms(state, params).taper(phase::game_phase(state))
+}
+
+// `evaluate_with_params` with the pawn structure looked up in `table`
+pub fn evaluate_with_pawn_table(state: [u64; 13], params: &EvalParams, table: &mut pawns::PawnHashTable) -> f64 {
+  let terms: TaperedScore = psqt::material_and_placement(state, params)
+    + pawns::pawn_structure_with_table(state, params, table)
+    + king_safety::king_safety(state, params)
+    + mobility::piece_activity(state, params);
+  terms.taper(phase::game_phase(state))
+}
+
+// tapered hand-crafted evaluation, in pawns from white's side. pawn structure
+// comes out of the shared pawn hash table.
+pub fn hand_crafted_eval(state: [u64; 13]) -> f64 {
+  evaluate_with_pawn_table(state, &DEFAULT_EVAL_PARAMS, &mut pawns::PAWN_HASH.lock().unwrap())
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::constants::*;
+
+  #[test]
+  fn iterates_set_squares() {
+    assert!(squares_of(0x8000000000000101).collect::<Vec<usize>>() == vec![0, 8, 63]);
+    assert!(squares_of(0).next().is_none());
+  }
+
+  #[test]
+  fn cached_eval_matches_uncached_eval() {
+    let state: [u64; 13] = crate::fen::state_from_fen("r1bqk2r/pp3ppp/2n5/3p4/1b1P4/2N2N2/PP3PPP/R2QKB1R w - -").unwrap().0;
+    assert!((hand_crafted_eval(state) - evaluate_with_params(state, &DEFAULT_EVAL_PARAMS)).abs() < 1e-9);
+  }
+
+  #[test]
+  fn kings_centralise_in_the_endgame() {
+    let mut corner: [u64; 13] = [0; 13];
+    corner[WKING as usize] = 1;
+    corner[BKING as usize] = 1 << 63;
+    let mut centre: [u64; 13] = corner;
+    centre[WKING as usize] = 1 << 27;
+    assert!(hand_crafted_eval(centre) > hand_crafted_eval(corner));
+  }
+
+  #[test]
+  fn kings_stay_home_in_the_middlegame() {
+    let mut home: [u64; 13] = crate::game::setup_board();
+    home[WKNIGHT as usize] &= !(1 << 6);
+    home[WKING as usize] = 1 << 6;
+    let mut centre: [u64; 13] = home;
+    centre[WKING as usize] = 1 << 27;
+    assert!(hand_crafted_eval(home) > hand_crafted_eval(centre));
+  }
+}
@@ -0,0 +1,444 @@
+use super::score::{tapered, TaperedScore};
+
+// tables are indexed by piece kind in slice order: rook, bishop, queen, pawn, knight, king
+pub const PIECE_KINDS: usize = 6;
+pub const ROOK: usize = 0;
+pub const BISHOP: usize = 1;
+pub const QUEEN: usize = 2;
+pub const PAWN: usize = 3;
+pub const KNIGHT: usize = 4;
+pub const KING: usize = 5;
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct EvalParams {
+  pub material: [TaperedScore; PIECE_KINDS],
+  // written from white's side with rank 8 first, so a white piece on square s reads entry s ^ 56
+  pub piece_square: [[TaperedScore; 64]; PIECE_KINDS],
+  // pawn structure, per pawn
+  pub doubled_pawn: TaperedScore,
+  pub isolated_pawn: TaperedScore,
+  pub backward_pawn: TaperedScore,
+  // by rank from the pawn's own side
+  pub connected_pawn: [TaperedScore; 8],
+  pub passed_pawn: [TaperedScore; 8],
+  // passed pawn terms scaled by how far the pawn has advanced
+  pub passed_pawn_blocked: TaperedScore,
+  // per square between the king and the pawn's stop square
+  pub passed_pawn_own_king_distance: TaperedScore,
+  pub passed_pawn_enemy_king_distance: TaperedScore,
+  // king safety, per file next to the king. the shield is indexed by how far in front of
+  // the king the nearest own pawn stands (0 when there is none), the storm by how far the
+  // nearest enemy pawn is (0 when none is within three ranks)
+  pub pawn_shield: [TaperedScore; 3],
+  pub pawn_storm: [TaperedScore; 4],
+  pub king_open_file: TaperedScore,
+  pub king_semi_open_file: TaperedScore,
+  // attack units for every king zone square a piece of each kind hits
+  pub king_attack_units: [f64; PIECE_KINDS],
+  // per attack unit squared, capped at `king_attack_unit_cap` units
+  pub king_danger: TaperedScore,
+  pub king_attack_unit_cap: f64,
+  // per safe square above or below the usual count for the piece kind
+  pub mobility: [TaperedScore; PIECE_KINDS],
+  pub rook_open_file: TaperedScore,
+  pub rook_semi_open_file: TaperedScore,
+  pub rook_on_seventh: TaperedScore,
+  pub bishop_pair: TaperedScore,
+  pub knight_outpost: TaperedScore,
+  // pieces left without a single safe square
+  pub trapped_piece: [TaperedScore; PIECE_KINDS]
+}
+
+// centipawns, rank 8 first
+type CentipawnTable = [i16; 64];
+
+const PAWN_MG: CentipawnTable = [
+    0,   0,   0,   0,   0,   0,   0,   0,
+   50,  50,  50,  50,  50,  50,  50,  50,
+   10,  10,  20,  30,  30,  20,  10,  10,
+    5,   5,  10,  25,  25,  10,   5,   5,
+    0,   0,   0,  20,  20,   0,   0,   0,
+    5,  -5, -10,   0,   0, -10,  -5,   5,
+    5,  10,  10, -20, -20,  10,  10,   5,
+    0,   0,   0,   0,   0,   0,   0,   0
+];
+
+const PAWN_EG: CentipawnTable = [
+    0,   0,   0,   0,   0,   0,   0,   0,
+   90,  90,  90,  90,  90,  90,  90,  90,
+   55,  55,  55,  55,  55,  55,  55,  55,
+   30,  30,  30,  30,  30,  30,  30,  30,
+   15,  15,  15,  15,  15,  15,  15,  15,
+    5,   5,   5,   5,   5,   5,   5,   5,
+    0,   0,   0,   0,   0,   0,   0,   0,
+    0,   0,   0,   0,   0,   0,   0,   0
+];
+
+const KNIGHT_MG: CentipawnTable = [
+  -50, -40, -30, -30, -30, -30, -40, -50,
+  -40, -20,   0,   0,   0,   0, -20, -40,
+  -30,   0,  10,  15,  15,  10,   0, -30,
+  -30,   5,  15,  20,  20,  15,   5, -30,
+  -30,   0,  15,  20,  20,  15,   0, -30,
+  -30,   5,  10,  15,  15,  10,   5, -30,
+  -40, -20,   0,   5,   5,   0, -20, -40,
+  -50, -40, -30, -30, -30, -30, -40, -50
+];
+
+const KNIGHT_EG: CentipawnTable = [
+  -40, -30, -20, -20, -20, -20, -30, -40,
+  -30, -15,   0,   0,   0,   0, -15, -30,
+  -20,   0,  10,  12,  12,  10,   0, -20,
+  -20,   0,  12,  15,  15,  12,   0, -20,
+  -20,   0,  12,  15,  15,  12,   0, -20,
+  -20,   0,  10,  12,  12,  10,   0, -20,
+  -30, -15,   0,   0,   0,   0, -15, -30,
+  -40, -30, -20, -20, -20, -20, -30, -40
+];
+
+const BISHOP_MG: CentipawnTable = [
+  -20, -10, -10, -10, -10, -10, -10, -20,
+  -10,   0,   0,   0,   0,   0,   0, -10,
+  -10,   0,   5,  10,  10,   5,   0, -10,
+  -10,   5,   5,  10,  10,   5,   5, -10,
+  -10,   0,  10,  10,  10,  10,   0, -10,
+  -10,  10,  10,  10,  10,  10,  10, -10,
+  -10,   5,   0,   0,   0,   0,   5, -10,
+  -20, -10, -10, -10, -10, -10, -10, -20
+];
+
+const BISHOP_EG: CentipawnTable = [
+  -15, -10,  -8,  -5,  -5,  -8, -10, -15,
+  -10,  -5,   0,   0,   0,   0,  -5, -10,
+   -8,   0,   5,   5,   5,   5,   0,  -8,
+   -5,   0,   5,  10,  10,   5,   0,  -5,
+   -5,   0,   5,  10,  10,   5,   0,  -5,
+   -8,   0,   5,   5,   5,   5,   0,  -8,
+  -10,  -5,   0,   0,   0,   0,  -5, -10,
+  -15, -10,  -8,  -5,  -5,  -8, -10, -15
+];
+
+const ROOK_MG: CentipawnTable = [
+    0,   0,   0,   0,   0,   0,   0,   0,
+    5,  10,  10,  10,  10,  10,  10,   5,
+   -5,   0,   0,   0,   0,   0,   0,  -5,
+   -5,   0,   0,   0,   0,   0,   0,  -5,
+   -5,   0,   0,   0,   0,   0,   0,  -5,
+   -5,   0,   0,   0,   0,   0,   0,  -5,
+   -5,   0,   0,   0,   0,   0,   0,  -5,
+    0,   0,   0,   5,   5,   0,   0,   0
+];
+
+const ROOK_EG: CentipawnTable = [
+    5,   5,   5,   5,   5,   5,   5,   5,
+   10,  10,  10,  10,  10,  10,  10,  10,
+    0,   0,   0,   0,   0,   0,   0,   0,
+    0,   0,   0,   0,   0,   0,   0,   0,
+    0,   0,   0,   0,   0,   0,   0,   0,
+    0,   0,   0,   0,   0,   0,   0,   0,
+    0,   0,   0,   0,   0,   0,   0,   0,
+   -5,   0,   0,   0,   0,   0,   0,  -5
+];
+
+const QUEEN_MG: CentipawnTable = [
+  -20, -10, -10,  -5,  -5, -10, -10, -20,
+  -10,   0,   0,   0,   0,   0,   0, -10,
+  -10,   0,   5,   5,   5,   5,   0, -10,
+   -5,   0,   5,   5,   5,   5,   0,  -5,
+    0,   0,   5,   5,   5,   5,   0,  -5,
+  -10,   5,   5,   5,   5,   5,   0, -10,
+  -10,   0,   5,   0,   0,   0,   0, -10,
+  -20, -10, -10,  -5,  -5, -10, -10, -20
+];
+
+const QUEEN_EG: CentipawnTable = [
+  -20, -10, -10,  -5,  -5, -10, -10, -20,
+  -10,   0,   5,   5,   5,   5,   0, -10,
+  -10,   5,  10,  10,  10,  10,   5, -10,
+   -5,   5,  10,  15,  15,  10,   5,  -5,
+   -5,   5,  10,  15,  15,  10,   5,  -5,
+  -10,   5,  10,  10,  10,  10,   5, -10,
+  -10,   0,   5,   5,   5,   5,   0, -10,
+  -20, -10, -10,  -5,  -5, -10, -10, -20
+];
+
+const KING_MG: CentipawnTable = [
+  -30, -40, -40, -50, -50, -40, -40, -30,
+  -30, -40, -40, -50, -50, -40, -40, -30,
+  -30, -40, -40, -50, -50, -40, -40, -30,
+  -30, -40, -40, -50, -50, -40, -40, -30,
+  -20, -30, -30, -40, -40, -30, -30, -20,
+  -10, -20, -20, -20, -20, -20, -20, -10,
+   20,  20,   0,   0,   0,   0,  20,  20,
+   20,  30,  10,   0,   0,  10,  30,  20
+];
+
+const KING_EG: CentipawnTable = [
+  -50, -40, -30, -20, -20, -30, -40, -50,
+  -30, -20, -10,   0,   0, -10, -20, -30,
+  -30, -10,  20,  30,  30,  20, -10, -30,
+  -30, -10,  30,  40,  40,  30, -10, -30,
+  -30, -10,  30,  40,  40,  30, -10, -30,
+  -30, -10,  20,  30,  30,  20, -10, -30,
+  -30, -30,   0,   0,   0,   0, -30, -30,
+  -50, -30, -30, -30, -30, -30, -30, -50
+];
+
+fn tapered_table(mg: &CentipawnTable, eg: &CentipawnTable) -> [TaperedScore; 64] {
+  let mut table: [TaperedScore; 64] = [TaperedScore::default(); 64];
+  for (entry, (mg_value, eg_value)) in table.iter_mut().zip(mg.iter().zip(eg.iter())) {
+    *entry = tapered(*mg_value as f64 / 100.0, *eg_value as f64 / 100.0);
+  }
+  table
+}
+
+pub fn default_eval_params() -> EvalParams {
+  EvalParams {
+    // the king keeps the 50 points `basic_eval` gives it, since losing it is how games end here
+    material: [
+      tapered(4.77, 5.12),
+      tapered(3.65, 2.97),
+      tapered(10.25, 9.36),
+      tapered(0.82, 0.94),
+      tapered(3.37, 2.81),
+      tapered(50.0, 50.0)
+    ],
+    piece_square: [
+      tapered_table(&ROOK_MG, &ROOK_EG),
+      tapered_table(&BISHOP_MG, &BISHOP_EG),
+      tapered_table(&QUEEN_MG, &QUEEN_EG),
+      tapered_table(&PAWN_MG, &PAWN_EG),
+      tapered_table(&KNIGHT_MG, &KNIGHT_EG),
+      tapered_table(&KING_MG, &KING_EG)
+    ],
+    doubled_pawn: tapered(-0.10, -0.20),
+    isolated_pawn: tapered(-0.10, -0.15),
+    backward_pawn: tapered(-0.08, -0.10),
+    connected_pawn: [
+      tapered(0.0, 0.0),
+      tapered(0.02, 0.01),
+      tapered(0.04, 0.03),
+      tapered(0.06, 0.05),
+      tapered(0.10, 0.08),
+      tapered(0.16, 0.14),
+      tapered(0.25, 0.22),
+      tapered(0.0, 0.0)
+    ],
+    passed_pawn: [
+      tapered(0.0, 0.0),
+      tapered(0.02, 0.10),
+      tapered(0.05, 0.15),
+      tapered(0.10, 0.25),
+      tapered(0.20, 0.45),
+      tapered(0.35, 0.75),
+      tapered(0.60, 1.10),
+      tapered(0.0, 0.0)
+    ],
+    passed_pawn_blocked: tapered(-0.05, -0.25),
+    passed_pawn_own_king_distance: tapered(0.0, -0.05),
+    passed_pawn_enemy_king_distance: tapered(0.0, 0.10),
+    pawn_shield: [
+      tapered(-0.25, -0.05),
+      tapered(0.10, 0.0),
+      tapered(0.05, 0.0)
+    ],
+    pawn_storm: [
+      tapered(0.0, 0.0),
+      tapered(-0.05, 0.0),
+      tapered(-0.20, -0.05),
+      tapered(-0.10, -0.02)
+    ],
+    king_open_file: tapered(-0.25, -0.05),
+    king_semi_open_file: tapered(-0.10, 0.0),
+    king_attack_units: [3.0, 2.0, 5.0, 0.0, 2.0, 0.0],
+    king_danger: tapered(-0.003, -0.001),
+    king_attack_unit_cap: 30.0,
+    mobility: [
+      tapered(0.03, 0.05),
+      tapered(0.05, 0.05),
+      tapered(0.02, 0.04),
+      tapered(0.0, 0.0),
+      tapered(0.06, 0.05),
+      tapered(0.0, 0.0)
+    ],
+    rook_open_file: tapered(0.25, 0.10),
+    rook_semi_open_file: tapered(0.10, 0.05),
+    rook_on_seventh: tapered(0.10, 0.25),
+    bishop_pair: tapered(0.30, 0.50),
+    knight_outpost: tapered(0.25, 0.15),
+    trapped_piece: [
+      tapered(-0.40, -0.20),
+      tapered(-0.50, -0.30),
+      tapered(-0.60, -0.40),
+      tapered(0.0, 0.0),
+      tapered(-0.40, -0.30),
+      tapered(0.0, 0.0)
+    ]
+  }
+}
+
+lazy_static! {
+  pub static ref DEFAULT_EVAL_PARAMS: EvalParams = default_eval_params();
+}
+
+const KIND_NAMES: [&str; PIECE_KINDS] = ["rook", "bishop", "queen", "pawn", "knight", "king"];
+
+// name of an entry in a rank-8-first table
+fn table_square_name(index: usize) -> String {
+  format!("{}{}", (b'a' + (index % 8) as u8) as char, 8 - index / 8)
+}
+
+fn visit_score(name: &str, score: &mut TaperedScore, visit: &mut dyn FnMut(&str, &mut f64)) {
+  visit(&format!("{}.mg", name), &mut score.mg);
+  visit(&format!("{}.eg", name), &mut score.eg);
+}
+
+fn visit_scores(name: &str, scores: &mut [TaperedScore], labels: &dyn Fn(usize) -> String, visit: &mut dyn FnMut(&str, &mut f64)) {
+  for (index, score) in scores.iter_mut().enumerate() {
+    visit_score(&format!("{}.{}", name, labels(index)), score, visit);
+  }
+}
+
+fn kind_label(kind: usize) -> String {
+  String::from(KIND_NAMES[kind])
+}
+
+fn index_label(index: usize) -> String {
+  index.to_string()
+}
+
+impl EvalParams {
+  // calls `visit` with the name and value of every number in the set, always in the same order
+  pub fn visit_values(&mut self, visit: &mut dyn FnMut(&str, &mut f64)) {
+    visit_scores("material", &mut self.material, &kind_label, visit);
+    for (name, table) in KIND_NAMES.iter().zip(self.piece_square.iter_mut()) {
+      visit_scores(&format!("piece_square.{}", name), table, &table_square_name, visit);
+    }
+    visit_score("doubled_pawn", &mut self.doubled_pawn, visit);
+    visit_score("isolated_pawn", &mut self.isolated_pawn, visit);
+    visit_score("backward_pawn", &mut self.backward_pawn, visit);
+    visit_scores("connected_pawn", &mut self.connected_pawn, &index_label, visit);
+    visit_scores("passed_pawn", &mut self.passed_pawn, &index_label, visit);
+    visit_score("passed_pawn_blocked", &mut self.passed_pawn_blocked, visit);
+    visit_score("passed_pawn_own_king_distance", &mut self.passed_pawn_own_king_distance, visit);
+    visit_score("passed_pawn_enemy_king_distance", &mut self.passed_pawn_enemy_king_distance, visit);
+    visit_scores("pawn_shield", &mut self.pawn_shield, &index_label, visit);
+    visit_scores("pawn_storm", &mut self.pawn_storm, &index_label, visit);
+    visit_score("king_open_file", &mut self.king_open_file, visit);
+    visit_score("king_semi_open_file", &mut self.king_semi_open_file, visit);
+    for (name, units) in KIND_NAMES.iter().zip(self.king_attack_units.iter_mut()) {
+      visit(&format!("king_attack_units.{}", name), units);
+    }
+    visit_score("king_danger", &mut self.king_danger, visit);
+    visit("king_attack_unit_cap", &mut self.king_attack_unit_cap);
+    visit_scores("mobility", &mut self.mobility, &kind_label, visit);
+    visit_score("rook_open_file", &mut self.rook_open_file, visit);
+    visit_score("rook_semi_open_file", &mut self.rook_semi_open_file, visit);
+    visit_score("rook_on_seventh", &mut self.rook_on_seventh, visit);
+    visit_score("bishop_pair", &mut self.bishop_pair, visit);
+    visit_score("knight_outpost", &mut self.knight_outpost, visit);
+    visit_scores("trapped_piece", &mut self.trapped_piece, &kind_label, visit);
+  }
+
+  pub fn named_values(&self) -> Vec<(String, f64)> {
+    let mut values: Vec<(String, f64)> = Vec::new();
+    self.clone().visit_values(&mut |name, value| values.push((String::from(name), *value)));
+    values
+  }
+
+  pub fn values(&self) -> Vec<f64> {
+    self.named_values().into_iter().map(|(_name, value)| value).collect()
+  }
+
+  // the same set with its numbers replaced, in `visit_values` order
+  pub fn with_values(&self, values: &[f64]) -> EvalParams {
+    let mut params: EvalParams = self.clone();
+    let mut index: usize = 0;
+    params.visit_values(&mut |_name, value| {
+      *value = values[index];
+      index += 1;
+    });
+    assert!(index == values.len(), "Wrong number of evaluation parameters. params.rs, with_values");
+    params
+  }
+}
+
+// one `name value` line per number
+pub fn format_eval_params(params: &EvalParams) -> String {
+  params.named_values().iter().map(|(name, value)| format!("{} {}\n", name, value)).collect()
+}
+
+// starts from the defaults, so a file only needs the values it changes
+pub fn parse_eval_params(text: &str) -> Result<EvalParams, String> {
+  let mut values: Vec<(String, f64)> = default_eval_params().named_values();
+  for (line_number, line) in text.lines().enumerate() {
+    let line: &str = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+      continue;
+    }
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    if fields.len() != 2 {
+      return Err(format!("line {}: expected `name value`", line_number + 1));
+    }
+    let value: f64 = fields[1].parse::<f64>().map_err(|_| format!("line {}: invalid value '{}'", line_number + 1, fields[1]))?;
+    match values.iter_mut().find(|(name, _value)| name == fields[0]) {
+      Some(entry) => entry.1 = value,
+      None => return Err(format!("line {}: unknown parameter '{}'", line_number + 1, fields[0]))
+    }
+  }
+  let numbers: Vec<f64> = values.into_iter().map(|(_name, value)| value).collect();
+  Ok(default_eval_params().with_values(&numbers))
+}
+
+pub fn load_eval_params(path: &str) -> Result<EvalParams, String> {
+  match std::fs::read_to_string(path) {
+    Ok(text) => parse_eval_params(&text).map_err(|error| format!("{}: {}", path, error)),
+    Err(error) => Err(format!("couldn't read {}: {}", path, error))
+  }
+}
+
+pub fn save_eval_params(params: &EvalParams, path: &str) -> Result<(), String> {
+  std::fs::write(path, format_eval_params(params)).map_err(|error| format!("couldn't write {}: {}", path, error))
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn names_are_unique() {
+    let names: Vec<String> = default_eval_params().named_values().into_iter().map(|(name, _value)| name).collect();
+    let mut sorted: Vec<String> = names.clone();
+    sorted.sort();
+    sorted.dedup();
+    assert!(sorted.len() == names.len());
+    assert!(names.contains(&String::from("piece_square.pawn.e4.mg")));
+  }
+
+  #[test]
+  fn values_round_trip() {
+    let params: EvalParams = default_eval_params();
+    let mut values: Vec<f64> = params.values();
+    values[0] += 1.0;
+    let changed: EvalParams = params.with_values(&values);
+    assert!(changed.material[ROOK].mg == params.material[ROOK].mg + 1.0);
+    assert!(changed.values() == values);
+  }
+
+  #[test]
+  fn file_format_round_trips() {
+    let mut params: EvalParams = default_eval_params();
+    params.bishop_pair = tapered(0.123, 0.456);
+    params.king_attack_units[QUEEN] = 7.0;
+    assert!(parse_eval_params(&format_eval_params(&params)) == Ok(params));
+  }
+
+  #[test]
+  fn partial_files_keep_defaults() {
+    let params: EvalParams = parse_eval_params("# tuned\nbishop_pair.mg 0.4\n").unwrap();
+    assert!(params.bishop_pair.mg == 0.4);
+    assert!(params.bishop_pair.eg == DEFAULT_EVAL_PARAMS.bishop_pair.eg);
+    assert!(parse_eval_params("no_such_term.mg 1").is_err());
+    assert!(parse_eval_params("bishop_pair.mg x").is_err());
+  }
+}
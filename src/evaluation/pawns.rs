@@ -0,0 +1,300 @@
+use super::params::{EvalParams, DEFAULT_EVAL_PARAMS};
+use super::score::TaperedScore;
+use super::squares_of;
+use crate::attacks::{white_pawn_attacks, black_pawn_attacks};
+use crate::board::get_all_occupation;
+use crate::constants::*;
+use crate::mask_for_square::{for_file, for_rank};
+use crate::zobrist::pawn_hash;
+use std::sync::Mutex;
+
+pub const PAWN_HASH_ENTRIES: usize = 1 << 14;
+
+// everything about the pawns that only depends on where the pawns are
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct PawnEntry {
+  pub key: u64,
+  pub structure: TaperedScore,
+  pub white_passed: u64,
+  pub black_passed: u64
+}
+
+pub struct PawnHashTable {
+  entries: Vec<Option<PawnEntry>>,
+  pub hits: u64,
+  pub misses: u64
+}
+
+pub fn make_pawn_hash_table(size: usize) -> PawnHashTable {
+  assert!(size.is_power_of_two(), "Pawn hash size must be a power of two. pawns.rs, make_pawn_hash_table");
+  PawnHashTable {
+    entries: vec![None; size],
+    hits: 0,
+    misses: 0
+  }
+}
+
+impl PawnHashTable {
+  pub fn get(&mut self, key: u64) -> Option<PawnEntry> {
+    let index: usize = key as usize & (self.entries.len() - 1);
+    match self.entries[index] {
+      Some(entry) if entry.key == key => {
+        self.hits += 1;
+        Some(entry)
+      },
+      _ => {
+        self.misses += 1;
+        None
+      }
+    }
+  }
+
+  pub fn insert(&mut self, entry: PawnEntry) {
+    let index: usize = entry.key as usize & (self.entries.len() - 1);
+    self.entries[index] = Some(entry);
+  }
+
+  pub fn clear(&mut self) {
+    self.entries.iter_mut().for_each(|entry| *entry = None);
+    self.hits = 0;
+    self.misses = 0;
+  }
+}
+
+lazy_static! {
+  // only holds entries computed with DEFAULT_EVAL_PARAMS
+  pub static ref PAWN_HASH: Mutex<PawnHashTable> = Mutex::new(make_pawn_hash_table(PAWN_HASH_ENTRIES));
+}
+
+pub fn adjacent_files(file: usize) -> u64 {
+  let left: u64 = if file > 0 { for_file(file as u8 - 1) } else { 0 };
+  let right: u64 = if file < 7 { for_file(file as u8 + 1) } else { 0 };
+  left | right
+}
+
+// ranks strictly in front of `rank` from the point of view of the side moving up (white) or down
+pub fn ranks_ahead(rank: usize, white: bool) -> u64 {
+  if white {
+    if rank >= 7 { 0 } else { WHOLE_BOARD << ((rank + 1) * 8) }
+  } else {
+    (1u64 << (rank * 8)) - 1
+  }
+}
+
+pub fn relative_rank(square: usize, white: bool) -> usize {
+  if white { square / 8 } else { 7 - square / 8 }
+}
+
+fn stop_square(square: usize, white: bool) -> usize {
+  if white { square + 8 } else { square - 8 }
+}
+
+// 0.0 for pawns on their second rank up to 1.0 on their seventh
+fn advancement(square: usize, white: bool) -> f64 {
+  (relative_rank(square, white).max(1) - 1) as f64 / 5.0
+}
+
+fn chebyshev_distance(first: usize, second: usize) -> f64 {
+  let files: i32 = (first % 8) as i32 - (second % 8) as i32;
+  let ranks: i32 = (first / 8) as i32 - (second / 8) as i32;
+  files.abs().max(ranks.abs()) as f64
+}
+
+// structure score and passed pawns of one side
+fn side_structure(own: u64, enemy: u64, white: bool, params: &EvalParams) -> (TaperedScore, u64) {
+  let mut score: TaperedScore = TaperedScore::default();
+  let mut passed: u64 = 0;
+  let own_attacks: u64 = if white { white_pawn_attacks(own) } else { black_pawn_attacks(own) };
+  let enemy_attacks: u64 = if white { black_pawn_attacks(enemy) } else { white_pawn_attacks(enemy) };
+
+  for file in 0..8 {
+    let on_file: u8 = (own & for_file(file as u8)).count_ones() as u8;
+    if on_file > 1 {
+      score += params.doubled_pawn * (on_file - 1) as f64;
+    }
+  }
+
+  for square in squares_of(own) {
+    let file: usize = square % 8;
+    let rank: usize = square / 8;
+    let bit: u64 = 1 << square;
+    let neighbours: u64 = own & adjacent_files(file);
+    let ahead: u64 = ranks_ahead(rank, white);
+
+    if neighbours == 0 {
+      score += params.isolated_pawn;
+    } else if neighbours & !ahead & !for_rank(rank as u8) == 0
+      && rank != 0 && rank != 7 && enemy_attacks & (1 << stop_square(square, white)) != 0 {
+      // every neighbour has already advanced past it and it can't step up safely
+      score += params.backward_pawn;
+    }
+
+    let side_by_side: bool = neighbours & for_rank(rank as u8) != 0;
+    if own_attacks & bit != 0 || side_by_side {
+      score += params.connected_pawn[relative_rank(square, white)];
+    }
+
+    let front_span: u64 = (for_file(file as u8) | adjacent_files(file)) & ahead;
+    if enemy & front_span == 0 {
+      passed |= bit;
+      score += params.passed_pawn[relative_rank(square, white)];
+    }
+  }
+  (score, passed)
+}
+
+pub fn pawn_entry(state: [u64; 13], params: &EvalParams) -> PawnEntry {
+  let white_pawns: u64 = state[WPAWN as usize];
+  let black_pawns: u64 = state[BPAWN as usize];
+  let (white_score, white_passed) = side_structure(white_pawns, black_pawns, true, params);
+  let (black_score, black_passed) = side_structure(black_pawns, white_pawns, false, params);
+  PawnEntry {
+    key: pawn_hash(state),
+    structure: white_score - black_score,
+    white_passed,
+    black_passed
+  }
+}
+
+// terms for passed pawns that also depend on the pieces: blockers and king distances
+fn passed_pawn_dynamics(state: [u64; 13], passed: u64, white: bool, params: &EvalParams) -> TaperedScore {
+  let mut score: TaperedScore = TaperedScore::default();
+  let occupied: u64 = get_all_occupation(state);
+  let own_king: u64 = if white { state[WKING as usize] } else { state[BKING as usize] };
+  let enemy_king: u64 = if white { state[BKING as usize] } else { state[WKING as usize] };
+
+  for square in squares_of(passed) {
+    if relative_rank(square, white) == 7 {
+      continue;
+    }
+    let stop: usize = stop_square(square, white);
+    let weight: f64 = advancement(square, white);
+    if occupied & (1 << stop) != 0 {
+      score += params.passed_pawn_blocked * weight;
+    }
+    if own_king != 0 {
+      score += params.passed_pawn_own_king_distance * (chebyshev_distance(own_king.trailing_zeros() as usize, stop) * weight);
+    }
+    if enemy_king != 0 {
+      score += params.passed_pawn_enemy_king_distance * (chebyshev_distance(enemy_king.trailing_zeros() as usize, stop) * weight);
+    }
+  }
+  score
+}
+
+fn score_from_entry(state: [u64; 13], entry: &PawnEntry, params: &EvalParams) -> TaperedScore {
+  entry.structure
+    + passed_pawn_dynamics(state, entry.white_passed, true, params)
+    - passed_pawn_dynamics(state, entry.black_passed, false, params)
+}
+
+// one side's share of `pawn_structure`
+pub fn side_pawn_structure(state: [u64; 13], white: bool, params: &EvalParams) -> TaperedScore {
+  let (own, enemy) = if white {
+    (state[WPAWN as usize], state[BPAWN as usize])
+  } else {
+    (state[BPAWN as usize], state[WPAWN as usize])
+  };
+  let (structure, passed) = side_structure(own, enemy, white, params);
+  structure + passed_pawn_dynamics(state, passed, white, params)
+}
+
+// pawn structure and passed pawns, white minus black, computed from scratch
+pub fn pawn_structure(state: [u64; 13], params: &EvalParams) -> TaperedScore {
+  score_from_entry(state, &pawn_entry(state, params), params)
+}
+
+// same as `pawn_structure`, reusing the pawn-only part from `table` when it holds this placement.
+// every entry in a table has to come from the same parameters.
+pub fn pawn_structure_with_table(state: [u64; 13], params: &EvalParams, table: &mut PawnHashTable) -> TaperedScore {
+  let entry: PawnEntry = match table.get(pawn_hash(state)) {
+    Some(entry) => entry,
+    None => {
+      let entry: PawnEntry = pawn_entry(state, params);
+      table.insert(entry);
+      entry
+    }
+  };
+  score_from_entry(state, &entry, params)
+}
+
+// `pawn_structure` with the default parameters, going through the shared pawn hash table
+pub fn cached_pawn_structure(state: [u64; 13]) -> TaperedScore {
+  pawn_structure_with_table(state, &DEFAULT_EVAL_PARAMS, &mut PAWN_HASH.lock().unwrap())
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::fen::state_from_fen;
+
+  fn structure(fen: &str) -> TaperedScore {
+    pawn_structure(state_from_fen(fen).unwrap().0, &DEFAULT_EVAL_PARAMS)
+  }
+
+  #[test]
+  fn symmetric_structure_scores_zero() {
+    let score: TaperedScore = structure("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w - -");
+    assert!(score.mg.abs() < 1e-9 && score.eg.abs() < 1e-9);
+  }
+
+  #[test]
+  fn finds_doubled_and_isolated_pawns() {
+    let entry: PawnEntry = pawn_entry(state_from_fen("4k3/8/8/8/8/4P3/4P3/4K3 w - -").unwrap().0, &DEFAULT_EVAL_PARAMS);
+    let params: &EvalParams = &DEFAULT_EVAL_PARAMS;
+    let expected: TaperedScore = params.doubled_pawn + params.isolated_pawn * 2.0
+      + params.passed_pawn[1] + params.passed_pawn[2];
+    assert!((entry.structure.mg - expected.mg).abs() < 1e-9);
+    assert!((entry.structure.eg - expected.eg).abs() < 1e-9);
+    assert!(entry.white_passed == (1 << 12) | (1 << 20));
+  }
+
+  #[test]
+  fn finds_backward_pawn() {
+    // d3 trails c4 and e4 and its stop square d4 is hit by the c5 pawn
+    let backward: TaperedScore = structure("4k3/8/8/2p5/2P1P3/3P4/8/4K3 w - -");
+    let healthy: TaperedScore = structure("4k3/8/8/2p5/2PPP3/8/8/4K3 w - -");
+    assert!(backward.mg < healthy.mg);
+  }
+
+  #[test]
+  fn connected_pawns_beat_split_pawns() {
+    let connected: TaperedScore = structure("4k3/pp6/8/8/8/8/3PP3/4K3 w - -");
+    let split: TaperedScore = structure("4k3/pp6/8/8/8/8/2P2P2/4K3 w - -");
+    assert!(connected.mg > split.mg);
+  }
+
+  #[test]
+  fn passed_pawns_grow_with_rank_and_fear_blockers() {
+    let far: TaperedScore = structure("4k3/8/8/8/8/8/1P6/4K3 w - -");
+    let near: TaperedScore = structure("4k3/8/1P6/8/8/8/8/4K3 w - -");
+    let blocked: TaperedScore = structure("4k3/1n6/1P6/8/8/8/8/4K3 w - -");
+    assert!(near.eg > far.eg);
+    assert!(blocked.eg < near.eg);
+  }
+
+  #[test]
+  fn enemy_pawn_in_front_span_stops_a_passer() {
+    let entry: PawnEntry = pawn_entry(state_from_fen("4k3/2p5/8/8/8/8/1P6/4K3 w - -").unwrap().0, &DEFAULT_EVAL_PARAMS);
+    assert!(entry.white_passed == 0);
+    assert!(entry.black_passed == 0);
+  }
+
+  #[test]
+  fn cached_score_matches_fresh_score() {
+    let state: [u64; 13] = state_from_fen("4k3/1p3p2/8/2P5/8/8/5PP1/4K3 w - -").unwrap().0;
+    let fresh: TaperedScore = pawn_structure(state, &DEFAULT_EVAL_PARAMS);
+    assert!(cached_pawn_structure(state) == fresh);
+    assert!(cached_pawn_structure(state) == fresh);
+  }
+
+  #[test]
+  fn table_returns_only_matching_keys() {
+    let mut table: PawnHashTable = make_pawn_hash_table(4);
+    let entry: PawnEntry = pawn_entry(state_from_fen("4k3/8/8/8/8/8/1P6/4K3 w - -").unwrap().0, &DEFAULT_EVAL_PARAMS);
+    table.insert(entry);
+    assert!(table.get(entry.key) == Some(entry));
+    assert!(table.get(entry.key ^ 4).is_none());
+    assert!(table.hits == 1 && table.misses == 1);
+  }
+}
@@ -0,0 +1,56 @@
+use crate::utility::number_of_bits;
+
+// rook, bishop, queen, pawn, knight, king
+const PHASE_WEIGHTS: [u8; 6] = [2, 1, 4, 0, 1, 0];
+// phase weight of the starting material; promotions can't push past it
+pub const MAX_PHASE: u8 = 24;
+
+// 1.0 while all the pieces are on the board, falling to 0.0 as they come off
+pub fn game_phase(state: [u64; 13]) -> f64 {
+  let phase: u8 = (0..12).map(|slice_index| PHASE_WEIGHTS[slice_index % 6] * number_of_bits(state[slice_index])).sum();
+  phase.min(MAX_PHASE) as f64 / MAX_PHASE as f64
+}
+
+// the same measure for one side's pieces, 1.0 while that side keeps its starting pieces
+pub fn side_phase(state: [u64; 13], white: bool) -> f64 {
+  let first_slice: usize = if white { 0 } else { 6 };
+  let phase: u8 = (first_slice..first_slice + 6).map(|slice_index| PHASE_WEIGHTS[slice_index % 6] * number_of_bits(state[slice_index])).sum();
+  phase.min(MAX_PHASE / 2) as f64 / (MAX_PHASE / 2) as f64
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::constants::*;
+  use crate::game::setup_board;
+
+  #[test]
+  fn starting_position_is_pure_middlegame() {
+    assert!(game_phase(setup_board()) == 1.0);
+  }
+
+  #[test]
+  fn kings_and_pawns_are_pure_endgame() {
+    let mut state: [u64; 13] = setup_board();
+    for slice_index in [WROOK, WBISHOP, WQUEEN, WKNIGHT, BROOK, BBISHOP, BQUEEN, BKNIGHT] {
+      state[slice_index as usize] = 0;
+    }
+    assert!(game_phase(state) == 0.0);
+  }
+
+  #[test]
+  fn side_phase_only_counts_one_side() {
+    let mut state: [u64; 13] = setup_board();
+    state[BQUEEN as usize] = 0;
+    assert!(side_phase(state, true) == 1.0);
+    assert!(side_phase(state, false) == 8.0 / 12.0);
+  }
+
+  #[test]
+  fn queens_off_is_in_between() {
+    let mut state: [u64; 13] = setup_board();
+    state[WQUEEN as usize] = 0;
+    state[BQUEEN as usize] = 0;
+    assert!(game_phase(state) == 16.0 / 24.0);
+  }
+}
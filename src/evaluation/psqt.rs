@@ -0,0 +1,66 @@
+use super::params::{EvalParams, PIECE_KINDS};
+use super::score::TaperedScore;
+use super::squares_of;
+
+fn side_slice(kind: usize, white: bool) -> usize {
+  if white { kind } else { kind + PIECE_KINDS }
+}
+
+pub fn side_material(state: [u64; 13], white: bool, params: &EvalParams) -> TaperedScore {
+  let mut score: TaperedScore = TaperedScore::default();
+  for kind in 0..PIECE_KINDS {
+    score += params.material[kind] * state[side_slice(kind, white)].count_ones() as f64;
+  }
+  score
+}
+
+pub fn side_placement(state: [u64; 13], white: bool, params: &EvalParams) -> TaperedScore {
+  // the tables are written for white, so white flips its squares onto them
+  let flip: usize = if white { 56 } else { 0 };
+  let mut score: TaperedScore = TaperedScore::default();
+  for kind in 0..PIECE_KINDS {
+    for square in squares_of(state[side_slice(kind, white)]) {
+      score += params.piece_square[kind][square ^ flip];
+    }
+  }
+  score
+}
+
+// material plus piece-square bonuses, white minus black
+pub fn material_and_placement(state: [u64; 13], params: &EvalParams) -> TaperedScore {
+  side_material(state, true, params) + side_placement(state, true, params)
+    - side_material(state, false, params) - side_placement(state, false, params)
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use super::super::params::DEFAULT_EVAL_PARAMS;
+  use crate::constants::*;
+  use crate::game::setup_board;
+
+  #[test]
+  fn starting_position_is_balanced() {
+    let score: TaperedScore = material_and_placement(setup_board(), &DEFAULT_EVAL_PARAMS);
+    assert!(score.mg.abs() < 1e-9);
+    assert!(score.eg.abs() < 1e-9);
+  }
+
+  #[test]
+  fn mirrored_pieces_score_the_same_for_both_sides() {
+    let mut white: [u64; 13] = [0; 13];
+    white[WKNIGHT as usize] = 1 << 18;
+    let mut black: [u64; 13] = [0; 13];
+    black[BKNIGHT as usize] = 1 << (18 ^ 56);
+    assert!(material_and_placement(white, &DEFAULT_EVAL_PARAMS) == -material_and_placement(black, &DEFAULT_EVAL_PARAMS));
+  }
+
+  #[test]
+  fn advanced_pawns_gain_value_in_the_endgame() {
+    let mut second_rank: [u64; 13] = [0; 13];
+    second_rank[WPAWN as usize] = 1 << 12;
+    let mut seventh_rank: [u64; 13] = [0; 13];
+    seventh_rank[WPAWN as usize] = 1 << 52;
+    assert!(material_and_placement(seventh_rank, &DEFAULT_EVAL_PARAMS).eg > material_and_placement(second_rank, &DEFAULT_EVAL_PARAMS).eg + 0.5);
+  }
+}
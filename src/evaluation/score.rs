@@ -0,0 +1,80 @@
+use std::ops::{Add, AddAssign, Mul, Neg, Sub, SubAssign};
+
+// a term's value in the middlegame and in the endgame, in pawns
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub struct TaperedScore {
+  pub mg: f64,
+  pub eg: f64
+}
+
+pub const fn tapered(mg: f64, eg: f64) -> TaperedScore {
+  TaperedScore { mg, eg }
+}
+
+impl TaperedScore {
+  // blends the two phases; `phase` is 1.0 with all pieces on the board and 0.0 with none
+  pub fn taper(&self, phase: f64) -> f64 {
+    self.mg * phase + self.eg * (1.0 - phase)
+  }
+}
+
+impl Add for TaperedScore {
+  type Output = TaperedScore;
+  fn add(self, other: TaperedScore) -> TaperedScore {
+    tapered(self.mg + other.mg, self.eg + other.eg)
+  }
+}
+
+impl Sub for TaperedScore {
+  type Output = TaperedScore;
+  fn sub(self, other: TaperedScore) -> TaperedScore {
+    tapered(self.mg - other.mg, self.eg - other.eg)
+  }
+}
+
+impl Neg for TaperedScore {
+  type Output = TaperedScore;
+  fn neg(self) -> TaperedScore {
+    tapered(-self.mg, -self.eg)
+  }
+}
+
+impl Mul<f64> for TaperedScore {
+  type Output = TaperedScore;
+  fn mul(self, factor: f64) -> TaperedScore {
+    tapered(self.mg * factor, self.eg * factor)
+  }
+}
+
+impl AddAssign for TaperedScore {
+  fn add_assign(&mut self, other: TaperedScore) {
+    *self = *self + other;
+  }
+}
+
+impl SubAssign for TaperedScore {
+  fn sub_assign(&mut self, other: TaperedScore) {
+    *self = *self - other;
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn tapers_between_phases() {
+    let score: TaperedScore = tapered(1.0, 3.0);
+    assert!(score.taper(1.0) == 1.0);
+    assert!(score.taper(0.0) == 3.0);
+    assert!(score.taper(0.5) == 2.0);
+  }
+
+  #[test]
+  fn adds_and_scales_both_phases() {
+    let mut score: TaperedScore = tapered(1.0, 2.0) + tapered(0.5, 0.5) * 2.0;
+    assert!(score == tapered(2.0, 3.0));
+    score -= tapered(2.0, 3.0);
+    assert!(score == TaperedScore::default());
+  }
+}
@@ -0,0 +1,121 @@
+use super::params::{EvalParams, DEFAULT_EVAL_PARAMS};
+use super::score::TaperedScore;
+use super::{king_safety, mobility, pawns, phase, psqt};
+
+pub struct TraceTerm {
+  pub name: &'static str,
+  pub white: TaperedScore,
+  pub black: TaperedScore
+}
+
+impl TraceTerm {
+  pub fn total(&self) -> TaperedScore {
+    self.white - self.black
+  }
+}
+
+pub struct EvalTrace {
+  pub terms: Vec<TraceTerm>,
+  pub phase: f64
+}
+
+impl EvalTrace {
+  pub fn total(&self) -> TaperedScore {
+    self.terms.iter().fold(TaperedScore::default(), |sum, term| sum + term.total())
+  }
+
+  // the number the evaluation returns, in pawns from white's side
+  pub fn score(&self) -> f64 {
+    self.total().taper(self.phase)
+  }
+}
+
+type SideTerm = fn([u64; 13], bool, &EvalParams) -> TaperedScore;
+
+const TERMS: [(&str, SideTerm); 5] = [
+  ("Material", psqt::side_material),
+  ("Placement", psqt::side_placement),
+  ("Pawns", pawns::side_pawn_structure),
+  ("King safety", king_safety::side_king_safety),
+  ("Mobility", mobility::side_activity)
+];
+
+pub fn eval_trace_with_params(state: [u64; 13], params: &EvalParams) -> EvalTrace {
+  EvalTrace {
+    terms: TERMS.iter().map(|(name, term)| TraceTerm {
+      name,
+      white: term(state, true, params),
+      black: term(state, false, params)
+    }).collect(),
+    phase: phase::game_phase(state)
+  }
+}
+
+// every term of `hand_crafted_eval` for each side
+pub fn eval_trace(state: [u64; 13]) -> EvalTrace {
+  eval_trace_with_params(state, &DEFAULT_EVAL_PARAMS)
+}
+
+fn format_row(name: &str, white: TaperedScore, black: TaperedScore, total: TaperedScore) -> String {
+  format!(
+    "{:>12} | {:>6.2} {:>6.2} | {:>6.2} {:>6.2} | {:>6.2} {:>6.2}\n",
+    name, white.mg, white.eg, black.mg, black.eg, total.mg, total.eg
+  )
+}
+
+pub fn format_eval_trace(trace: &EvalTrace) -> String {
+  let mut table: String = String::new();
+  table.push_str("        Term |     White     |     Black     |     Total\n");
+  table.push_str("             |     MG     EG |     MG     EG |     MG     EG\n");
+  table.push_str("-------------+---------------+---------------+--------------\n");
+  let mut white: TaperedScore = TaperedScore::default();
+  let mut black: TaperedScore = TaperedScore::default();
+  for term in trace.terms.iter() {
+    table.push_str(&format_row(term.name, term.white, term.black, term.total()));
+    white += term.white;
+    black += term.black;
+  }
+  table.push_str("-------------+---------------+---------------+--------------\n");
+  table.push_str(&format_row("Total", white, black, trace.total()));
+  table.push_str(&format!("\nPhase: {:.2} (1.00 middlegame, 0.00 endgame)\n", trace.phase));
+  table.push_str(&format!("Final evaluation: {:.2} (white side)\n", trace.score()));
+  table
+}
+
+pub fn print_eval_trace(trace: &EvalTrace) {
+  print!("{}", format_eval_trace(trace));
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use super::super::{evaluate_with_params, hand_crafted_eval};
+  use crate::fen::state_from_fen;
+
+  #[test]
+  fn trace_adds_up_to_the_evaluation() {
+    let state: [u64; 13] = state_from_fen("r1bq1rk1/pp3ppp/2n1p3/3n4/1b1P4/2NB1N2/PP3PPP/R1BQ1RK1 w - -").unwrap().0;
+    let trace: EvalTrace = eval_trace(state);
+    assert!((trace.score() - evaluate_with_params(state, &DEFAULT_EVAL_PARAMS)).abs() < 1e-9);
+    assert!((trace.score() - hand_crafted_eval(state)).abs() < 1e-9);
+  }
+
+  #[test]
+  fn mirrored_sides_trade_places() {
+    let trace: EvalTrace = eval_trace(state_from_fen("4k3/8/8/8/8/8/PPP5/R3K3 w - -").unwrap().0);
+    let mirrored: EvalTrace = eval_trace(state_from_fen("r3k3/ppp5/8/8/8/8/8/4K3 w - -").unwrap().0);
+    for (term, mirrored_term) in trace.terms.iter().zip(mirrored.terms.iter()) {
+      assert!((term.white.mg - mirrored_term.black.mg).abs() < 1e-9, "{}", term.name);
+      assert!((term.white.eg - mirrored_term.black.eg).abs() < 1e-9, "{}", term.name);
+    }
+  }
+
+  #[test]
+  fn table_lists_every_term() {
+    let table: String = format_eval_trace(&eval_trace(crate::game::setup_board()));
+    for (name, _term) in TERMS.iter() {
+      assert!(table.contains(name));
+    }
+    assert!(table.contains("Final evaluation: 0.00"));
+  }
+}
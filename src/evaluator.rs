@@ -0,0 +1,127 @@
+use crate::bot::{basic_eval, center_squares_worth};
+use crate::evaluation::evaluate_with_pawn_table;
+use crate::evaluation::params::EvalParams;
+use crate::evaluation::pawns::{make_pawn_hash_table, PawnHashTable, PAWN_HASH_ENTRIES};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+// in pawns, from white's side
+pub type Score = f64;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Position {
+  pub state: [u64; 13],
+  // odd when white is to move
+  pub turn_number: u8
+}
+
+pub fn make_position(state: [u64; 13], turn_number: u8) -> Position {
+  Position {
+    state,
+    turn_number
+  }
+}
+
+// anything the search can ask for a score. evaluators may keep state between calls;
+// the search calls `on_make` when it steps from a position into a child and `on_unmake`
+// when it steps back, so evaluators can update incrementally instead of from scratch
+pub trait Evaluator: Send {
+  fn evaluate(&mut self, position: &Position) -> Score;
+
+  fn on_make(&mut self, _parent: &Position, _child: &Position) {}
+
+  fn on_unmake(&mut self, _child: &Position, _parent: &Position) {}
+
+  // how promising each move from `parent` to one of `children` looks, higher first, for
+  // evaluators that can rank moves. the search orders moves by these when it gets them
+  fn move_priors(&mut self, _parent: &Position, _children: &[[u64; 13]]) -> Option<Vec<f64>> {
+    None
+  }
+}
+
+// material only, see `basic_eval`
+pub struct BasicEvaluator;
+
+impl Evaluator for BasicEvaluator {
+  fn evaluate(&mut self, position: &Position) -> Score {
+    basic_eval(position.state)
+  }
+}
+
+// material plus centre control, see `center_squares_worth`
+pub struct CenterSquaresEvaluator;
+
+impl Evaluator for CenterSquaresEvaluator {
+  fn evaluate(&mut self, position: &Position) -> Score {
+    center_squares_worth(position.state)
+  }
+}
+
+pub struct RandomEvaluator {
+  rng: StdRng
+}
+
+pub fn make_random_evaluator() -> RandomEvaluator {
+  RandomEvaluator {
+    rng: StdRng::from_entropy()
+  }
+}
+
+impl Evaluator for RandomEvaluator {
+  fn evaluate(&mut self, _position: &Position) -> Score {
+    self.rng.gen_range(-50f64..50f64)
+  }
+}
+
+// the tapered hand-crafted evaluation with its own parameters and pawn hash table
+pub struct HandCraftedEvaluator {
+  params: EvalParams,
+  pawn_hash: PawnHashTable
+}
+
+pub fn make_hand_crafted_evaluator(params: EvalParams) -> HandCraftedEvaluator {
+  HandCraftedEvaluator {
+    params,
+    pawn_hash: make_pawn_hash_table(PAWN_HASH_ENTRIES)
+  }
+}
+
+impl HandCraftedEvaluator {
+  pub fn params(&self) -> &EvalParams {
+    &self.params
+  }
+}
+
+impl Evaluator for HandCraftedEvaluator {
+  fn evaluate(&mut self, position: &Position) -> Score {
+    evaluate_with_pawn_table(position.state, &self.params, &mut self.pawn_hash)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::evaluation::hand_crafted_eval;
+  use crate::evaluation::params::default_eval_params;
+  use crate::fen::state_from_fen;
+
+  #[test]
+  fn wrappers_match_their_functions() {
+    let (state, turn_number) = state_from_fen("r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w - -").unwrap();
+    let position: Position = make_position(state, turn_number);
+    assert!(BasicEvaluator.evaluate(&position) == basic_eval(state));
+    assert!(CenterSquaresEvaluator.evaluate(&position) == center_squares_worth(state));
+    let mut hand_crafted: HandCraftedEvaluator = make_hand_crafted_evaluator(default_eval_params());
+    assert!((hand_crafted.evaluate(&position) - hand_crafted_eval(state)).abs() < 1e-9);
+    // the second call comes out of the evaluator's own pawn table
+    assert!((hand_crafted.evaluate(&position) - hand_crafted_eval(state)).abs() < 1e-9);
+    assert!(hand_crafted.pawn_hash.hits == 1);
+  }
+
+  #[test]
+  fn random_scores_stay_in_range() {
+    let mut evaluator: RandomEvaluator = make_random_evaluator();
+    let position: Position = make_position([0; 13], 1);
+    assert!((0..100).map(|_| evaluator.evaluate(&position)).all(|score| (-50.0..50.0).contains(&score)));
+  }
+}
@@ -0,0 +1,141 @@
+use crate::constants::*;
+use std::collections::HashMap;
+
+lazy_static! {
+  static ref LETTER_TO_INDEX: HashMap<char, u8> = HashMap::from([
+    ('R', WROOK),
+    ('B', WBISHOP),
+    ('Q', WQUEEN),
+    ('P', WPAWN),
+    ('N', WKNIGHT),
+    ('K', WKING),
+    ('r', BROOK),
+    ('b', BBISHOP),
+    ('q', BQUEEN),
+    ('p', BPAWN),
+    ('n', BKNIGHT),
+    ('k', BKING)
+  ]);
+}
+
+// white moves on odd turn numbers, black on even ones
+pub fn turn_number_for_side(side: &str) -> Result<u8, String> {
+  match side {
+    "w" => Ok(1),
+    "b" => Ok(2),
+    _ => Err(format!("unknown side to move '{}'", side))
+  }
+}
+
+fn parse_placement(placement: &str) -> Result<[u64; 13], String> {
+  let mut state: [u64; 13] = [0; 13];
+  let ranks: Vec<&str> = placement.split('/').collect();
+  if ranks.len() != 8 {
+    return Err(format!("expected 8 ranks in '{}'", placement));
+  }
+  for (rank_text, rank_index) in ranks.iter().zip((0..8).rev()) {
+    let mut file: u8 = 0;
+    for ch in rank_text.chars() {
+      if let Some(skip) = ch.to_digit(10) {
+        if file as u32 + skip > 8 {
+          return Err(format!("rank '{}' is longer than 8 squares", rank_text));
+        }
+        file += skip as u8;
+      } else if let Some(slice_index) = LETTER_TO_INDEX.get(&ch) {
+        if file > 7 {
+          return Err(format!("rank '{}' is longer than 8 squares", rank_text));
+        }
+        state[*slice_index as usize] |= 1 << (rank_index * 8 + file);
+        file += 1;
+      } else {
+        return Err(format!("unknown piece '{}'", ch));
+      }
+    }
+    if file != 8 {
+      return Err(format!("rank '{}' does not cover 8 squares", rank_text));
+    }
+  }
+  Ok(state)
+}
+
+// parses the placement and side-to-move fields; castling, en passant and clocks are ignored
+// because the move generator has no use for them
+pub fn state_from_fen(fen: &str) -> Result<([u64; 13], u8), String> {
+  let fields: Vec<&str> = fen.split_whitespace().collect();
+  if fields.len() < 2 {
+    return Err(format!("'{}' needs at least a placement and a side to move", fen));
+  }
+  Ok((parse_placement(fields[0])?, turn_number_for_side(fields[1])?))
+}
+
+pub fn fen_from_state(state: [u64; 13], turn_number: u8) -> String {
+  let mut index_to_letter: HashMap<u8, char> = HashMap::new();
+  for (letter, index) in LETTER_TO_INDEX.iter() {
+    index_to_letter.insert(*index, *letter);
+  }
+
+  let mut ranks: Vec<String> = Vec::new();
+  for rank in (0..8).rev() {
+    let mut rank_text: String = String::new();
+    let mut empty: u8 = 0;
+    for file in 0..8 {
+      let square: u64 = 1 << (rank * 8 + file);
+      match (0..12).find(|slice_index| state[*slice_index as usize] & square != 0) {
+        Some(slice_index) => {
+          if empty > 0 {
+            rank_text.push_str(&empty.to_string());
+            empty = 0;
+          }
+          rank_text.push(index_to_letter[&slice_index]);
+        },
+        None => empty += 1
+      }
+    }
+    if empty > 0 {
+      rank_text.push_str(&empty.to_string());
+    }
+    ranks.push(rank_text);
+  }
+  let side: &str = if turn_number % 2 == 1 { "w" } else { "b" };
+  format!("{} {} - - 0 1", ranks.join("/"), side)
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::game::setup_board;
+
+  const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+  #[test]
+  fn parses_starting_position() {
+    let (state, turn_number) = state_from_fen(START_FEN).unwrap();
+    assert!(state == setup_board());
+    assert!(turn_number == 1);
+  }
+
+  #[test]
+  fn parses_black_to_move() {
+    let (state, turn_number) = state_from_fen("7k/5ppp/8/8/8/8/8/R1K5 b - -").unwrap();
+    assert!(state[BKING as usize] == 1 << 63);
+    assert!(state[WROOK as usize] == 1);
+    assert!(state[WKING as usize] == 1 << 2);
+    assert!(turn_number % 2 == 0);
+  }
+
+  #[test]
+  fn writes_fen_that_parses_back() {
+    let fen: String = fen_from_state(setup_board(), 1);
+    assert!(fen.starts_with("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w"));
+    assert!(state_from_fen(&fen).unwrap().0 == setup_board());
+  }
+
+  #[test]
+  fn rejects_malformed_placement() {
+    assert!(state_from_fen("8/8/8 w").is_err());
+    assert!(state_from_fen("9/8/8/8/8/8/8/8 w").is_err());
+    assert!(state_from_fen("8/8/8/8/8/8/8/7x w").is_err());
+    assert!(state_from_fen("8/8/8/8/8/8/8/8 x").is_err());
+    assert!(state_from_fen("99999999999999999999999999999/8/8/8/8/8/8/8 w").is_err());
+  }
+}
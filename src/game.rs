@@ -2,12 +2,14 @@ use crate::user::get_legal_input_state;
 use crate::r#move::states_for_turn;
 use crate::constants::*;
 use crate::utility::print_board_pieces;
-use crate::bot::{make_bot, Bot, basic_eval, center_squares_worth, random_eval};
-use crate::network::train::learn_bot_eval;
+use crate::bot::{make_bot, make_bot_with_config, plain_search_config, Bot};
+use crate::evaluator::{make_random_evaluator, Evaluator};
+use crate::mcts::{make_mcts, Mcts, MctsConfig};
+use crate::network::train::make_learn_bot_evaluator;
+use crate::network::network_storage::NetworkOptions;
 
 lazy_static! {
-  pub static ref RANDOM_BOT: Bot = make_bot(random_eval, 0);
-  pub static ref LEARN_BOT: Bot = make_bot(learn_bot_eval, 3);
+  pub static ref RANDOM_BOT: Bot = make_bot_with_config(Box::new(make_random_evaluator()), 0, plain_search_config());
 }
 
 pub fn two_console_game() {
@@ -18,41 +20,65 @@ pub fn two_console_game() {
   }
 }
 
-pub fn one_bot_game() {
-  let mut state: [u64; 13] = setup_board();
-  let mut turn_number: u8 = 1;
+// anything that can pick a move for the side to play. the game modes take engines, so any
+// of them can be played by minimax or by tree search
+pub trait Engine {
+  fn play_turn(&mut self, state: [u64; 13], turn_number: u8) -> [u64; 13];
+}
 
-  let bot: Bot = make_bot(basic_eval, 3);
-  loop {
-    play_player_turn(&mut state, &mut turn_number);
-    play_engine_turn(&bot, &mut state, &mut turn_number);
+impl Engine for Bot {
+  fn play_turn(&mut self, state: [u64; 13], turn_number: u8) -> [u64; 13] {
+    self.get_state(state, turn_number)
+  }
+}
+
+impl Engine for Mcts {
+  fn play_turn(&mut self, state: [u64; 13], turn_number: u8) -> [u64; 13] {
+    let new_state: [u64; 13] = self.search(state, turn_number);
+    println!("Visits: {}, value: {:.4}", self.root_visits(), self.root_value());
+    new_state
+  }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum EngineKind {
+  // alpha-beta to this depth
+  Minimax(u8),
+  Mcts(MctsConfig)
+}
+
+pub fn make_engine(kind: EngineKind, evaluator: Box<dyn Evaluator>) -> Box<dyn Engine> {
+  match kind {
+    EngineKind::Minimax(depth) => Box::new(make_bot(evaluator, depth)),
+    EngineKind::Mcts(config) => Box::new(make_mcts(evaluator, config))
   }
 }
 
-pub fn two_bot_game_learn_bot() {
+pub fn one_bot_game(engine: &mut dyn Engine) {
   let mut state: [u64; 13] = setup_board();
   let mut turn_number: u8 = 1;
 
-  let bot1: Bot = make_bot(learn_bot_eval, 0);
-  let bot2: Bot = make_bot(learn_bot_eval, 0);
-
   loop {
-    println!("Gotten here.");
-    play_engine_turn(&bot2, &mut state, &mut turn_number);
-    play_engine_turn(&bot1, &mut state, &mut turn_number);
+    play_player_turn(&mut state, &mut turn_number);
+    play_engine_turn(engine, &mut state, &mut turn_number);
   }
 }
 
-pub fn two_bot_game() {
+// both sides use the network at the options' path, each through its own kind of engine
+pub fn two_bot_game_learn_bot(options: &NetworkOptions, white: EngineKind, black: EngineKind) -> Result<(), String> {
+  let mut white_engine: Box<dyn Engine> = make_engine(white, Box::new(make_learn_bot_evaluator(options)?));
+  let mut black_engine: Box<dyn Engine> = make_engine(black, Box::new(make_learn_bot_evaluator(options)?));
+  two_bot_game(white_engine.as_mut(), black_engine.as_mut());
+  Ok(())
+}
+
+pub fn two_bot_game(white: &mut dyn Engine, black: &mut dyn Engine) {
   let mut state: [u64; 13] = setup_board();
   let mut turn_number: u8 = 1;
 
-  let bot1: Bot = make_bot(basic_eval, 3);
-  let bot2: Bot = make_bot(center_squares_worth, 3);
-
   loop {
-    play_engine_turn(&bot2, &mut state, &mut turn_number);
-    play_engine_turn(&bot1, &mut state, &mut turn_number);
+    play_engine_turn(white, &mut state, &mut turn_number);
+    play_engine_turn(black, &mut state, &mut turn_number);
   }
 }
 
@@ -94,8 +120,8 @@ fn play_player_turn(state: &mut [u64; 13], turn_number: &mut u8) {
   *turn_number += 1;
 }
 
-pub fn play_engine_turn(engine: &Bot, state: &mut [u64; 13], turn_number: &mut u8) {
-  *state = engine.get_state(*state, *turn_number);
+pub fn play_engine_turn(engine: &mut dyn Engine, state: &mut [u64; 13], turn_number: &mut u8) {
+  *state = engine.play_turn(*state, *turn_number);
   print_board_pieces(*state);
   println!("The engine has played.");
   println!("Move #{}", *turn_number);
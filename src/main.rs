@@ -1,6 +1,7 @@
 #![cfg_attr(debug_assertions, allow(dead_code, unused_imports, non_snake_case))]
 
 mod network;
+mod evaluation;
 
 mod mask_for_square;
 mod constants;
@@ -20,6 +21,19 @@ mod pawn_move;
 mod user;
 mod game;
 mod bot;
+mod evaluator;
+mod attacks;
+mod fen;
+mod notation;
+mod epd;
+mod pgn;
+mod suite;
+mod bench;
+mod commands;
+mod zobrist;
+mod tuner;
+mod mcts;
+mod uci;
 
 extern crate rand;
 
@@ -33,7 +47,10 @@ use crate::game::{two_console_game, one_bot_game, two_bot_game, two_bot_game_lea
 extern crate lazy_static;
 
 fn main() {
-  //two_bot_game();
-  // network::train::train_network_with_games(100);
-  two_bot_game_learn_bot();
+  //two_bot_game(&mut bot::make_bot(Box::new(evaluator::BasicEvaluator), 3), &mut bot::make_bot(Box::new(evaluator::CenterSquaresEvaluator), 3));
+  // network::train::train_network_with_games(100, network::train::default_training_config(), &network::train::default_run_config(), &network::network_storage::network_options(None, true));
+  if let Err(error) = commands::run(std::env::args().skip(1).collect()) {
+    eprintln!("{}", error);
+    std::process::exit(1);
+  }
 }
\ No newline at end of file
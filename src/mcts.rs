@@ -0,0 +1,424 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use crate::bot::king_captured;
+use crate::evaluator::{make_position, Evaluator};
+use crate::r#move::states_for_turn;
+
+// Monte Carlo tree search with PUCT selection: each playout walks down the tree to a leaf,
+// picking at every node the child with the best value so far plus a bonus for its prior
+// that shrinks as it's visited, then expands the leaf and backs its evaluation up the path
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct MctsConfig {
+  pub playouts: usize,
+  // weight of the prior bonus against the value found so far
+  pub exploration: f64,
+  // leaves picked before any of them is evaluated. each pick counts as `virtual_loss` lost
+  // games along its path until then, so the next pick looks somewhere else
+  pub leaves_per_batch: usize,
+  pub virtual_loss: f64,
+  // the move is drawn in proportion to the root's visit counts raised to 1 / temperature;
+  // 0 always plays the most visited move
+  pub temperature: f64,
+  // turn number after which the temperature drops to 0
+  pub temperature_plies: u8,
+  // share of the root priors replaced by Dirichlet noise, 0 for none
+  pub noise_fraction: f64,
+  pub dirichlet_alpha: f64,
+  // evaluator scores are divided by this and clamped to -1..1. 1 suits networks, whose
+  // outputs are already in that range; material scores want something like 10
+  pub value_scale: f64,
+  // keeps the part of the tree below the position the game reaches, to search on from
+  pub reuse_tree: bool,
+  pub seed: u64
+}
+
+pub fn default_mcts_config() -> MctsConfig {
+  MctsConfig {
+    playouts: 800,
+    exploration: 1.5,
+    leaves_per_batch: 8,
+    virtual_loss: 1.0,
+    temperature: 0.0,
+    temperature_plies: 30,
+    noise_fraction: 0.0,
+    dirichlet_alpha: 0.3,
+    value_scale: 1.0,
+    reuse_tree: true,
+    seed: 0
+  }
+}
+
+#[derive(Clone, Debug)]
+struct Node {
+  state: [u64; 13],
+  turn_number: u8,
+  parent: Option<usize>,
+  children: Vec<usize>,
+  prior: f64,
+  visits: u32,
+  // values for the side that moved into the node, summed over its visits
+  value_sum: f64,
+  // picks in the current batch that haven't been backed up yet
+  virtual_visits: u32,
+  expanded: bool
+}
+
+fn make_node(state: [u64; 13], turn_number: u8, parent: Option<usize>, prior: f64) -> Node {
+  Node {
+    state,
+    turn_number,
+    parent,
+    children: Vec::new(),
+    prior,
+    visits: 0,
+    value_sum: 0.0,
+    virtual_visits: 0,
+    expanded: false
+  }
+}
+
+// Box-Muller
+fn standard_normal(rng: &mut StdRng) -> f64 {
+  let (first, second): (f64, f64) = (1.0 - rng.gen::<f64>(), rng.gen::<f64>());
+  (-2.0 * first.ln()).sqrt() * (2.0 * std::f64::consts::PI * second).cos()
+}
+
+// Marsaglia and Tsang's method, boosted from alpha + 1 for shapes below 1
+pub fn sample_gamma(alpha: f64, rng: &mut StdRng) -> f64 {
+  if alpha < 1.0 {
+    return sample_gamma(alpha + 1.0, rng) * rng.gen::<f64>().powf(1.0 / alpha);
+  }
+  let d: f64 = alpha - 1.0 / 3.0;
+  let c: f64 = 1.0 / (9.0 * d).sqrt();
+  loop {
+    let x: f64 = standard_normal(rng);
+    let v: f64 = (1.0 + c * x).powi(3);
+    if v <= 0.0 {
+      continue;
+    }
+    if rng.gen::<f64>().ln() < 0.5 * x * x + d - d * v + d * v.ln() {
+      return d * v;
+    }
+  }
+}
+
+// `count` shares that sum to 1, each gamma distributed with shape `alpha` before normalising
+pub fn sample_dirichlet(alpha: f64, count: usize, rng: &mut StdRng) -> Vec<f64> {
+  let samples: Vec<f64> = (0..count).map(|_| sample_gamma(alpha, rng)).collect();
+  let total: f64 = samples.iter().sum();
+  if total > 0.0 {
+    samples.iter().map(|sample| sample / total).collect()
+  } else {
+    vec![1.0 / count as f64; count]
+  }
+}
+
+pub struct Mcts {
+  evaluator: Box<dyn Evaluator>,
+  pub config: MctsConfig,
+  // the root is always the first node
+  nodes: Vec<Node>,
+  // the root children's priors with the noise mixed in, when there's noise
+  root_priors: Vec<f64>,
+  rng: StdRng
+}
+
+pub fn make_mcts(evaluator: Box<dyn Evaluator>, config: MctsConfig) -> Mcts {
+  Mcts {
+    evaluator,
+    config,
+    nodes: Vec::new(),
+    root_priors: Vec::new(),
+    rng: StdRng::seed_from_u64(config.seed)
+  }
+}
+
+impl Mcts {
+  // visits of the root, which a reused tree starts with
+  pub fn root_visits(&self) -> u32 {
+    self.nodes.first().map(|root| root.visits).unwrap_or(0)
+  }
+
+  // each move from the root with its visit count
+  pub fn root_children(&self) -> Vec<([u64; 13], u32)> {
+    match self.nodes.first() {
+      Some(root) => root.children.iter().map(|child| (self.nodes[*child].state, self.nodes[*child].visits)).collect(),
+      None => Vec::new()
+    }
+  }
+
+  // the root's average value from white's side
+  pub fn root_value(&self) -> f64 {
+    match self.nodes.first() {
+      Some(root) if root.visits > 0 => {
+        let value: f64 = root.value_sum / root.visits as f64;
+        // the root's values are for the side that moved into it
+        if root.turn_number % 2 == 0 { value } else { -value }
+      },
+      _ => 0.0
+    }
+  }
+
+  // the position after the move the search picks for the side moving on `turn_number`, or
+  // `state` itself when there's no move to make
+  pub fn search(&mut self, state: [u64; 13], turn_number: u8) -> [u64; 13] {
+    self.set_root(state, turn_number);
+    if !self.nodes[0].expanded {
+      let value: f64 = self.evaluate_leaf(0);
+      self.back_up(0, value);
+    }
+    if self.nodes[0].children.is_empty() {
+      return state;
+    }
+    self.mix_root_noise();
+    let mut playouts: usize = 0;
+    while playouts < self.config.playouts {
+      let batch: usize = self.config.leaves_per_batch.max(1).min(self.config.playouts - playouts);
+      let leaves: Vec<usize> = (0..batch).map(|_| self.select_leaf()).collect();
+      for leaf in leaves {
+        let value: f64 = self.evaluate_leaf(leaf);
+        self.back_up(leaf, value);
+      }
+      playouts += batch;
+    }
+    let chosen: usize = self.choose_child(turn_number);
+    let child_state: [u64; 13] = self.nodes[chosen].state;
+    if self.config.reuse_tree {
+      self.rebase(chosen);
+    } else {
+      self.nodes.clear();
+    }
+    child_state
+  }
+
+  // keeps the tree when the position is its root or a node up to two plies below, so
+  // either the move this search chose or the opponent's reply to it
+  fn set_root(&mut self, state: [u64; 13], turn_number: u8) {
+    let matches = |node: &Node| node.state == state && node.turn_number == turn_number;
+    let found: Option<usize> = if !self.config.reuse_tree || self.nodes.is_empty() {
+      None
+    } else if matches(&self.nodes[0]) {
+      Some(0)
+    } else {
+      self.nodes[0].children.iter()
+        .flat_map(|child| std::iter::once(*child).chain(self.nodes[*child].children.iter().copied()))
+        .find(|index| matches(&self.nodes[*index]))
+    };
+    match found {
+      Some(index) => self.rebase(index),
+      None => self.nodes = vec![make_node(state, turn_number, None, 1.0)]
+    }
+    self.root_priors.clear();
+  }
+
+  // makes `index` the root, keeping only what lies below it
+  fn rebase(&mut self, index: usize) {
+    if index == 0 {
+      return;
+    }
+    let mut kept: Vec<Node> = Vec::new();
+    // (old index, new parent)
+    let mut queue: std::collections::VecDeque<(usize, Option<usize>)> = std::collections::VecDeque::from([(index, None)]);
+    while let Some((old, parent)) = queue.pop_front() {
+      let new: usize = kept.len();
+      let mut node: Node = self.nodes[old].clone();
+      node.parent = parent;
+      node.children = Vec::new();
+      kept.push(node);
+      if let Some(parent) = parent {
+        kept[parent].children.push(new);
+      }
+      for child in self.nodes[old].children.iter() {
+        queue.push_back((*child, Some(new)));
+      }
+    }
+    self.nodes = kept;
+  }
+
+  fn mix_root_noise(&mut self) {
+    if self.config.noise_fraction <= 0.0 {
+      return;
+    }
+    let children: Vec<usize> = self.nodes[0].children.clone();
+    let noise: Vec<f64> = sample_dirichlet(self.config.dirichlet_alpha, children.len(), &mut self.rng);
+    self.root_priors = children.iter().zip(noise.iter())
+      .map(|(child, noise)| (1.0 - self.config.noise_fraction) * self.nodes[*child].prior + self.config.noise_fraction * noise)
+      .collect();
+  }
+
+  fn puct(&self, parent: usize, child_position: usize) -> f64 {
+    let child: &Node = &self.nodes[self.nodes[parent].children[child_position]];
+    let visits: f64 = (child.visits + child.virtual_visits) as f64;
+    let value: f64 = if visits == 0.0 { 0.0 } else { (child.value_sum - self.config.virtual_loss * child.virtual_visits as f64) / visits };
+    let parent_visits: f64 = (self.nodes[parent].visits + self.nodes[parent].virtual_visits) as f64;
+    let prior: f64 = if parent == 0 && !self.root_priors.is_empty() { self.root_priors[child_position] } else { child.prior };
+    value + self.config.exploration * prior * parent_visits.max(1.0).sqrt() / (1.0 + visits)
+  }
+
+  // walks from the root to a node that hasn't been expanded or has no moves, marking the
+  // path with virtual visits
+  fn select_leaf(&mut self) -> usize {
+    let mut node: usize = 0;
+    loop {
+      self.nodes[node].virtual_visits += 1;
+      if !self.nodes[node].expanded || self.nodes[node].children.is_empty() {
+        return node;
+      }
+      let best: usize = (0..self.nodes[node].children.len())
+        .map(|position| (position, self.puct(node, position)))
+        .fold((0, f64::NEG_INFINITY), |best, candidate| if candidate.1 > best.1 { candidate } else { best }).0;
+      node = self.nodes[node].children[best];
+    }
+  }
+
+  // the leaf's value for the side that moved into it, expanding it the first time
+  fn evaluate_leaf(&mut self, index: usize) -> f64 {
+    let (state, turn_number) = (self.nodes[index].state, self.nodes[index].turn_number);
+    if king_captured(state) {
+      self.nodes[index].expanded = true;
+      // only the side that just moved can have taken a king
+      return 1.0;
+    }
+    if !self.nodes[index].expanded {
+      let children: Vec<[u64; 13]> = states_for_turn(state, turn_number);
+      let priors: Vec<f64> = self.priors(state, turn_number, &children);
+      for (child, prior) in children.into_iter().zip(priors) {
+        let child_index: usize = self.nodes.len();
+        self.nodes.push(make_node(child, turn_number + 1, Some(index), prior));
+        self.nodes[index].children.push(child_index);
+      }
+      self.nodes[index].expanded = true;
+    }
+    if self.nodes[index].children.is_empty() {
+      return 0.0;
+    }
+    let white_value: f64 = (self.evaluator.evaluate(&make_position(state, turn_number)) / self.config.value_scale).clamp(-1.0, 1.0);
+    // white moved into the node when black is to move
+    if turn_number % 2 == 0 { white_value } else { -white_value }
+  }
+
+  // the evaluator's move priors normalised to sum to 1, or an even split without them
+  fn priors(&mut self, state: [u64; 13], turn_number: u8, children: &[[u64; 13]]) -> Vec<f64> {
+    let uniform: Vec<f64> = vec![1.0 / children.len().max(1) as f64; children.len()];
+    match self.evaluator.move_priors(&make_position(state, turn_number), children) {
+      Some(priors) => {
+        let total: f64 = priors.iter().map(|prior| prior.max(0.0)).sum();
+        if total > 0.0 { priors.iter().map(|prior| prior.max(0.0) / total).collect() } else { uniform }
+      },
+      None => uniform
+    }
+  }
+
+  // adds `value` to the leaf and its ancestors, flipping it at every ply, and takes back
+  // the virtual visits the path was marked with
+  fn back_up(&mut self, leaf: usize, value: f64) {
+    let mut node: Option<usize> = Some(leaf);
+    let mut value: f64 = value;
+    while let Some(index) = node {
+      let current: &mut Node = &mut self.nodes[index];
+      current.visits += 1;
+      current.value_sum += value;
+      current.virtual_visits = current.virtual_visits.saturating_sub(1);
+      value = -value;
+      node = current.parent;
+    }
+  }
+
+  fn choose_child(&mut self, turn_number: u8) -> usize {
+    let children: Vec<usize> = self.nodes[0].children.clone();
+    if self.config.temperature > 0.0 && turn_number <= self.config.temperature_plies {
+      let weights: Vec<f64> = children.iter().map(|child| (self.nodes[*child].visits as f64).powf(1.0 / self.config.temperature)).collect();
+      let total: f64 = weights.iter().sum();
+      if total > 0.0 {
+        let mut pick: f64 = self.rng.gen::<f64>() * total;
+        for (child, weight) in children.iter().zip(weights.iter()) {
+          if pick < *weight {
+            return *child;
+          }
+          pick -= weight;
+        }
+      }
+    }
+    // most visits, then the higher prior
+    *children.iter()
+      .max_by(|a, b| (self.nodes[**a].visits, self.nodes[**a].prior).partial_cmp(&(self.nodes[**b].visits, self.nodes[**b].prior)).unwrap_or(std::cmp::Ordering::Equal))
+      .unwrap()
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::constants::*;
+  use crate::evaluator::BasicEvaluator;
+  use crate::game::setup_board;
+
+  fn material_mcts(playouts: usize) -> Mcts {
+    make_mcts(Box::new(BasicEvaluator), MctsConfig { playouts, value_scale: 10.0, ..default_mcts_config() })
+  }
+
+  #[test]
+  fn dirichlet_noise_is_a_distribution() {
+    let mut rng: StdRng = StdRng::seed_from_u64(3);
+    let noise: Vec<f64> = sample_dirichlet(0.3, 20, &mut rng);
+    assert!(noise.len() == 20 && noise.iter().all(|share| *share >= 0.0));
+    assert!((noise.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+    // a gamma's mean is its shape
+    for alpha in [0.3, 2.5] {
+      let mean: f64 = (0..4000).map(|_| sample_gamma(alpha, &mut rng)).sum::<f64>() / 4000.0;
+      assert!((mean - alpha).abs() < 0.1 * alpha + 0.02, "{}: {}", alpha, mean);
+    }
+  }
+
+  #[test]
+  fn takes_a_hanging_king() {
+    // white: Kh1, Ra1; black: Ka8
+    let mut board: [u64; 13] = [0; 13];
+    board[WKING as usize] = 1 << 7;
+    board[WROOK as usize] = 1;
+    board[BKING as usize] = 1 << 56;
+    let child: [u64; 13] = material_mcts(200).search(board, 1);
+    assert!(child[BKING as usize] == 0);
+  }
+
+  #[test]
+  fn virtual_loss_spreads_a_batch() {
+    let mut mcts: Mcts = make_mcts(Box::new(BasicEvaluator), MctsConfig { playouts: 8, value_scale: 10.0, reuse_tree: false, ..default_mcts_config() });
+    mcts.set_root(setup_board(), 1);
+    let value: f64 = mcts.evaluate_leaf(0);
+    mcts.back_up(0, value);
+    let leaves: Vec<usize> = (0..8).map(|_| mcts.select_leaf()).collect();
+    let mut distinct: Vec<usize> = leaves.clone();
+    distinct.sort_unstable();
+    distinct.dedup();
+    assert!(distinct.len() == 8);
+    for leaf in leaves {
+      let value: f64 = mcts.evaluate_leaf(leaf);
+      mcts.back_up(leaf, value);
+    }
+    assert!(mcts.root_visits() == 9);
+    assert!(mcts.nodes.iter().all(|node| node.virtual_visits == 0));
+  }
+
+  #[test]
+  fn reuses_the_tree_after_a_reply() {
+    let mut mcts: Mcts = material_mcts(300);
+    let after: [u64; 13] = mcts.search(setup_board(), 1);
+    assert!(mcts.root_visits() > 0 && mcts.nodes[0].state == after);
+    let (reply, visits) = mcts.root_children().into_iter().max_by_key(|(_, visits)| *visits).unwrap();
+    mcts.set_root(reply, 3);
+    assert!(mcts.root_visits() == visits && mcts.nodes[0].parent.is_none());
+    assert!(mcts.nodes.iter().skip(1).all(|node| node.parent.is_some_and(|parent| parent < mcts.nodes.len())));
+    // anything else starts over
+    mcts.set_root(setup_board(), 1);
+    assert!(mcts.root_visits() == 0 && mcts.nodes.len() == 1);
+  }
+
+  #[test]
+  fn temperature_picks_among_visited_moves() {
+    let config: MctsConfig = MctsConfig { playouts: 100, value_scale: 10.0, temperature: 1.0, noise_fraction: 0.25, seed: 5, reuse_tree: false, ..default_mcts_config() };
+    let mut mcts: Mcts = make_mcts(Box::new(BasicEvaluator), config);
+    let child: [u64; 13] = mcts.search(setup_board(), 1);
+    assert!(states_for_turn(setup_board(), 1).contains(&child));
+  }
+}
@@ -0,0 +1,131 @@
+use std::f64::consts::E;
+
+const LEAKY_SLOPE: f64 = 0.01;
+
+// a layer's activation, stored by name so networks can be saved and loaded with it
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Activation {
+  Tanh,
+  // tanh trained with its derivative capped at 0.5, how networks were originally trained
+  TanhClipped,
+  Sigmoid,
+  Relu,
+  LeakyRelu,
+  // relu capped at 1, as used by the nnue feature layer
+  ClippedRelu,
+  Identity
+}
+
+pub const ACTIVATIONS: [Activation; 7] = [
+  Activation::Tanh,
+  Activation::TanhClipped,
+  Activation::Sigmoid,
+  Activation::Relu,
+  Activation::LeakyRelu,
+  Activation::ClippedRelu,
+  Activation::Identity
+];
+
+pub fn tanh(input: f64) -> f64 {
+  ((E.powf(input)) - E.powf(-input)) /
+  ((E.powf(input)) + E.powf(-input))
+}
+
+pub fn tanh_der(input: f64) -> f64 {
+  let activated = tanh(input);
+  1f64 - activated * activated
+}
+
+// tanh_der capped at 0.5, which slows learning where the curve is steepest
+pub fn tanh_der_clipped(input: f64) -> f64 {
+  let val = tanh_der(input);
+  if val.abs() < 0.5f64 {
+    val
+  } else if val >= 0.5f64 {
+    0.5f64
+  } else {
+    -0.5f64
+  }
+}
+
+fn sigmoid(input: f64) -> f64 {
+  1f64 / (1f64 + E.powf(-input))
+}
+
+impl Activation {
+  pub fn name(&self) -> &'static str {
+    match self {
+      Activation::Tanh => "tanh",
+      Activation::TanhClipped => "tanh_clipped",
+      Activation::Sigmoid => "sigmoid",
+      Activation::Relu => "relu",
+      Activation::LeakyRelu => "leaky_relu",
+      Activation::ClippedRelu => "clipped_relu",
+      Activation::Identity => "identity"
+    }
+  }
+
+  pub fn from_name(name: &str) -> Option<Activation> {
+    ACTIVATIONS.iter().copied().find(|activation| activation.name() == name)
+  }
+
+  // the activation behind the function and derivative names files stored before layers
+  // had their own activations
+  pub fn from_legacy_names(act_fn_name: &str, der_fn_name: &str) -> Option<Activation> {
+    match (act_fn_name, der_fn_name) {
+      ("tanh", "tanh_der_clipped") => Some(Activation::TanhClipped),
+      ("tanh", "tanh_der") => Some(Activation::Tanh),
+      _ => None
+    }
+  }
+
+  pub fn apply(&self, input: f64) -> f64 {
+    match self {
+      Activation::Tanh | Activation::TanhClipped => tanh(input),
+      Activation::Sigmoid => sigmoid(input),
+      Activation::Relu => input.max(0f64),
+      Activation::LeakyRelu => if input > 0f64 { input } else { input * LEAKY_SLOPE },
+      Activation::ClippedRelu => input.clamp(0f64, 1f64),
+      Activation::Identity => input
+    }
+  }
+
+  // derivative at the pre-activation sum `input`
+  pub fn derivative(&self, input: f64) -> f64 {
+    match self {
+      Activation::Tanh => tanh_der(input),
+      Activation::TanhClipped => tanh_der_clipped(input),
+      Activation::Sigmoid => sigmoid(input) * (1f64 - sigmoid(input)),
+      Activation::Relu => if input > 0f64 { 1f64 } else { 0f64 },
+      Activation::LeakyRelu => if input > 0f64 { 1f64 } else { LEAKY_SLOPE },
+      Activation::ClippedRelu => if input > 0f64 && input < 1f64 { 1f64 } else { 0f64 },
+      Activation::Identity => 1f64
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn names_round_trip() {
+    for activation in ACTIVATIONS.iter() {
+      assert!(Activation::from_name(activation.name()) == Some(*activation));
+    }
+    assert!(Activation::from_name("swish").is_none());
+  }
+
+  #[test]
+  fn derivatives_match_the_slope() {
+    let epsilon: f64 = 1e-6;
+    // away from the kinks of the piecewise activations
+    for input in [-2.3, -0.4, 0.3, 0.7, 1.9] {
+      for activation in ACTIVATIONS.iter().filter(|activation| **activation != Activation::TanhClipped) {
+        let slope: f64 = (activation.apply(input + epsilon) - activation.apply(input - epsilon)) / (2f64 * epsilon);
+        assert!((slope - activation.derivative(input)).abs() < 1e-6, "{} at {}", activation.name(), input);
+      }
+    }
+    assert!(Activation::TanhClipped.derivative(0f64) == 0.5);
+  }
+}
@@ -0,0 +1,435 @@
+use super::net::Net;
+use super::encoding::InputEncoder;
+use super::activation::Activation;
+use super::network_storage::{activation_from_legacy_names, activation_from_name, parse_network_text, write_file_atomically};
+
+// layout, all little-endian:
+//   magic "CENN", format version u16, precision u8
+//   input encoder name, a u8 length and the bytes
+//   learning rate f64
+//   layer count u32 and each layer's width u32, input layer first and output last
+//   the activation name of every layer after the input, each like the encoder's
+//   the policy head's move count u32, 0 without one
+//   for i16 weights, the f32 scale they were multiplied by
+//   every weight by layer, node and next node, then every bias by layer and node. the
+//   input layer's biases come first and are always zero. then the policy head's weights, a
+//   row of the last hidden layer's width per move, and its biases
+//   CRC-32 of everything before it, u32
+const MAGIC: &[u8; 4] = b"CENN";
+pub const BINARY_FORMAT_VERSION: u16 = 3;
+// version 2 had no policy head
+const NO_POLICY_FORMAT_VERSION: u16 = 2;
+// version 1 named one activation and derivative pair, before the encoder name, for all layers
+const FIRST_FORMAT_VERSION: u16 = 1;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Precision {
+  F32,
+  F64,
+  // each value times a per-file scale that maps the largest magnitude to i16::MAX
+  I16
+}
+
+impl Precision {
+  fn id(&self) -> u8 {
+    match self {
+      Precision::F32 => 0,
+      Precision::F64 => 1,
+      Precision::I16 => 2
+    }
+  }
+
+  fn from_id(id: u8) -> Option<Precision> {
+    [Precision::F32, Precision::F64, Precision::I16].into_iter().find(|precision| precision.id() == id)
+  }
+
+  pub fn from_name(name: &str) -> Option<Precision> {
+    match name {
+      "f32" => Some(Precision::F32),
+      "f64" => Some(Precision::F64),
+      "i16" => Some(Precision::I16),
+      _ => None
+    }
+  }
+}
+
+// IEEE CRC-32, as used by zip and png
+pub fn crc32(bytes: &[u8]) -> u32 {
+  let mut crc: u32 = 0xFFFFFFFF;
+  for byte in bytes {
+    crc ^= *byte as u32;
+    for _bit in 0..8 {
+      crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB88320 } else { crc >> 1 };
+    }
+  }
+  !crc
+}
+
+pub fn is_binary_network(bytes: &[u8]) -> bool {
+  bytes.starts_with(MAGIC)
+}
+
+// the precision byte after the magic number and version, for a binary network
+pub fn binary_precision(bytes: &[u8]) -> Option<Precision> {
+  if !is_binary_network(bytes) {
+    return None;
+  }
+  bytes.get(MAGIC.len() + 2).and_then(|id| Precision::from_id(*id))
+}
+
+pub fn push_name(bytes: &mut Vec<u8>, name: &str) {
+  bytes.push(name.len() as u8);
+  bytes.extend_from_slice(name.as_bytes());
+}
+
+// in the file's order, which walks each layer's weights input by input
+fn parameters(net: &Net) -> Vec<f64> {
+  let mut values: Vec<f64> = Vec::new();
+  for layer in net.layers.iter() {
+    for input_index in 0..layer.inputs() {
+      values.extend((0..layer.outputs()).map(|output_index| layer.weight(input_index, output_index) as f64));
+    }
+  }
+  values.extend(vec![0f64; net.input.len()]);
+  values.extend(net.layers.iter().flat_map(|layer| layer.biases.iter()).map(|bias| *bias as f64));
+  values.extend(net.policy.iter().flat_map(|policy| policy.weights.iter().chain(policy.biases.iter())).map(|value| *value as f64));
+  values
+}
+
+fn set_parameters(net: &mut Net, values: Vec<f64>) {
+  let mut values = values.into_iter();
+  for layer in net.layers.iter_mut() {
+    for input_index in 0..layer.inputs() {
+      for output_index in 0..layer.outputs() {
+        *layer.weight_mut(input_index, output_index) = values.next().unwrap() as f32;
+      }
+    }
+  }
+  values.by_ref().take(net.input.len()).for_each(drop);
+  for bias in net.layers.iter_mut().flat_map(|layer| layer.biases.iter_mut()) {
+    *bias = values.next().unwrap() as f32;
+  }
+  if let Some(policy) = net.policy.as_mut() {
+    for value in policy.weights.iter_mut().chain(policy.biases.iter_mut()) {
+      *value = values.next().unwrap() as f32;
+    }
+  }
+}
+
+pub fn network_to_bytes(net: &Net, precision: Precision) -> Vec<u8> {
+  let mut bytes: Vec<u8> = Vec::new();
+  bytes.extend_from_slice(MAGIC);
+  bytes.extend_from_slice(&BINARY_FORMAT_VERSION.to_le_bytes());
+  bytes.push(precision.id());
+  push_name(&mut bytes, net.encoder.name());
+  bytes.extend_from_slice(&net.learning_rate.to_le_bytes());
+  let widths: Vec<usize> = net.widths();
+  bytes.extend_from_slice(&(widths.len() as u32).to_le_bytes());
+  for width in widths.iter() {
+    bytes.extend_from_slice(&(*width as u32).to_le_bytes());
+  }
+  for layer in net.layers.iter() {
+    push_name(&mut bytes, layer.activation.name());
+  }
+  bytes.extend_from_slice(&(net.policy.as_ref().map(|policy| policy.outputs()).unwrap_or(0) as u32).to_le_bytes());
+
+  let values: Vec<f64> = parameters(net);
+  match precision {
+    Precision::F32 => values.iter().for_each(|value| bytes.extend_from_slice(&(*value as f32).to_le_bytes())),
+    Precision::F64 => values.iter().for_each(|value| bytes.extend_from_slice(&value.to_le_bytes())),
+    Precision::I16 => {
+      let largest: f64 = values.iter().fold(0f64, |largest, value| largest.max(value.abs()));
+      let scale: f32 = if largest > 0.0 { (i16::MAX as f64 / largest) as f32 } else { 1.0 };
+      bytes.extend_from_slice(&scale.to_le_bytes());
+      for value in values.iter() {
+        let quantised: i16 = (value * scale as f64).round().clamp(i16::MIN as f64, i16::MAX as f64) as i16;
+        bytes.extend_from_slice(&quantised.to_le_bytes());
+      }
+    }
+  }
+  let checksum: u32 = crc32(&bytes);
+  bytes.extend_from_slice(&checksum.to_le_bytes());
+  bytes
+}
+
+// reads the fields of a binary file in order, naming the field a short file ends in
+pub struct Reader<'a> {
+  pub bytes: &'a [u8],
+  pub offset: usize
+}
+
+impl<'a> Reader<'a> {
+  pub fn take(&mut self, count: usize, what: &str) -> Result<&'a [u8], String> {
+    if self.bytes.len() - self.offset < count {
+      return Err(format!("file ends at byte {} while reading {}", self.bytes.len(), what));
+    }
+    let taken: &'a [u8] = &self.bytes[self.offset..self.offset + count];
+    self.offset += count;
+    Ok(taken)
+  }
+
+  pub fn array<const N: usize>(&mut self, what: &str) -> Result<[u8; N], String> {
+    Ok(self.take(N, what)?.try_into().unwrap())
+  }
+
+  pub fn u32(&mut self, what: &str) -> Result<u32, String> {
+    Ok(u32::from_le_bytes(self.array(what)?))
+  }
+
+  pub fn name(&mut self, what: &str) -> Result<String, String> {
+    let length: usize = self.take(1, what)?[0] as usize;
+    String::from_utf8(self.take(length, what)?.to_vec()).map_err(|_| format!("{} is not valid UTF-8", what))
+  }
+}
+
+pub fn network_from_bytes(bytes: &[u8]) -> Result<Net, String> {
+  let mut reader: Reader = Reader { bytes, offset: 0 };
+  if reader.take(4, "the magic number")? != MAGIC {
+    return Err(String::from("not a binary network: bad magic number"));
+  }
+  let version: u16 = u16::from_le_bytes(reader.array("the format version")?);
+  if version != BINARY_FORMAT_VERSION && version != NO_POLICY_FORMAT_VERSION && version != FIRST_FORMAT_VERSION {
+    return Err(format!("unsupported format version {}, expected {}", version, BINARY_FORMAT_VERSION));
+  }
+  if bytes.len() < 4 + reader.offset {
+    return Err(String::from("file too short to hold a checksum"));
+  }
+  let (body, stored) = bytes.split_at(bytes.len() - 4);
+  let stored_checksum: u32 = u32::from_le_bytes(stored.try_into().unwrap());
+  let checksum: u32 = crc32(body);
+  if stored_checksum != checksum {
+    return Err(format!("checksum mismatch: stored {:08x}, computed {:08x}", stored_checksum, checksum));
+  }
+  reader.bytes = body;
+
+  let precision_id: u8 = reader.take(1, "the precision")?[0];
+  let precision: Precision = Precision::from_id(precision_id).ok_or(format!("unknown precision id {}", precision_id))?;
+  let legacy_activation: Option<Activation> = if version == FIRST_FORMAT_VERSION {
+    let act_fn_name: String = reader.name("the activation name")?;
+    let der_fn_name: String = reader.name("the derivative name")?;
+    Some(activation_from_legacy_names(&act_fn_name, &der_fn_name)?)
+  } else {
+    None
+  };
+  let encoder_name: String = reader.name("the encoder name")?;
+  let encoder: InputEncoder = InputEncoder::from_name(&encoder_name).ok_or(format!("unknown input encoder '{}'", encoder_name))?;
+  let learning_rate: f64 = f64::from_le_bytes(reader.array("the learning rate")?);
+
+  let layer_count: usize = reader.u32("the layer count")? as usize;
+  if layer_count < 3 {
+    return Err(format!("{} layers; a network needs input, hidden and output layers", layer_count));
+  }
+  let mut widths: Vec<usize> = Vec::new();
+  for layer_index in 0..layer_count {
+    widths.push(reader.u32(&format!("the width of layer {}", layer_index))? as usize);
+  }
+  let hidden: &[usize] = &widths[1..layer_count - 1];
+  if widths[layer_count - 1] != 1 || hidden.contains(&0) {
+    return Err(format!("layer widths {:?} aren't supported: hidden layers can't be empty and the output must be a single node", widths));
+  }
+  if widths[0] != encoder.input_size() {
+    return Err(format!("{} inputs don't fit the {} encoder, which needs {}", widths[0], encoder.name(), encoder.input_size()));
+  }
+  let activations: Vec<Activation> = match legacy_activation {
+    Some(activation) => vec![activation; layer_count - 1],
+    None => (1..layer_count)
+      .map(|layer_index| activation_from_name(&reader.name(&format!("the activation of layer {}", layer_index))?))
+      .collect::<Result<Vec<Activation>, String>>()?
+  };
+  let policy_moves: usize = if version == BINARY_FORMAT_VERSION { reader.u32("the policy move count")? as usize } else { 0 };
+
+  // checked before building the net so a bogus width can't ask for a huge allocation
+  let policy_count: usize = policy_moves.saturating_mul(widths[layer_count - 2].saturating_add(1));
+  let count: usize = widths.windows(2).map(|pair| pair[0].saturating_mul(pair[1])).chain(widths.iter().copied()).fold(policy_count, usize::saturating_add);
+  let value_size: usize = match precision { Precision::F32 => 4, Precision::F64 => 8, Precision::I16 => 2 };
+  let scale_size: usize = if precision == Precision::I16 { 4 } else { 0 };
+  let needed: usize = value_size.saturating_mul(count).saturating_add(scale_size);
+  if body.len() - reader.offset != needed {
+    return Err(format!(
+      "layer widths {:?} need {} bytes of weights and biases, found {}",
+      widths, needed, body.len() - reader.offset
+    ));
+  }
+
+  let mut net: Net = Net::with_widths(vec![0f64; widths[0]], hidden, activations[0], learning_rate);
+  net.encoder = encoder;
+  for (layer, activation) in net.layers.iter_mut().zip(activations) {
+    layer.activation = activation;
+  }
+  if policy_moves > 0 {
+    net.add_policy_head(policy_moves);
+  }
+
+  let values: Vec<f64> = match precision {
+    Precision::F32 => reader.take(4 * count, "the weights")?.chunks(4).map(|chunk| f32::from_le_bytes(chunk.try_into().unwrap()) as f64).collect(),
+    Precision::F64 => reader.take(8 * count, "the weights")?.chunks(8).map(|chunk| f64::from_le_bytes(chunk.try_into().unwrap())).collect(),
+    Precision::I16 => {
+      let scale: f32 = f32::from_le_bytes(reader.array("the quantisation scale")?);
+      if scale <= 0.0 || !scale.is_finite() {
+        return Err(format!("invalid quantisation scale {}", scale));
+      }
+      reader.take(2 * count, "the weights")?.chunks(2).map(|chunk| i16::from_le_bytes(chunk.try_into().unwrap()) as f64 / scale as f64).collect()
+    }
+  };
+
+  set_parameters(&mut net, values);
+  Ok(net)
+}
+
+pub fn write_binary_network(net: &Net, precision: Precision, path: &str) -> Result<(), String> {
+  write_file_atomically(path, &network_to_bytes(net, precision))
+}
+
+pub fn read_binary_network(path: &str) -> Result<Net, String> {
+  let bytes: Vec<u8> = std::fs::read(path).map_err(|error| format!("couldn't read {}: {}", path, error))?;
+  network_from_bytes(&bytes).map_err(|error| format!("{}: {}", path, error))
+}
+
+// rewrites a network stored in the old one-line-per-weight text format
+pub fn convert_text_network(text_path: &str, binary_path: &str, precision: Precision) -> Result<(), String> {
+  let text: String = std::fs::read_to_string(text_path).map_err(|error| format!("couldn't read {}: {}", text_path, error))?;
+  let net: Net = parse_network_text(&text).map_err(|error| format!("{}: {}", text_path, error))?;
+  write_binary_network(&net, precision, binary_path)
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  fn sample_net() -> Net {
+    let mut net: Net = Net::random_with_widths(vec![0f64; 769], &[5, 3], Activation::Relu, 0.05);
+    net.encoder = InputEncoder::SideToMove;
+    net.layers[2].activation = Activation::Identity;
+    net.layers[0].biases[2] = -0.25;
+    net
+  }
+
+  #[test]
+  fn matches_the_standard_crc() {
+    assert!(crc32(b"123456789") == 0xCBF43926);
+  }
+
+  #[test]
+  fn round_trips_at_every_precision() {
+    let net: Net = sample_net();
+    let f64_net: Net = network_from_bytes(&network_to_bytes(&net, Precision::F64)).unwrap();
+    assert!(f64_net.widths() == vec![769, 5, 3, 1]);
+    assert!(f64_net.parameters() == net.parameters());
+    assert!(f64_net.encoder == InputEncoder::SideToMove && f64_net.learning_rate == 0.05);
+    assert!(f64_net.activations() == net.activations());
+    for (precision, tolerance) in [(Precision::F32, 1e-7), (Precision::I16, 1e-4)] {
+      let loaded: Net = network_from_bytes(&network_to_bytes(&net, precision)).unwrap();
+      for (original, stored) in parameters(&net).iter().zip(parameters(&loaded).iter()) {
+        assert!((original - stored).abs() < tolerance, "{:?}", precision);
+      }
+    }
+  }
+
+  #[test]
+  fn keeps_the_policy_head() {
+    let mut net: Net = sample_net();
+    net.add_policy_head(12);
+    net.policy.as_mut().unwrap().weights[7] = 0.75;
+    net.policy.as_mut().unwrap().biases[11] = -0.125;
+    let loaded: Net = network_from_bytes(&network_to_bytes(&net, Precision::F64)).unwrap();
+    assert!(loaded.policy.as_ref().map(|policy| policy.outputs()) == Some(12));
+    assert!(loaded.parameters() == net.parameters());
+  }
+
+  #[test]
+  fn reads_the_version_without_a_policy() {
+    let net: Net = sample_net();
+    let current: Vec<u8> = network_to_bytes(&net, Precision::F64);
+    // the zero policy move count sits just before the values
+    let values_start: usize = current.len() - 4 - 8 * parameters(&net).len();
+    let mut bytes: Vec<u8> = current[..values_start - 4].to_vec();
+    bytes.extend_from_slice(&current[values_start..current.len() - 4]);
+    bytes[4..6].copy_from_slice(&NO_POLICY_FORMAT_VERSION.to_le_bytes());
+    let checksum: u32 = crc32(&bytes);
+    bytes.extend_from_slice(&checksum.to_le_bytes());
+    let loaded: Net = network_from_bytes(&bytes).unwrap();
+    assert!(loaded.policy.is_none() && loaded.parameters() == net.parameters());
+  }
+
+  #[test]
+  fn reads_the_first_version() {
+    let net: Net = sample_net();
+    let mut bytes: Vec<u8> = Vec::new();
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&FIRST_FORMAT_VERSION.to_le_bytes());
+    bytes.push(Precision::F64.id());
+    push_name(&mut bytes, "tanh");
+    push_name(&mut bytes, "tanh_der_clipped");
+    let current: Vec<u8> = network_to_bytes(&net, Precision::F64);
+    // from the encoder name through the layer widths, then the values, without activations
+    // or the policy move count
+    let widths_end: usize = 7 + 1 + net.encoder.name().len() + 8 + 4 + 4 * net.widths().len();
+    bytes.extend_from_slice(&current[7..widths_end]);
+    let activations_length: usize = net.layers.iter().map(|layer| 1 + layer.activation.name().len()).sum();
+    bytes.extend_from_slice(&current[widths_end + activations_length + 4..current.len() - 4]);
+    let checksum: u32 = crc32(&bytes);
+    bytes.extend_from_slice(&checksum.to_le_bytes());
+
+    let loaded: Net = network_from_bytes(&bytes).unwrap();
+    assert!(loaded.activations() == vec![Activation::TanhClipped; 3]);
+    assert!(loaded.parameters() == net.parameters());
+  }
+
+  #[test]
+  fn is_much_smaller_than_text() {
+    let net: Net = Net::create_random(vec![0f64; 768], 2, 20, Activation::Tanh, 0.05);
+    let text_path = std::env::temp_dir().join("size_network.txt");
+    super::super::network_storage::write_network_to_file(net.clone(), text_path.to_str().unwrap()).unwrap();
+    let text_size: usize = std::fs::metadata(&text_path).unwrap().len() as usize;
+    let binary_size: usize = network_to_bytes(&net, Precision::F32).len();
+    assert!(binary_size < 4 * parameters(&net).len() + 100);
+    assert!(binary_size * 5 < text_size);
+  }
+
+  #[test]
+  fn reports_what_is_wrong() {
+    let bytes: Vec<u8> = network_to_bytes(&sample_net(), Precision::F32);
+    assert!(network_from_bytes(b"Hidden 2\n").unwrap_err().contains("magic"));
+
+    let mut newer: Vec<u8> = bytes.clone();
+    newer[4] = 9;
+    assert!(network_from_bytes(&newer).unwrap_err().contains("version 9"));
+
+    let mut corrupted: Vec<u8> = bytes.clone();
+    corrupted[100] ^= 1;
+    assert!(network_from_bytes(&corrupted).unwrap_err().contains("checksum"));
+
+    // a consistent checksum over a body that stops early
+    let mut truncated: Vec<u8> = bytes[..bytes.len() - 40].to_vec();
+    let checksum: u32 = crc32(&truncated);
+    truncated.extend_from_slice(&checksum.to_le_bytes());
+    assert!(network_from_bytes(&truncated).unwrap_err().contains("bytes of weights and biases"));
+
+    let mut unknown: Vec<u8> = network_to_bytes(&sample_net(), Precision::F32);
+    let name_at: usize = unknown.windows(8).position(|window| window == b"identity").unwrap();
+    unknown[name_at..name_at + 8].copy_from_slice(b"identitx");
+    let body_length: usize = unknown.len() - 4;
+    let checksum: u32 = crc32(&unknown[..body_length]);
+    unknown[body_length..].copy_from_slice(&checksum.to_le_bytes());
+    assert!(network_from_bytes(&unknown).unwrap_err().contains("unknown activation 'identitx'"));
+  }
+
+  #[test]
+  fn converts_the_text_format() {
+    let directory = std::env::temp_dir();
+    let text_path = directory.join("convert_network.txt");
+    let binary_path = directory.join("convert_network.bin");
+    std::fs::write(&text_path, "Hidden 1\nNodes 2\nAct tanh\nDer tanh_der\nLr 0.1\nInput 0 0\nWeight 0 0 1 0.5\nBias 1 1 -0.5\nWeight 1 1 0 2\n").unwrap();
+    let mut board: String = std::fs::read_to_string(&text_path).unwrap();
+    // a real network needs the encoder's 768 inputs
+    board.push_str(&(1..768).map(|index| format!("Input {} 0\n", index)).collect::<String>());
+    std::fs::write(&text_path, board).unwrap();
+
+    convert_text_network(text_path.to_str().unwrap(), binary_path.to_str().unwrap(), Precision::F64).unwrap();
+    let net: Net = network_from_bytes(&std::fs::read(&binary_path).unwrap()).unwrap();
+    assert!(net.layers[0].weight(0, 1) == 0.5 && net.layers[0].biases[1] == -0.5 && net.layers[1].weight(1, 0) == 2.0);
+    assert!(net.activations() == vec![Activation::Tanh; 2]);
+    assert!(read_binary_network(binary_path.to_str().unwrap()).is_ok());
+    assert!(convert_text_network("no_such_network.txt", binary_path.to_str().unwrap(), Precision::F32).is_err());
+  }
+}
@@ -0,0 +1,451 @@
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use crate::attacks::is_in_check;
+use crate::board::{get_all_occupation, get_enemy_occupation};
+use crate::bot::{king_captured, make_bot, Bot};
+use crate::evaluator::BasicEvaluator;
+use crate::constants::*;
+use crate::game::setup_board;
+use crate::pgn::{read_pgn_file, replay_game, PgnGame, ReplayedGame};
+use crate::r#move::states_for_turn;
+use crate::zobrist;
+
+// layout, all little-endian:
+//   magic "CEDS", format version u16
+//   then records of RECORD_SIZE bytes until the end of the file:
+//     occupancy u64
+//     16 bytes holding the slice index of each occupied square, lowest square first, two
+//     to a byte with the first in the low nibble
+//     flags u8: 1 when black is to move, 2 when the record has a search score
+//     result u8: white's score in half points, 2 for a white win
+//     search score i16, white's side in centipawns
+//     ply u16, counted from the start of the game
+const MAGIC: &[u8; 4] = b"CEDS";
+pub const DATASET_FORMAT_VERSION: u16 = 1;
+const HEADER_SIZE: usize = 6;
+pub const RECORD_SIZE: usize = 30;
+const MAX_PIECES: usize = 32;
+
+const BLACK_TO_MOVE: u8 = 1;
+const HAS_SCORE: u8 = 2;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Sample {
+  pub state: [u64; 13],
+  // 1 or 2; only whose move it is survives the round trip
+  pub turn_number: u8,
+  // white's score from the game, 1.0 for a white win
+  pub result: f64,
+  // the searching side's verdict in pawns from white's side, when one was recorded
+  pub score: Option<f64>,
+  pub ply: u16
+}
+
+pub fn encode_sample(sample: &Sample) -> Result<[u8; RECORD_SIZE], String> {
+  let mut record: [u8; RECORD_SIZE] = [0; RECORD_SIZE];
+  let occupancy: u64 = get_all_occupation(sample.state);
+  if occupancy.count_ones() as usize > MAX_PIECES {
+    return Err(format!("{} pieces don't fit a record", occupancy.count_ones()));
+  }
+  record[0..8].copy_from_slice(&occupancy.to_le_bytes());
+  let mut squares: u64 = occupancy;
+  let mut piece_index: usize = 0;
+  while squares != 0 {
+    let square: u64 = squares & squares.wrapping_neg();
+    let slice_index: u8 = (0..12).find(|slice_index| sample.state[*slice_index as usize] & square != 0).unwrap();
+    record[8 + piece_index / 2] |= slice_index << (4 * (piece_index % 2));
+    piece_index += 1;
+    squares &= squares - 1;
+  }
+  let mut flags: u8 = 0;
+  if sample.turn_number.is_multiple_of(2) {
+    flags |= BLACK_TO_MOVE;
+  }
+  if sample.score.is_some() {
+    flags |= HAS_SCORE;
+  }
+  record[24] = flags;
+  record[25] = (sample.result * 2.0).round().clamp(0.0, 2.0) as u8;
+  let centipawns: i16 = (sample.score.unwrap_or(0.0) * 100.0).round().clamp(i16::MIN as f64, i16::MAX as f64) as i16;
+  record[26..28].copy_from_slice(&centipawns.to_le_bytes());
+  record[28..30].copy_from_slice(&sample.ply.to_le_bytes());
+  Ok(record)
+}
+
+pub fn decode_sample(record: &[u8; RECORD_SIZE]) -> Result<Sample, String> {
+  let occupancy: u64 = u64::from_le_bytes(record[0..8].try_into().unwrap());
+  if occupancy.count_ones() as usize > MAX_PIECES {
+    return Err(format!("occupancy of {} squares is more than a record holds", occupancy.count_ones()));
+  }
+  let mut state: [u64; 13] = [0; 13];
+  let mut squares: u64 = occupancy;
+  let mut piece_index: usize = 0;
+  while squares != 0 {
+    let slice_index: u8 = (record[8 + piece_index / 2] >> (4 * (piece_index % 2))) & 0xF;
+    if slice_index >= 12 {
+      return Err(format!("unknown piece {}", slice_index));
+    }
+    state[slice_index as usize] |= squares & squares.wrapping_neg();
+    piece_index += 1;
+    squares &= squares - 1;
+  }
+  let flags: u8 = record[24];
+  if record[25] > 2 {
+    return Err(format!("result of {} half points", record[25]));
+  }
+  let centipawns: i16 = i16::from_le_bytes(record[26..28].try_into().unwrap());
+  Ok(Sample {
+    state,
+    turn_number: if flags & BLACK_TO_MOVE != 0 { 2 } else { 1 },
+    result: record[25] as f64 / 2.0,
+    score: if flags & HAS_SCORE != 0 { Some(centipawns as f64 / 100.0) } else { None },
+    ply: u16::from_le_bytes(record[28..30].try_into().unwrap())
+  })
+}
+
+pub struct DatasetWriter<W: Write> {
+  out: W,
+  pub written: usize
+}
+
+pub fn make_dataset_writer<W: Write>(mut out: W) -> Result<DatasetWriter<W>, String> {
+  out.write_all(MAGIC).and_then(|_| out.write_all(&DATASET_FORMAT_VERSION.to_le_bytes()))
+     .map_err(|error| format!("couldn't write the dataset header: {}", error))?;
+  Ok(DatasetWriter { out, written: 0 })
+}
+
+pub fn create_dataset_file(path: &str) -> Result<DatasetWriter<BufWriter<File>>, String> {
+  let file: File = File::create(path).map_err(|error| format!("couldn't create {}: {}", path, error))?;
+  make_dataset_writer(BufWriter::new(file))
+}
+
+impl<W: Write> DatasetWriter<W> {
+  pub fn write(&mut self, sample: &Sample) -> Result<(), String> {
+    self.out.write_all(&encode_sample(sample)?).map_err(|error| format!("couldn't write a sample: {}", error))?;
+    self.written += 1;
+    Ok(())
+  }
+
+  pub fn finish(mut self) -> Result<W, String> {
+    self.out.flush().map_err(|error| format!("couldn't flush the dataset: {}", error))?;
+    Ok(self.out)
+  }
+}
+
+// reads samples one record at a time, so a dataset never has to fit in memory
+pub struct DatasetReader<R: Read> {
+  input: R,
+  read: usize
+}
+
+pub fn make_dataset_reader<R: Read>(mut input: R) -> Result<DatasetReader<R>, String> {
+  let mut header: [u8; HEADER_SIZE] = [0; HEADER_SIZE];
+  input.read_exact(&mut header).map_err(|_| String::from("too short for a dataset header"))?;
+  if &header[0..4] != MAGIC {
+    return Err(String::from("not a dataset: bad magic number"));
+  }
+  let version: u16 = u16::from_le_bytes([header[4], header[5]]);
+  if version != DATASET_FORMAT_VERSION {
+    return Err(format!("unsupported dataset version {}, expected {}", version, DATASET_FORMAT_VERSION));
+  }
+  Ok(DatasetReader { input, read: 0 })
+}
+
+pub fn open_dataset_file(path: &str) -> Result<DatasetReader<BufReader<File>>, String> {
+  let file: File = File::open(path).map_err(|error| format!("couldn't open {}: {}", path, error))?;
+  make_dataset_reader(BufReader::new(file)).map_err(|error| format!("{}: {}", path, error))
+}
+
+impl<R: Read> Iterator for DatasetReader<R> {
+  type Item = Result<Sample, String>;
+
+  fn next(&mut self) -> Option<Result<Sample, String>> {
+    let mut record: [u8; RECORD_SIZE] = [0; RECORD_SIZE];
+    let mut filled: usize = 0;
+    while filled < RECORD_SIZE {
+      match self.input.read(&mut record[filled..]) {
+        Ok(0) if filled == 0 => return None,
+        Ok(0) => return Some(Err(format!("record {} is cut off after {} bytes", self.read, filled))),
+        Ok(count) => filled += count,
+        Err(error) if error.kind() == std::io::ErrorKind::Interrupted => {},
+        Err(error) => return Some(Err(format!("couldn't read record {}: {}", self.read, error)))
+      }
+    }
+    self.read += 1;
+    Some(decode_sample(&record).map_err(|error| format!("record {}: {}", self.read - 1, error)))
+  }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct FilterConfig {
+  // positions where the side to move is in check
+  pub skip_checks: bool,
+  // positions where the side to move has a capture that gains material, whose static value
+  // is still in flux
+  pub skip_captures: bool,
+  // opening positions repeat across games and say little
+  pub min_ply: u16
+}
+
+pub fn default_filter_config() -> FilterConfig {
+  FilterConfig {
+    skip_checks: true,
+    skip_captures: true,
+    min_ply: 8
+  }
+}
+
+lazy_static! {
+  // resolves captures on material alone to tell quiet positions apart
+  static ref QUIESCENCE_BOT: Bot = make_bot(Box::new(BasicEvaluator), 0);
+}
+
+pub fn keeps(filter: &FilterConfig, state: [u64; 13], turn_number: u8, ply: u16) -> bool {
+  ply >= filter.min_ply
+    && !(filter.skip_checks && is_in_check(state, turn_number))
+    && (!filter.skip_captures || QUIESCENCE_BOT.is_quiet(state, turn_number))
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub struct DatasetStats {
+  pub games: usize,
+  // games cut short by a move the generator can't play, or left without a result
+  pub truncated_games: usize,
+  pub skipped_games: usize,
+  pub positions: usize,
+  pub filtered: usize,
+  pub duplicates: usize,
+  pub written: usize
+}
+
+pub fn print_dataset_stats(stats: &DatasetStats) {
+  println!("Games: {} ({} cut short, {} without a result)", stats.games, stats.truncated_games, stats.skipped_games);
+  println!("Positions: {}, filtered {}, duplicates {}", stats.positions, stats.filtered, stats.duplicates);
+  println!("Samples written: {}", stats.written);
+}
+
+// a position, its turn number and the search's score for it, when there was a search
+pub type ScoredPosition = ([u64; 13], u8, Option<f64>);
+
+// filters and deduplicates positions from any number of games into one dataset
+pub struct DatasetBuilder<W: Write> {
+  writer: DatasetWriter<W>,
+  filter: FilterConfig,
+  seen: HashSet<u64>,
+  pub stats: DatasetStats
+}
+
+pub fn make_dataset_builder<W: Write>(writer: DatasetWriter<W>, filter: FilterConfig) -> DatasetBuilder<W> {
+  DatasetBuilder {
+    writer,
+    filter,
+    seen: HashSet::new(),
+    stats: DatasetStats::default()
+  }
+}
+
+impl<W: Write> DatasetBuilder<W> {
+  // positions of one game with the search score of each if there was one
+  pub fn add_game(&mut self, positions: &[ScoredPosition], result: f64) -> Result<(), String> {
+    self.stats.games += 1;
+    for (state, turn_number, score) in positions.iter() {
+      self.stats.positions += 1;
+      let ply: u16 = turn_number.saturating_sub(1) as u16;
+      if !keeps(&self.filter, *state, *turn_number, ply) {
+        self.stats.filtered += 1;
+        continue;
+      }
+      if !self.seen.insert(zobrist::hash(*state, *turn_number)) {
+        self.stats.duplicates += 1;
+        continue;
+      }
+      self.writer.write(&Sample { state: *state, turn_number: 2 - turn_number % 2, result, score: *score, ply })?;
+      self.stats.written += 1;
+    }
+    Ok(())
+  }
+
+  pub fn add_pgn_game(&mut self, game: &PgnGame) -> Result<(), String> {
+    let result: f64 = match game.result {
+      Some(result) => result,
+      None => {
+        self.stats.skipped_games += 1;
+        return Ok(());
+      }
+    };
+    let replayed: ReplayedGame = replay_game(game)?;
+    if !replayed.complete {
+      self.stats.truncated_games += 1;
+    }
+    let positions: Vec<ScoredPosition> = replayed.positions.iter().map(|(state, turn_number)| (*state, *turn_number, None)).collect();
+    self.add_game(&positions, result)
+  }
+
+  pub fn finish(self) -> Result<(W, DatasetStats), String> {
+    let stats: DatasetStats = self.stats;
+    Ok((self.writer.finish()?, stats))
+  }
+}
+
+// every game of every file; a game that can't be replayed is reported with its file
+pub fn dataset_from_pgn_files(paths: &[String], filter: FilterConfig, out_path: &str) -> Result<DatasetStats, String> {
+  let mut builder = make_dataset_builder(create_dataset_file(out_path)?, filter);
+  for path in paths.iter() {
+    for (index, game) in read_pgn_file(path)?.iter().enumerate() {
+      builder.add_pgn_game(game).map_err(|error| format!("{} game {}: {}", path, index + 1, error))?;
+    }
+  }
+  Ok(builder.finish()?.1)
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct SelfPlayConfig {
+  pub games: usize,
+  // random moves at the start of each game, so games don't all repeat
+  pub random_plies: usize,
+  // games still going after this many plies are adjudicated
+  pub max_plies: usize,
+  // a search score past this many pawns at adjudication counts as a win, anything less a draw
+  pub adjudication_score: f64,
+  pub seed: u64
+}
+
+pub fn default_self_play_config() -> SelfPlayConfig {
+  SelfPlayConfig {
+    games: 100,
+    random_plies: 8,
+    max_plies: 160,
+    adjudication_score: 5.0,
+    seed: 0
+  }
+}
+
+// the searched positions of one game the bot plays against itself, and white's score
+pub fn self_play_game(bot: &Bot, config: &SelfPlayConfig, rng: &mut StdRng) -> (Vec<ScoredPosition>, f64) {
+  let mut state: [u64; 13] = setup_board();
+  let mut positions: Vec<ScoredPosition> = Vec::new();
+  let mut last_score: f64 = 0.0;
+  // turn numbers are a u8
+  let max_plies: usize = config.max_plies.min(240);
+  for (ply, turn_number) in (0..max_plies).zip(1u8..) {
+    if king_captured(state) {
+      return (positions, if state[WKING as usize] == 0 { 0.0 } else { 1.0 });
+    }
+    let children: Vec<[u64; 13]> = states_for_turn(state, turn_number);
+    if children.is_empty() {
+      return (positions, 0.5);
+    }
+    if ply < config.random_plies {
+      state = children[rng.gen_range(0..children.len())];
+    } else {
+      let (score, child) = bot.search(state, turn_number);
+      positions.push((state, turn_number, Some(score)));
+      last_score = score;
+      state = child;
+    }
+  }
+  let result: f64 = if last_score > config.adjudication_score {
+    1.0
+  } else if last_score < -config.adjudication_score {
+    0.0
+  } else {
+    0.5
+  };
+  (positions, result)
+}
+
+pub fn dataset_from_self_play(bot: &Bot, config: SelfPlayConfig, filter: FilterConfig, out_path: &str) -> Result<DatasetStats, String> {
+  let mut builder = make_dataset_builder(create_dataset_file(out_path)?, filter);
+  let mut rng: StdRng = StdRng::seed_from_u64(config.seed);
+  for _game in 0..config.games {
+    let (positions, result) = self_play_game(bot, &config, &mut rng);
+    builder.add_game(&positions, result)?;
+  }
+  Ok(builder.finish()?.1)
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::bot::{make_bot_with_config, plain_search_config};
+  use crate::evaluator::BasicEvaluator;
+  use crate::fen::state_from_fen;
+  use crate::pgn::parse_pgn;
+
+  fn no_filter() -> FilterConfig {
+    FilterConfig { skip_checks: false, skip_captures: false, min_ply: 0 }
+  }
+
+  #[test]
+  fn records_round_trip() {
+    let (state, turn_number) = state_from_fen("r3k2r/pp1n1ppp/2p5/8/3P4/2N2N2/PP3PPP/R3K2R b - -").unwrap();
+    for score in [Some(-1.25), None, Some(400.0)] {
+      let sample: Sample = Sample { state, turn_number, result: 0.5, score, ply: 31 };
+      let decoded: Sample = decode_sample(&encode_sample(&sample).unwrap()).unwrap();
+      assert!(decoded.state == state && decoded.turn_number == 2 && decoded.result == 0.5 && decoded.ply == 31);
+      assert!(decoded.score == score.map(|score: f64| score.min(327.67)));
+    }
+  }
+
+  #[test]
+  fn streams_what_was_written() {
+    let mut writer = make_dataset_writer(Vec::new()).unwrap();
+    let sample: Sample = Sample { state: setup_board(), turn_number: 1, result: 1.0, score: Some(0.2), ply: 0 };
+    writer.write(&sample).unwrap();
+    writer.write(&Sample { turn_number: 2, result: 0.0, ..sample }).unwrap();
+    let bytes: Vec<u8> = writer.finish().unwrap();
+    assert!(bytes.len() == HEADER_SIZE + 2 * RECORD_SIZE);
+
+    let samples: Vec<Sample> = make_dataset_reader(&bytes[..]).unwrap().collect::<Result<Vec<Sample>, String>>().unwrap();
+    assert!(samples.len() == 2 && samples[0] == sample && samples[1].result == 0.0);
+    let cut: Vec<Result<Sample, String>> = make_dataset_reader(&bytes[..bytes.len() - 3]).unwrap().collect();
+    assert!(cut.len() == 2 && cut[1].as_ref().unwrap_err().contains("cut off"));
+    assert!(make_dataset_reader(&b"CENN\x01\x00"[..]).is_err());
+  }
+
+  #[test]
+  fn filters_checks_and_pending_captures() {
+    let filter: FilterConfig = default_filter_config();
+    let (quiet, _) = state_from_fen("4k3/8/8/8/8/8/4P3/4K3 w - -").unwrap();
+    assert!(keeps(&filter, quiet, 1, 10));
+    assert!(!keeps(&filter, quiet, 1, 3));
+    let (check, _) = state_from_fen("4k3/8/8/8/8/8/4r3/4K3 w - -").unwrap();
+    assert!(!keeps(&filter, check, 1, 10));
+    let (capture, _) = state_from_fen("4k3/8/8/8/8/3p4/4P3/4K3 w - -").unwrap();
+    assert!(!keeps(&filter, capture, 1, 10));
+    assert!(keeps(&no_filter(), capture, 1, 0));
+    // Qxd4 is a capture, but it loses the queen to cxd4
+    let (losing_capture, _) = state_from_fen("4k3/8/8/2p5/3p4/8/8/3QK3 w - -").unwrap();
+    assert!(keeps(&filter, losing_capture, 1, 10));
+  }
+
+  #[test]
+  fn labels_and_deduplicates_pgn_positions() {
+    let games: Vec<PgnGame> = parse_pgn("1. Nf3 Nf6 2. Ng1 Ng8 3. Nf3 Nf6 0-1\n\n1. e4 *\n").unwrap();
+    let mut builder = make_dataset_builder(make_dataset_writer(Vec::new()).unwrap(), no_filter());
+    for game in games.iter() {
+      builder.add_pgn_game(game).unwrap();
+    }
+    let (bytes, stats) = builder.finish().unwrap();
+    // the knights return to the start, repeating the first two positions
+    assert!(stats.positions == 6 && stats.duplicates == 2 && stats.written == 4);
+    assert!(stats.skipped_games == 1);
+    let samples: Vec<Sample> = make_dataset_reader(&bytes[..]).unwrap().map(Result::unwrap).collect();
+    assert!(samples.iter().all(|sample| sample.result == 0.0 && sample.score.is_none()));
+    assert!(samples[0].state == setup_board() && samples[1].turn_number == 2);
+  }
+
+  #[test]
+  fn self_play_records_scores() {
+    let bot: Bot = make_bot_with_config(Box::new(BasicEvaluator), 0, plain_search_config());
+    let config: SelfPlayConfig = SelfPlayConfig { games: 1, random_plies: 2, max_plies: 12, ..default_self_play_config() };
+    let (positions, result) = self_play_game(&bot, &config, &mut StdRng::seed_from_u64(1));
+    assert!(!positions.is_empty() && positions.len() <= 10);
+    assert!(positions.iter().all(|(_, _, score)| score.is_some()));
+    assert!([0.0, 0.5, 1.0].contains(&result));
+    assert!(positions[0].1 == 3);
+  }
+}
@@ -0,0 +1,256 @@
+use crate::constants::*;
+use crate::evaluator::Position;
+
+// how a position is turned into network inputs. the name is what the network file stores,
+// so a network is always fed the inputs it was trained on
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum InputEncoder {
+  // one input per piece kind, colour and square
+  Board768,
+  // Board768 plus one input that is set when white is to move
+  SideToMove,
+  // SideToMove plus four castling inputs. the board doesn't track castling rights, so a
+  // right counts as kept while the king and that rook are still on their starting squares
+  Castling,
+  // Board768 seen from the side to move: its pieces come first and, for black, the board
+  // is flipped so the mover always plays up the board. the output is the mover's score
+  Mirrored,
+  // HalfKP-style: every non-king piece's square, once from each side's point of view and
+  // split by which quarter of the board that side's king is on. mover's half first
+  KingBuckets
+}
+
+pub const ENCODERS: [InputEncoder; 5] = [
+  InputEncoder::Board768,
+  InputEncoder::SideToMove,
+  InputEncoder::Castling,
+  InputEncoder::Mirrored,
+  InputEncoder::KingBuckets
+];
+
+const KING_BUCKETS: usize = 4;
+// colour relative to the viewing side times the five kinds that aren't kings
+const BUCKET_PIECES: usize = 10;
+const KING_BUCKET_HALF: usize = KING_BUCKETS * BUCKET_PIECES * 64;
+
+fn white_to_move(position: &Position) -> bool {
+  position.turn_number % 2 == 1
+}
+
+// 0 when white is to move, 1 for black
+fn mover(position: &Position) -> usize {
+  if white_to_move(position) { 0 } else { 1 }
+}
+
+fn piece_squares(position: &Position, slice_index: usize) -> impl Iterator<Item = usize> {
+  let mut slice: u64 = position.state[slice_index];
+  std::iter::from_fn(move || {
+    if slice == 0 {
+      return None;
+    }
+    let square: usize = slice.trailing_zeros() as usize;
+    slice &= slice - 1;
+    Some(square)
+  })
+}
+
+// king on its starting square with the rook on `rook_square`
+fn castling_kept(position: &Position, king_slice: u8, king_square: u8, rook_slice: u8, rook_square: u8) -> bool {
+  position.state[king_slice as usize] & (1 << king_square) != 0
+    && position.state[rook_slice as usize] & (1 << rook_square) != 0
+}
+
+// quarter of the board the king stands on, from `white`'s side: queen or king side, home ranks or not
+fn king_bucket(king_square: usize) -> usize {
+  (king_square % 8 >= 4) as usize + 2 * (king_square / 8 >= 2) as usize
+}
+
+impl InputEncoder {
+  pub fn name(&self) -> &'static str {
+    match self {
+      InputEncoder::Board768 => "v1-board-768",
+      InputEncoder::SideToMove => "v2-side-to-move-769",
+      InputEncoder::Castling => "v3-castling-773",
+      InputEncoder::Mirrored => "v4-mirrored-768",
+      InputEncoder::KingBuckets => "v5-king-buckets-5120"
+    }
+  }
+
+  pub fn from_name(name: &str) -> Option<InputEncoder> {
+    ENCODERS.iter().copied().find(|encoder| encoder.name() == name)
+  }
+
+  pub fn input_size(&self) -> usize {
+    match self {
+      InputEncoder::Board768 | InputEncoder::Mirrored => 768,
+      InputEncoder::SideToMove => 769,
+      InputEncoder::Castling => 773,
+      InputEncoder::KingBuckets => 2 * KING_BUCKET_HALF
+    }
+  }
+
+  // whether the network's output is the side to move's score rather than white's
+  pub fn relative_to_mover(&self) -> bool {
+    matches!(self, InputEncoder::Mirrored | InputEncoder::KingBuckets)
+  }
+
+  // indices of the inputs that are 1.0; every other input is 0.0
+  pub fn active_features(&self, position: &Position) -> Vec<usize> {
+    let mut features: Vec<usize> = Vec::new();
+    match self {
+      InputEncoder::Board768 | InputEncoder::SideToMove | InputEncoder::Castling => {
+        for slice_index in 0..12 {
+          features.extend(piece_squares(position, slice_index).map(|square| slice_index * 64 + square));
+        }
+        if *self != InputEncoder::Board768 && white_to_move(position) {
+          features.push(768);
+        }
+        if *self == InputEncoder::Castling {
+          let rights: [bool; 4] = [
+            castling_kept(position, WKING, 4, WROOK, 7),
+            castling_kept(position, WKING, 4, WROOK, 0),
+            castling_kept(position, BKING, 60, BROOK, 63),
+            castling_kept(position, BKING, 60, BROOK, 56)
+          ];
+          features.extend((0..4).filter(|right| rights[*right]).map(|right| 769 + right));
+        }
+      },
+      InputEncoder::Mirrored => {
+        features = self.perspective_features(position, mover(position));
+      },
+      InputEncoder::KingBuckets => {
+        features = self.perspective_features(position, mover(position));
+        features.extend(self.perspective_features(position, mover(position) ^ 1).into_iter().map(|feature| feature + KING_BUCKET_HALF));
+      }
+    }
+    features
+  }
+
+  // blocks of inputs the encoding is made of, each seen from one side. mover-relative
+  // encoders start with the mover's block; the others are a single block from white's side
+  pub fn perspectives(&self) -> usize {
+    if *self == InputEncoder::KingBuckets { 2 } else { 1 }
+  }
+
+  pub fn perspective_size(&self) -> usize {
+    if *self == InputEncoder::KingBuckets { KING_BUCKET_HALF } else { self.input_size() }
+  }
+
+  // active inputs of one block from `side`'s view, 0 for white and 1 for black. for the
+  // mover-relative encoders they don't depend on who is to move, which is what lets an
+  // accumulator per side be updated move by move
+  pub fn perspective_features(&self, position: &Position, side: usize) -> Vec<usize> {
+    let flip: usize = if side == 0 { 0 } else { 56 };
+    let mut features: Vec<usize> = Vec::new();
+    match self {
+      InputEncoder::Mirrored => {
+        for slice_index in 0..12 {
+          let relative_slice: usize = ((slice_index / 6) ^ side) * 6 + slice_index % 6;
+          features.extend(piece_squares(position, slice_index).map(|square| relative_slice * 64 + (square ^ flip)));
+        }
+      },
+      InputEncoder::KingBuckets => {
+        let king: u64 = position.state[side * 6 + WKING as usize];
+        if king == 0 {
+          return features;
+        }
+        let bucket: usize = king_bucket(king.trailing_zeros() as usize ^ flip);
+        for slice_index in 0..12 {
+          let kind: usize = slice_index % 6;
+          if kind == WKING as usize {
+            continue;
+          }
+          let piece: usize = ((slice_index / 6) ^ side) * 5 + kind;
+          let offset: usize = (bucket * BUCKET_PIECES + piece) * 64;
+          features.extend(piece_squares(position, slice_index).map(|square| offset + (square ^ flip)));
+        }
+      },
+      _ => features = self.active_features(position)
+    }
+    features
+  }
+
+  pub fn encode(&self, position: &Position) -> Vec<f64> {
+    let mut input: Vec<f64> = vec![0f64; self.input_size()];
+    for feature in self.active_features(position) {
+      input[feature] = 1f64;
+    }
+    input
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::evaluator::make_position;
+  use crate::fen::state_from_fen;
+  use crate::network::eval::convert_positions_to_input_layer;
+
+  fn position(fen: &str) -> Position {
+    let (state, turn_number) = state_from_fen(fen).unwrap();
+    make_position(state, turn_number)
+  }
+
+  #[test]
+  fn names_round_trip() {
+    for encoder in ENCODERS.iter() {
+      assert!(InputEncoder::from_name(encoder.name()) == Some(*encoder));
+    }
+    assert!(InputEncoder::from_name("v9-unknown").is_none());
+  }
+
+  #[test]
+  fn board_768_matches_the_original_encoding() {
+    let start: Position = position("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b - -");
+    assert!(InputEncoder::Board768.encode(&start) == convert_positions_to_input_layer(start.state));
+  }
+
+  #[test]
+  fn features_stay_inside_the_input_layer() {
+    let middlegame: Position = position("r1bq1rk1/pp3ppp/2n1p3/3n4/1b1P4/2NB1N2/PP3PPP/R1BQ1RK1 b - -");
+    for encoder in ENCODERS.iter() {
+      assert!(encoder.active_features(&middlegame).iter().all(|feature| *feature < encoder.input_size()));
+    }
+  }
+
+  #[test]
+  fn side_to_move_changes_the_inputs() {
+    let white: Position = position("4k3/8/8/8/8/8/4P3/4K3 w - -");
+    let black: Position = position("4k3/8/8/8/8/8/4P3/4K3 b - -");
+    assert!(InputEncoder::Board768.encode(&white) == InputEncoder::Board768.encode(&black));
+    for encoder in [InputEncoder::SideToMove, InputEncoder::Castling, InputEncoder::Mirrored, InputEncoder::KingBuckets] {
+      assert!(encoder.encode(&white) != encoder.encode(&black), "{}", encoder.name());
+    }
+  }
+
+  #[test]
+  fn castling_inputs_follow_king_and_rooks() {
+    let all: Vec<usize> = InputEncoder::Castling.active_features(&position("r3k2r/8/8/8/8/8/8/R3K2R w - -"));
+    assert!((769..773).all(|feature| all.contains(&feature)));
+    let moved: Vec<usize> = InputEncoder::Castling.active_features(&position("r3k2r/8/8/8/8/8/8/R4RK1 w - -"));
+    assert!(!moved.contains(&769) && !moved.contains(&770));
+    assert!(moved.contains(&771) && moved.contains(&772));
+  }
+
+  #[test]
+  fn mover_relative_encoders_ignore_colour_flips() {
+    // the same position with colours swapped and the board flipped, the other side to move
+    let original: Position = position("4k3/2n5/8/8/8/8/3PP3/4K2R w - -");
+    let flipped: Position = position("4k2r/3pp3/8/8/8/8/2N5/4K3 b - -");
+    for encoder in [InputEncoder::Mirrored, InputEncoder::KingBuckets] {
+      let mut first: Vec<usize> = encoder.active_features(&original);
+      let mut second: Vec<usize> = encoder.active_features(&flipped);
+      first.sort();
+      second.sort();
+      assert!(first == second, "{}", encoder.name());
+    }
+  }
+
+  #[test]
+  fn king_buckets_split_by_king_placement() {
+    let short: Vec<usize> = InputEncoder::KingBuckets.active_features(&position("6k1/8/8/8/8/8/P7/6K1 w - -"));
+    let long: Vec<usize> = InputEncoder::KingBuckets.active_features(&position("6k1/8/8/8/8/8/P7/2K5 w - -"));
+    assert!(short != long);
+    assert!(short.len() == 2 && long.len() == 2);
+  }
+}
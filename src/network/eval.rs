@@ -1,9 +1,13 @@
-use crate::network::net::{Net, tanh, tanh_der_clipped};
+use crate::network::net::Net;
+use super::activation::Activation;
 use std::sync::Mutex;
 use crate::rand::Rng;
+use crate::evaluator::{Evaluator, Position, Score};
+use super::encoding::InputEncoder;
+use super::policy::child_indices;
 
 lazy_static! {
-  pub static ref NET: Mutex<Net> = Mutex::new(Net::create_random(vec![1f64; 768], 80, 20, tanh, tanh_der_clipped, 0.02));
+  pub static ref NET: Mutex<Net> = Mutex::new(Net::create_random(vec![1f64; 768], 80, 20, Activation::TanhClipped, 0.02));
 }
 
 pub fn run_random_inputs_through_net(number: usize) {
@@ -47,8 +51,39 @@ pub fn convert_positions_to_input_layer(position: [u64; 13]) -> Vec<f64> {
   input
 }
 
-pub fn evaluate_position(_position: [u64; 13]) -> f32 {
-  0.0
+// a network that owns its buffers, so evaluating reuses them instead of cloning the net
+pub struct NetEvaluator {
+  net: Net
+}
+
+pub fn make_net_evaluator(net: Net) -> NetEvaluator {
+  NetEvaluator {
+    net
+  }
+}
+
+// flips between white's side and the mover's side for networks that score for the mover.
+// used on training targets going in and on network outputs coming out
+pub fn training_target(encoder: InputEncoder, position: &Position, white_score: f64) -> f64 {
+  if encoder.relative_to_mover() && position.turn_number % 2 != 1 { -white_score } else { white_score }
+}
+
+impl Evaluator for NetEvaluator {
+  fn evaluate(&mut self, position: &Position) -> Score {
+    assert!(self.net.input.len() == self.net.encoder.input_size(), "Network input layer doesn't fit its encoder. eval.rs, evaluate");
+    let value: f64 = self.net.forward_prop_to_value(self.net.encoder.encode(position));
+    // the flip is its own inverse
+    training_target(self.net.encoder, position, value)
+  }
+
+  // the policy head's probabilities; a child no single move reaches gets none
+  fn move_priors(&mut self, parent: &Position, children: &[[u64; 13]]) -> Option<Vec<f64>> {
+    self.net.policy.as_ref()?;
+    let indices: Vec<Option<usize>> = child_indices(self.net.encoder, parent, children);
+    let moves: Vec<usize> = indices.iter().flatten().copied().collect();
+    let mut probabilities = self.net.move_probabilities(self.net.encoder.encode(parent), &moves).into_iter();
+    Some(indices.iter().map(|index| if index.is_some() { probabilities.next().unwrap() } else { 0.0 }).collect())
+  }
 }
 
 #[cfg(test)]
@@ -75,4 +110,22 @@ mod tests {
     // tested manually, seems to work
     println!("{:?}", NET.lock().unwrap().get_final_value());
   }
+
+  // cargo test --release forward_passes_per_second -- --ignored --nocapture
+  #[test]
+  #[ignore]
+  fn forward_passes_per_second() {
+    let mut net = Net::random_with_widths(vec![0f64; 768], &[256, 32], Activation::ClippedRelu, 0.01);
+    let inputs: Vec<Vec<f64>> = generate_random_inputs(64).into_iter().map(convert_positions_to_input_layer).collect();
+    let start = std::time::Instant::now();
+    let mut passes: usize = 0;
+    let mut total: f64 = 0.0;
+    while start.elapsed().as_secs_f64() < 2.0 {
+      for input in inputs.iter() {
+        total += net.forward_prop_to_value(input.clone());
+      }
+      passes += inputs.len();
+    }
+    println!("{:.0} forward passes per second ({})", passes as f64 / start.elapsed().as_secs_f64(), total);
+  }
 }
\ No newline at end of file
@@ -0,0 +1,96 @@
+// the vector kernels behind the network's layers. each one works on LANES independent
+// partial results, which the compiler turns into simd instructions on its own, and handles
+// whatever doesn't fill a last chunk one value at a time
+const LANES: usize = 8;
+
+pub fn dot(first: &[f32], second: &[f32]) -> f32 {
+  debug_assert!(first.len() == second.len());
+  let mut lanes: [f32; LANES] = [0f32; LANES];
+  let first_chunks = first.chunks_exact(LANES);
+  let second_chunks = second.chunks_exact(LANES);
+  let mut sum: f32 = first_chunks.remainder().iter().zip(second_chunks.remainder().iter()).map(|(a, b)| a * b).sum();
+  for (a, b) in first_chunks.zip(second_chunks) {
+    for lane in 0..LANES {
+      lanes[lane] += a[lane] * b[lane];
+    }
+  }
+  sum += lanes.iter().sum::<f32>();
+  sum
+}
+
+// target += scale * source
+pub fn add_scaled(target: &mut [f32], source: &[f32], scale: f32) {
+  debug_assert!(target.len() == source.len());
+  let mut target_chunks = target.chunks_exact_mut(LANES);
+  let mut source_chunks = source.chunks_exact(LANES);
+  for (a, b) in (&mut target_chunks).zip(&mut source_chunks) {
+    for lane in 0..LANES {
+      a[lane] += scale * b[lane];
+    }
+  }
+  for (a, b) in target_chunks.into_remainder().iter_mut().zip(source_chunks.remainder().iter()) {
+    *a += scale * b;
+  }
+}
+
+// output = biases + matrix * input, with `matrix` row-major and one row per output
+pub fn multiply_add(matrix: &[f32], input: &[f32], biases: &[f32], output: &mut [f32]) {
+  debug_assert!(matrix.len() == input.len() * output.len());
+  if input.is_empty() {
+    output.copy_from_slice(biases);
+    return;
+  }
+  for ((value, row), bias) in output.iter_mut().zip(matrix.chunks_exact(input.len())).zip(biases.iter()) {
+    *value = bias + dot(row, input);
+  }
+}
+
+// output = transpose(matrix) * input, how errors flow back through a layer
+pub fn transpose_multiply(matrix: &[f32], input: &[f32], output: &mut [f32]) {
+  debug_assert!(matrix.len() == input.len() * output.len());
+  output.iter_mut().for_each(|value| *value = 0f32);
+  if output.is_empty() {
+    return;
+  }
+  for (row, scale) in matrix.chunks_exact(output.len()).zip(input.iter()) {
+    add_scaled(output, row, *scale);
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  fn numbers(count: usize, offset: f32) -> Vec<f32> {
+    (0..count).map(|index| ((index as f32 + offset) * 0.37).sin()).collect()
+  }
+
+  #[test]
+  fn kernels_match_plain_loops() {
+    // lengths either side of whole chunks
+    for length in [0, 1, 7, 8, 9, 23, 64] {
+      let first: Vec<f32> = numbers(length, 0.0);
+      let second: Vec<f32> = numbers(length, 5.0);
+      let expected: f32 = first.iter().zip(second.iter()).map(|(a, b)| a * b).sum();
+      assert!((dot(&first, &second) - expected).abs() < 1e-5, "{}", length);
+
+      let mut target: Vec<f32> = second.clone();
+      add_scaled(&mut target, &first, 0.5);
+      for index in 0..length {
+        assert!((target[index] - (second[index] + 0.5 * first[index])).abs() < 1e-6);
+      }
+    }
+  }
+
+  #[test]
+  fn multiplies_row_major_matrices() {
+    // 2 outputs by 3 inputs
+    let matrix: Vec<f32> = vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0];
+    let mut output: Vec<f32> = vec![0f32; 2];
+    multiply_add(&matrix, &[1.0, 0.0, -1.0], &[0.5, -0.5], &mut output);
+    assert!(output == vec![-1.5, -2.5]);
+    let mut back: Vec<f32> = vec![0f32; 3];
+    transpose_multiply(&matrix, &[1.0, -1.0], &mut back);
+    assert!(back == vec![-3.0, -3.0, -3.0]);
+  }
+}
@@ -1,9 +1,13 @@
-mod eval;
-mod node;
-mod layer;
-mod network;
-mod nn;
-mod forward_prop;
-mod net;
-mod network_storage;
-pub mod train;
\ No newline at end of file
+pub mod eval;
+pub mod activation;
+pub mod encoding;
+pub mod nnue;
+pub mod matrix;
+pub mod net;
+pub mod policy;
+pub mod optimizer;
+pub mod network_storage;
+pub mod binary_storage;
+pub mod dataset;
+pub mod train;
+pub mod td;
\ No newline at end of file
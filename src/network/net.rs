@@ -1,91 +1,211 @@
 use crate::rand::Rng;
-use std::f64::consts::E;
-type LayerNodeMatrix = Vec<Vec<f64>>;
+use super::activation::Activation;
+use super::encoding::InputEncoder;
+use super::matrix::{add_scaled, dot, multiply_add, transpose_multiply};
+use super::optimizer::Optimizer;
 
+// a fully connected layer and the buffers its forward and backward passes fill
 #[derive(Clone, PartialEq, Debug)]
-pub struct Net {
-  pub values: LayerNodeMatrix,
-  pub biases: LayerNodeMatrix,
-  pub weights: Vec<Vec<Vec<f64>>>,
-  pub act_fn: fn(f64) -> f64,
-  pub der_fn: fn(f64) -> f64,
-  pub error_signals: LayerNodeMatrix,
-  pub learning_rate: f64
+pub struct Layer {
+  inputs: usize,
+  // row-major, one row of `inputs` weights per output
+  pub weights: Vec<f32>,
+  pub biases: Vec<f32>,
+  pub activation: Activation,
+  // each node's weighted sum before the activation, which is what derivatives are taken at
+  pub sums: Vec<f32>,
+  pub values: Vec<f32>,
+  // the negative derivative of the loss with respect to each sum
+  pub error_signals: Vec<f32>,
+  // loss gradients summed over the samples of the current mini-batch, laid out like the
+  // weights and biases
+  pub weight_gradients: Vec<f32>,
+  pub bias_gradients: Vec<f32>
 }
 
-impl Net {
-  pub fn run_data(&mut self, input: Vec<f64>, target: f64) {
-    self.forward_prop(input);
-    self.backward_prop(target);
+pub fn make_layer(inputs: usize, outputs: usize, activation: Activation) -> Layer {
+  Layer {
+    inputs,
+    weights: vec![0f32; inputs * outputs],
+    biases: vec![0f32; outputs],
+    activation,
+    sums: vec![0f32; outputs],
+    values: vec![0f32; outputs],
+    error_signals: vec![0f32; outputs],
+    weight_gradients: vec![0f32; inputs * outputs],
+    bias_gradients: vec![0f32; outputs]
   }
+}
 
-  pub fn forward_backward_forward_get_value(&mut self, input: Vec<f64>, target: f64) -> f64 {
-    self.forward_prop(input.clone());
-    self.backward_prop(target);
-    self.forward_prop(input);
-    self.get_final_value()
+impl Layer {
+  pub fn inputs(&self) -> usize {
+    self.inputs
   }
 
-  pub fn forward_prop(&mut self, input: Vec<f64>) {
-    self.values[0] = input;
-    self.set_network_values();
+  pub fn outputs(&self) -> usize {
+    self.biases.len()
   }
 
-  pub fn get_forward_prop_error(&mut self, input: Vec<f64>, target: f64) -> f64 {
-    self.values[0] = input;
-    self.set_network_values();
-    self.get_error(target)
+  #[inline]
+  pub fn weight(&self, input_index: usize, output_index: usize) -> f32 {
+    self.weights[output_index * self.inputs + input_index]
   }
 
-  pub fn backward_prop(&mut self, target: f64) {
-    self.set_network_error_signals(target);
-    self.adjust_weights_after_error_signals_set();
+  #[inline]
+  pub fn weight_mut(&mut self, input_index: usize, output_index: usize) -> &mut f32 {
+    &mut self.weights[output_index * self.inputs + input_index]
   }
 
-  pub fn create(input_values: Vec<f64>, layers: usize, nodes: usize, activation_fn: fn(f64) -> f64, derivative: fn(f64) -> f64, l_r: f64) -> Net {
-    let number_of_nodes_in_input_layer = input_values.len();
-    let mut values: LayerNodeMatrix = LayerNodeMatrix::new();
-    values.append(&mut vec![input_values]);
-    values.append(&mut vec![vec![0f64; nodes]; layers]);
-    values.append(&mut vec![vec![0f64; 1]]);
+  pub fn forward(&mut self, input: &[f32]) {
+    multiply_add(&self.weights, input, &self.biases, &mut self.sums);
+    for (value, sum) in self.values.iter_mut().zip(self.sums.iter()) {
+      *value = self.activation.apply(*sum as f64) as f32;
+    }
+  }
+
+  // `value_errors` is how much the loss falls as each of this layer's values rises
+  fn set_error_signals(&mut self, value_errors: &[f32]) {
+    for ((signal, sum), value_error) in self.error_signals.iter_mut().zip(self.sums.iter()).zip(value_errors.iter()) {
+      *signal = self.activation.derivative(*sum as f64) as f32 * value_error;
+    }
+  }
+
+  // the value errors of the layer feeding this one, once its error signals are set
+  fn input_errors(&self, errors: &mut Vec<f32>) {
+    errors.resize(self.inputs, 0f32);
+    transpose_multiply(&self.weights, &self.error_signals, errors);
+  }
+
+  // derivative of the loss with respect to a weight, given the input the layer last saw
+  #[inline]
+  fn weight_gradient(&self, input: &[f32], input_index: usize, output_index: usize) -> f32 {
+    -self.error_signals[output_index] * input[input_index]
+  }
+
+  // moves every weight and bias `rate` times its error signal, which for the learning rate
+  // is a step of gradient descent
+  fn descend(&mut self, input: &[f32], rate: f32) {
+    if self.inputs > 0 {
+      for (row, signal) in self.weights.chunks_exact_mut(self.inputs).zip(self.error_signals.iter()) {
+        add_scaled(row, input, rate * signal);
+      }
+    }
+    add_scaled(&mut self.biases, &self.error_signals, rate);
+  }
+
+  fn accumulate(&mut self, input: &[f32]) {
+    if self.inputs > 0 {
+      for (row, signal) in self.weight_gradients.chunks_exact_mut(self.inputs).zip(self.error_signals.iter()) {
+        add_scaled(row, input, -signal);
+      }
+    }
+    add_scaled(&mut self.bias_gradients, &self.error_signals, -1f32);
+  }
+}
 
-    let mut biases: LayerNodeMatrix = LayerNodeMatrix::new();
-    biases.append(&mut vec![vec![0f64; number_of_nodes_in_input_layer]]);
-    biases.append(&mut vec![vec![0f64; nodes]; layers]);
-    biases.append(&mut vec![vec![0f64; 1]]);
+#[derive(Clone, PartialEq, Debug)]
+pub struct Net {
+  pub input: Vec<f32>,
+  // every layer after the input, the last being the single output node
+  pub layers: Vec<Layer>,
+  pub learning_rate: f64,
+  pub accumulated_samples: usize,
+  // how positions become the input layer
+  pub encoder: InputEncoder,
+  // an optional second head beside the output node: one logit per move of the policy's
+  // move space, read from the same layer the output node is
+  pub policy: Option<Layer>,
+  // reused between backward passes
+  value_errors: Vec<f32>,
+  next_value_errors: Vec<f32>,
+  // what the policy head passes back to the layer it reads
+  policy_errors: Vec<f32>
+}
 
-    let mut weights: Vec<Vec<Vec<f64>>> = Vec::new();
-    weights.append(&mut vec![vec![vec![0f64; nodes]; number_of_nodes_in_input_layer]]);
-    weights.append(&mut vec![vec![vec![0f64; nodes]; nodes]; layers - 1]);
-    weights.append(&mut vec![vec![vec![0f64; 1]; nodes]]);
+// probabilities in proportion to e to each logit
+fn softmax(logits: &[f64]) -> Vec<f64> {
+  let largest: f64 = logits.iter().fold(f64::NEG_INFINITY, |largest, logit| largest.max(*logit));
+  let exponentials: Vec<f64> = logits.iter().map(|logit| (logit - largest).exp()).collect();
+  let total: f64 = exponentials.iter().sum();
+  exponentials.iter().map(|exponential| exponential / total).collect()
+}
 
-    let mut error_signals: LayerNodeMatrix = LayerNodeMatrix::new();
-    error_signals.append(&mut vec![vec![0f64; number_of_nodes_in_input_layer]]);
-    error_signals.append(&mut vec![vec![0f64; nodes]; layers]);
-    error_signals.append(&mut vec![vec![0f64; 1]]);
+impl Net {
+  // every hidden layer `nodes` wide; see `with_widths` for anything else
+  pub fn create(input_values: Vec<f64>, layers: usize, nodes: usize, activation: Activation, l_r: f64) -> Net {
+    Net::with_widths(input_values, &vec![nodes; layers], activation, l_r)
+  }
 
+  // one hidden layer per entry of `hidden_widths`, then the output node. every layer gets
+  // `activation`; set a layer's afterwards to mix them
+  pub fn with_widths(input_values: Vec<f64>, hidden_widths: &[usize], activation: Activation, l_r: f64) -> Net {
+    let mut widths: Vec<usize> = vec![input_values.len()];
+    widths.extend_from_slice(hidden_widths);
+    widths.push(1);
     Net {
-      values: values,
-      biases: biases,
-      weights: weights,
-      act_fn: activation_fn,
-      der_fn: derivative,
-      error_signals: error_signals,
-      learning_rate: l_r
+      input: input_values.iter().map(|value| *value as f32).collect(),
+      layers: widths.windows(2).map(|pair| make_layer(pair[0], pair[1], activation)).collect(),
+      learning_rate: l_r,
+      accumulated_samples: 0,
+      encoder: InputEncoder::Board768,
+      policy: None,
+      value_errors: Vec::new(),
+      next_value_errors: Vec::new(),
+      policy_errors: Vec::new()
     }
   }
 
-  pub fn create_random(input_values: Vec<f64>, layers: usize, nodes: usize, activation_fn: fn(f64) -> f64, derivative: fn(f64) -> f64, l_r: f64) -> Net {
-    let net = &mut Net::create(input_values, layers, nodes, activation_fn, derivative, l_r);
+  pub fn create_random(input_values: Vec<f64>, layers: usize, nodes: usize, activation: Activation, l_r: f64) -> Net {
+    Net::random_with_widths(input_values, &vec![nodes; layers], activation, l_r)
+  }
+
+  pub fn random_with_widths(input_values: Vec<f64>, hidden_widths: &[usize], activation: Activation, l_r: f64) -> Net {
+    let mut net = Net::with_widths(input_values, hidden_widths, activation, l_r);
     let mut rng = rand::thread_rng();
-    for layer in net.weights.iter_mut() {
-      for node in layer.iter_mut() {
-        for weight in node {
-          *weight = rng.gen_range(-0.5..0.5);
-        }
-      }
+    for weight in net.layers.iter_mut().flat_map(|layer| layer.weights.iter_mut()) {
+      *weight = rng.gen_range(-0.5..0.5);
     }
-    net.clone()
+    net
+  }
+
+  // input layer first and output last
+  pub fn widths(&self) -> Vec<usize> {
+    let mut widths: Vec<usize> = vec![self.input.len()];
+    widths.extend(self.layers.iter().map(|layer| layer.outputs()));
+    widths
+  }
+
+  pub fn activations(&self) -> Vec<Activation> {
+    self.layers.iter().map(|layer| layer.activation).collect()
+  }
+
+  // the values layer `layer_index` was fed on the last forward pass
+  fn layer_input(&self, layer_index: usize) -> &[f32] {
+    if layer_index == 0 { &self.input } else { &self.layers[layer_index - 1].values }
+  }
+
+  fn layer_and_input(&mut self, layer_index: usize) -> (&mut Layer, &[f32]) {
+    let (before, after) = self.layers.split_at_mut(layer_index);
+    let input: &[f32] = if layer_index == 0 { &self.input } else { &before[layer_index - 1].values };
+    (&mut after[0], input)
+  }
+
+  pub fn run_data(&mut self, input: Vec<f64>, target: f64) {
+    self.forward_prop(input);
+    self.backward_prop(target);
+  }
+
+  pub fn forward_backward_forward_get_value(&mut self, input: Vec<f64>, target: f64) -> f64 {
+    self.forward_prop(input.clone());
+    self.backward_prop(target);
+    self.forward_prop(input);
+    self.get_final_value()
+  }
+
+  pub fn forward_prop(&mut self, input: Vec<f64>) {
+    self.input.clear();
+    self.input.extend(input.iter().map(|value| *value as f32));
+    self.set_network_values();
   }
 
   pub fn forward_prop_to_value(&mut self, input_values: Vec<f64>) -> f64 {
@@ -93,325 +213,453 @@ impl Net {
     self.get_final_value()
   }
 
+  pub fn get_forward_prop_error(&mut self, input: Vec<f64>, target: f64) -> f64 {
+    self.forward_prop(input);
+    self.get_error(target)
+  }
+
   #[inline]
   fn get_error(&self, target: f64) -> f64 {
     target - self.get_final_value()
   }
 
+  // half the squared error, the loss the weight and bias updates descend
+  pub fn get_loss(&mut self, input: Vec<f64>, target: f64) -> f64 {
+    let error = self.get_forward_prop_error(input, target);
+    0.5 * error * error
+  }
+
   #[inline]
   pub fn get_final_value(&self) -> f64 {
-    self.values[self.values.len() - 1][0]
+    self.layers[self.layers.len() - 1].values[0] as f64
   }
 
   pub fn set_network_values(&mut self) {
-    let network_length = self.values.len();
-    for layer_index in 1..network_length {
-      self.set_layer_values(layer_index);
+    for layer_index in 0..self.layers.len() {
+      let (layer, input) = self.layer_and_input(layer_index);
+      layer.forward(input);
     }
   }
 
-  fn set_layer_values(&mut self, layer_index: usize) {
-    let length_of_layer = self.values[layer_index].len();
-    let length_of_prev_layer = self.values[layer_index - 1].len();
-    for node_index in 0..length_of_layer {
-      self.values[layer_index][node_index] = self.biases[layer_index][node_index];
-      for previous_index in 0..length_of_prev_layer {
-        self.values[layer_index][node_index] += self.values[layer_index - 1][previous_index] * self.weights[layer_index - 1][previous_index][node_index];
-      }
-    }
-    self.values[layer_index].iter_mut()
-                            .for_each(|value| *value = (self.act_fn)(*value));
-  }
-
-  fn adjust_weights_after_error_signals_set(&mut self) {
-    let number_of_layers = self.values.len();
-    let mut number_of_nodes_in_current_layer;
-    let mut number_of_nodes_in_next_layer;
-    for layer_index in 0..(number_of_layers - 1) {
-      number_of_nodes_in_current_layer = self.values[layer_index].len();
-      number_of_nodes_in_next_layer = self.values[layer_index + 1].len();
-      for node_index in 0..number_of_nodes_in_current_layer {
-        for weight_index in 0..number_of_nodes_in_next_layer {
-          self.weights[layer_index][node_index][weight_index] += 
-            self.learning_rate * self.error_signals[layer_index + 1][weight_index] * self.values[layer_index][node_index];
+  fn set_network_error_signals(&mut self, target: f64) {
+    self.propagate_errors(self.get_error(target) as f32);
+  }
+
+  // sets every layer's error signals from the output node's value error and whatever the
+  // policy head left in `policy_errors`
+  fn propagate_errors(&mut self, output_error: f32) {
+    self.value_errors.clear();
+    self.value_errors.push(output_error);
+    let last: usize = self.layers.len() - 1;
+    for (layer_index, layer) in self.layers.iter_mut().enumerate().rev() {
+      layer.set_error_signals(&self.value_errors);
+      // nothing uses the input layer's errors
+      if layer_index > 0 {
+        layer.input_errors(&mut self.next_value_errors);
+        if layer_index == last && !self.policy_errors.is_empty() {
+          add_scaled(&mut self.next_value_errors, &self.policy_errors, 1f32);
         }
+        std::mem::swap(&mut self.value_errors, &mut self.next_value_errors);
       }
     }
   }
 
-  fn set_network_error_signals(&mut self, target: f64) {
-    self.set_final_layer_error_signal(target);
-    let final_index = self.values.len() - 1;
-    (1..final_index).rev()
-                    .for_each(|layer_index| self.set_layer_error_signals_not_last(layer_index));
+  // a policy head of `moves` logits. its weights start at zero, so every move starts out
+  // equally likely
+  pub fn add_policy_head(&mut self, moves: usize) {
+    let features: usize = self.layer_input(self.layers.len() - 1).len();
+    self.policy = Some(make_layer(features, moves, Activation::Identity));
   }
 
-  fn set_final_layer_error_signal(&mut self, target: f64) {
-    let last_layer_index = self.values.len() - 1;
-    let change_in_error_from_output = self.get_error(target);
-    let change_in_output_from_net = (self.der_fn)(self.get_final_value());
-    self.error_signals[last_layer_index][0] = change_in_error_from_output * change_in_output_from_net;
+  // the logit of each of `moves`, indices into the policy's move space, after a forward pass
+  fn policy_logits(&self, moves: &[usize]) -> Vec<f64> {
+    let policy: &Layer = self.policy.as_ref().expect("Network has no policy head. net.rs, policy_logits");
+    let features: &[f32] = self.layer_input(self.layers.len() - 1);
+    moves.iter()
+         .map(|index| (policy.biases[*index] + dot(&policy.weights[index * policy.inputs..(index + 1) * policy.inputs], features)) as f64)
+         .collect()
   }
 
-  fn get_final_layer_error_signal_after_calculated(&self) -> f64 {
-    self.error_signals[self.values.len() - 1][0]
+  // how likely the policy makes each of `moves`, normalised over those moves alone since
+  // they're the ones that can be played. only their rows of the head are computed
+  pub fn move_probabilities(&mut self, input: Vec<f64>, moves: &[usize]) -> Vec<f64> {
+    self.forward_prop(input);
+    softmax(&self.policy_logits(moves))
+  }
+
+  // cross-entropy of the policy over `moves` against the move at `played`, an index into `moves`
+  pub fn policy_loss(&mut self, input: Vec<f64>, moves: &[usize], played: usize) -> f64 {
+    -self.move_probabilities(input, moves)[played].ln()
+  }
+
+  // backpropagates one sample into the mini-batch's gradients like `accumulate_gradients`,
+  // adding the policy's cross-entropy against the move at `played`. without a `target` the
+  // output node is left out and only the policy trains the shared layers. returns the
+  // probabilities the policy gave `moves` before the step
+  pub fn accumulate_policy_gradients(&mut self, input: Vec<f64>, target: Option<f64>, moves: &[usize], played: usize) -> Vec<f64> {
+    let probabilities: Vec<f64> = self.move_probabilities(input, moves);
+    let features: Vec<f32> = self.layer_input(self.layers.len() - 1).to_vec();
+    let policy: &mut Layer = self.policy.as_mut().expect("Network has no policy head. net.rs, accumulate_policy_gradients");
+    let inputs: usize = policy.inputs;
+    self.policy_errors.clear();
+    self.policy_errors.resize(inputs, 0f32);
+    for (move_index, (index, probability)) in moves.iter().zip(probabilities.iter()).enumerate() {
+      // the negative derivative of the cross-entropy with respect to the move's logit
+      let signal: f32 = ((move_index == played) as u8 as f64 - probability) as f32;
+      add_scaled(&mut self.policy_errors, &policy.weights[index * inputs..(index + 1) * inputs], signal);
+      add_scaled(&mut policy.weight_gradients[index * inputs..(index + 1) * inputs], &features, -signal);
+      policy.bias_gradients[*index] -= signal;
+    }
+    let output_error: f32 = target.map(|target| self.get_error(target) as f32).unwrap_or(0f32);
+    self.propagate_errors(output_error);
+    self.policy_errors.clear();
+    for layer_index in 0..self.layers.len() {
+      let (layer, input) = self.layer_and_input(layer_index);
+      layer.accumulate(input);
+    }
+    self.accumulated_samples += 1;
+    probabilities
   }
 
-  fn set_layer_error_signals_not_last(&mut self, layer_index: usize) {
-    let curr_layer_length = self.values[layer_index].len();
-    let next_layer_length = self.values[layer_index + 1].len();
-    for node_index in 0..curr_layer_length {
-      self.error_signals[layer_index][node_index] = 0f64;
-      for weight_index in 0..next_layer_length {
-        self.error_signals[layer_index][node_index] += self.error_signals[layer_index + 1][weight_index] * self.weights[layer_index][node_index][weight_index];
-      }
+  // derivative of the loss with respect to a weight of layer `layer_index`, once the error
+  // signals are set
+  pub fn weight_gradient(&self, layer_index: usize, input_index: usize, output_index: usize) -> f64 {
+    self.layers[layer_index].weight_gradient(self.layer_input(layer_index), input_index, output_index) as f64
+  }
+
+  // derivative of the loss with respect to a bias, once the error signals are set
+  pub fn bias_gradient(&self, layer_index: usize, node_index: usize) -> f64 {
+    -self.layers[layer_index].error_signals[node_index] as f64
+  }
+
+  pub fn backward_prop(&mut self, target: f64) {
+    self.set_network_error_signals(target);
+    let learning_rate: f32 = self.learning_rate as f32;
+    for layer_index in 0..self.layers.len() {
+      let (layer, input) = self.layer_and_input(layer_index);
+      layer.descend(input, learning_rate);
     }
+  }
 
-    let derivative_of = self.der_fn;
-    for node_index in 0..curr_layer_length {
-      self.error_signals[layer_index][node_index] = (derivative_of)(self.values[layer_index][node_index]) * self.error_signals[layer_index][node_index];
+  // backpropagates one sample into the mini-batch's gradients without touching the weights
+  pub fn accumulate_gradients(&mut self, input: Vec<f64>, target: f64) {
+    self.forward_prop(input);
+    self.set_network_error_signals(target);
+    for layer_index in 0..self.layers.len() {
+      let (layer, input) = self.layer_and_input(layer_index);
+      layer.accumulate(input);
     }
+    self.accumulated_samples += 1;
   }
-}
 
-pub fn tanh(input: f64) -> f64 {
-  ((E.powf(input)) - E.powf(-input)) /
-  ((E.powf(input)) + E.powf(-input))
-}
+  // every weight, layer by layer in each layer's row-major order, then every bias layer by
+  // layer, then the policy head's weights and biases. the order `set_parameters` and the
+  // gradients handed to optimisers use
+  pub fn parameters(&self) -> Vec<f64> {
+    let mut parameters: Vec<f64> = self.layers.iter().flat_map(|layer| layer.weights.iter()).map(|weight| *weight as f64).collect();
+    parameters.extend(self.layers.iter().flat_map(|layer| layer.biases.iter()).map(|bias| *bias as f64));
+    parameters.extend(self.policy.iter().flat_map(|policy| policy.weights.iter().chain(policy.biases.iter())).map(|value| *value as f64));
+    parameters
+  }
 
-pub fn tanh_der(input: f64) -> f64 {
-  2f64 / (E.powf(input) + E.powf(-input))
-}
+  pub fn set_parameters(&mut self, parameters: &[f64]) {
+    let mut values = parameters.iter();
+    for weight in self.layers.iter_mut().flat_map(|layer| layer.weights.iter_mut()) {
+      *weight = *values.next().expect("Too few parameters for the network. net.rs, set_parameters") as f32;
+    }
+    for bias in self.layers.iter_mut().flat_map(|layer| layer.biases.iter_mut()) {
+      *bias = *values.next().expect("Too few parameters for the network. net.rs, set_parameters") as f32;
+    }
+    if let Some(policy) = self.policy.as_mut() {
+      for value in policy.weights.iter_mut().chain(policy.biases.iter_mut()) {
+        *value = *values.next().expect("Too few parameters for the network. net.rs, set_parameters") as f32;
+      }
+    }
+  }
 
-pub fn tanh_der_clipped(input: f64) -> f64 {
-  let val = tanh_der(input);
-  if val.abs() < 0.5f64 {
-    val
-  } else if val >= 0.5f64 {
-    0.5f64
-  } else {
-    -0.5f64
+  // the mini-batch's summed gradients, in `parameters` order
+  fn gradients(&self) -> Vec<f64> {
+    let mut gradients: Vec<f64> = self.layers.iter().flat_map(|layer| layer.weight_gradients.iter()).map(|gradient| *gradient as f64).collect();
+    gradients.extend(self.layers.iter().flat_map(|layer| layer.bias_gradients.iter()).map(|gradient| *gradient as f64));
+    gradients.extend(self.policy.iter().flat_map(|policy| policy.weight_gradients.iter().chain(policy.bias_gradients.iter())).map(|gradient| *gradient as f64));
+    gradients
+  }
+
+  // hands every weight and bias to the optimiser with the mini-batch's average gradient,
+  // then starts a new batch
+  pub fn apply_gradients(&mut self, optimizer: &mut dyn Optimizer, learning_rate: f64) {
+    if self.accumulated_samples == 0 {
+      return;
+    }
+    let mut parameters: Vec<f64> = self.parameters();
+    let samples = self.accumulated_samples as f64;
+    let gradients: Vec<f64> = self.gradients().iter().map(|gradient| gradient / samples).collect();
+    optimizer.step(&mut parameters, &gradients, learning_rate);
+    self.set_parameters(&parameters);
+    self.clear_gradients();
+  }
+
+  pub fn clear_gradients(&mut self) {
+    for layer in self.layers.iter_mut().chain(self.policy.iter_mut()) {
+      layer.weight_gradients.iter_mut().for_each(|gradient| *gradient = 0f32);
+      layer.bias_gradients.iter_mut().for_each(|gradient| *gradient = 0f32);
+    }
+    self.accumulated_samples = 0;
   }
 }
 
 #[cfg(test)]
 mod test {
   use super::*;
-  use std::sync::Mutex;
+  use super::super::optimizer::{make_optimizer, OptimizerKind};
+  use rand::rngs::StdRng;
+  use rand::SeedableRng;
 
-  fn leaky_ReLu(input: f64) -> f64 {
-    if input > 0f64 { input } else { input * 0.2f64 }
-  }
-
-  fn leaky_ReLu_der(input: f64) -> f64 {
-    if input > 0f64 { 1f64 } else { 0.2f64 }
+  lazy_static! {
+    static ref NN: Net = Net::create(vec![1f64; 2], 3, 6, Activation::LeakyRelu, 0.05);
   }
 
-  fn sigmoid(input: f64) -> f64 {
-    1f64 / (1f64 + f64::powf(std::f64::consts::E as f64, -input))
+  fn ones(activation: Activation) -> Net {
+    let mut net = Net::create(vec![1f64; 2], 3, 6, activation, 0.05);
+    for weight in net.layers.iter_mut().flat_map(|layer| layer.weights.iter_mut()) {
+      *weight = 1f32;
+    }
+    net
   }
 
-  fn sigmoid_der(input: f64) -> f64 {
-    input * (1f64 - input)
+  #[test]
+  fn creates_network_with_correct_dimensions() {
+    assert_eq!(NN.widths(), vec![2, 6, 6, 6, 1]);
+    assert_eq!(NN.layers.len(), 4);
+    for (layer, pair) in NN.layers.iter().zip(NN.widths().windows(2)) {
+      assert_eq!((layer.inputs(), layer.outputs()), (pair[0], pair[1]));
+      assert_eq!(layer.weights.len(), pair[0] * pair[1]);
+    }
   }
 
-  fn identity(input: f64) -> f64 {
-    input
+  #[test]
+  fn initializes_network_with_values() {
+    assert_eq!(NN.input, vec![1f32; 2]);
   }
 
-  fn identity_der(_input: f64) -> f64 {
-    1f64
+  #[test]
+  fn layers_can_differ_in_width() {
+    let mut net = Net::with_widths(vec![1f64; 3], &[5, 2], Activation::Identity, 0.05);
+    assert_eq!(net.widths(), vec![3, 5, 2, 1]);
+    for weight in net.layers.iter_mut().flat_map(|layer| layer.weights.iter_mut()) {
+      *weight = 1f32;
+    }
+    net.set_network_values();
+    assert_eq!(net.get_final_value(), 30f64);
   }
 
-  lazy_static! {
-    static ref NN: Net = Net::create(vec![1f64; 2], 3, 6, leaky_ReLu, leaky_ReLu_der, 0.05);
-    static ref NN_MUT: Mutex<Net> = Mutex::new(Net::create(vec![1f64; 2], 3, 6, leaky_ReLu, leaky_ReLu_der, 0.05));
-    // for NN_RAND, tanh, tanh_der, 0.02 learning rate, it seems like 
-    // 40 layers and 15 nodes per layer is the sweet spot
-    static ref NN_RAND: Mutex<Net> = Mutex::new(Net::create_random(vec![1f64; 2], 80, 20, tanh, tanh_der_clipped, 0.02));
+  #[test]
+  fn weights_are_row_major() {
+    let mut net = Net::with_widths(vec![1f64, 2f64], &[3], Activation::Identity, 0.05);
+    *net.layers[0].weight_mut(1, 2) = 0.5;
+    assert_eq!(net.layers[0].weights[5], 0.5);
+    assert_eq!(net.layers[0].weight(1, 2), 0.5);
+    net.set_network_values();
+    assert_eq!(net.layers[0].values, vec![0f32, 0f32, 1f32]);
   }
 
   #[test]
-  fn creates_network_with_correct_dimensions_for_values() {
-    assert_eq!(NN.values[0].len(), 2);
-    assert_eq!(NN.values[1].len(), 6);
-    assert_eq!(NN.values[4].len(), 1);
-    assert_eq!(NN.values.len(), 5);
+  fn sets_all_values() {
+    let mut net = ones(Activation::LeakyRelu);
+    net.set_network_values();
+    assert_eq!(net.get_final_value(), 432f64);
   }
 
   #[test]
-  fn initializes_network_with_values() {
-    assert_eq!(NN.values[0], vec![1f64; 2]);
+  fn bias_affects_values() {
+    let mut net = ones(Activation::LeakyRelu);
+    net.layers[0].biases[0] = 1f32;
+    net.set_network_values();
+    assert_eq!(net.layers[0].values[0], 3f32);
+    assert_eq!(net.get_final_value(), 468f64);
   }
 
   #[test]
-  fn creates_network_with_correct_dimensions_for_weights() {
-    assert_eq!(NN.weights[0].len(), 2);
-    assert_eq!(NN.weights[0][0].len(), NN.values[1].len());
-    assert_eq!(NN.weights[1][0].len(), NN.values[2].len());
-    assert_eq!(NN.weights[NN.weights.len() - 1][0].len(), NN.values[NN.values.len() - 1].len());
-    assert_eq!(NN.weights.len(), NN.values.len() - 1);
-    assert_eq!(NN.weights[NN.weights.len() - 1].len(), NN.values[NN.values.len() - 2].len());
+  fn gets_error_from_forward_prop() {
+    let mut net = ones(Activation::LeakyRelu);
+    net.layers[0].biases[0] = 1f32;
+    let input: Vec<f64> = vec![1f64; 2];
+    assert_eq!(net.get_forward_prop_error(input, 466f64), -2f64);
   }
 
   #[test]
-  fn sets_all_values() {
-    let new = &mut NN_MUT.lock().unwrap().clone();
-    for layer in new.weights.iter_mut() {
-      for node in layer.iter_mut() {
-        for weight in node.iter_mut() {
-          *weight = 1f64;
-        }
-      }
+  fn correctly_sets_error_signals() {
+    let mut net = ones(Activation::Identity);
+    net.layers[0].biases[0] = 1f32;
+    net.set_network_values();
+    net.set_network_error_signals(466f64);
+    assert_eq!(net.layers[3].error_signals, vec![-2f32]);
+    // every weight is 1, so each node passes on the sum of the signals after it
+    assert_eq!(net.layers[2].error_signals, vec![-2f32; 6]);
+    assert_eq!(net.layers[1].error_signals, vec![-12f32; 6]);
+    assert_eq!(net.layers[0].error_signals, vec![-72f32; 6]);
+  }
+
+  // weights and biases from a fixed seed, so the finite differences below can't land on a
+  // kink of the piecewise activations on some runs and not others
+  fn seeded_net(input: &[f64], hidden_widths: &[usize], activation: Activation, seed: u64) -> Net {
+    let mut net = Net::with_widths(input.to_vec(), hidden_widths, activation, 0.1);
+    let mut rng: StdRng = StdRng::seed_from_u64(seed);
+    for layer in net.layers.iter_mut() {
+      layer.weights.iter_mut().chain(layer.biases.iter_mut()).for_each(|parameter| *parameter = rng.gen_range(-0.5..0.5));
     }
-    new.set_network_values();
-    println!("{:?}", new.values);
-    println!("{:?}", new.weights);
-    let length_of_network = new.values.len();
-    assert_eq!(new.values[length_of_network - 1][0], 432f64);
+    net
   }
 
-  #[test]
-  fn bias_affects_values() {
-    let new = &mut NN_MUT.lock().unwrap().clone();
-    for layer in new.weights.iter_mut() {
-      for node in layer.iter_mut() {
-        for weight in node.iter_mut() {
-          *weight = 1f64;
-        }
-      }
-    }
-    new.biases[1][0] = 1f64;
-    new.set_network_values();
-    println!("{:?}", new.values);
-    println!("{:?}", new.weights);
-    let length_of_network = new.values.len();
-    assert_eq!(new.values[length_of_network - 1][0], 468f64);
+  // central difference of the loss as `nudge` moves one parameter up and down. the step is
+  // large enough for f32 to resolve the change in loss
+  fn numeric_gradient(net: &Net, input: &[f64], target: f64, nudge: impl Fn(&mut Net, f32)) -> f64 {
+    let epsilon: f32 = 1e-3;
+    let mut probe = net.clone();
+    nudge(&mut probe, epsilon);
+    let above: f64 = probe.get_loss(input.to_vec(), target);
+    nudge(&mut probe, -2f32 * epsilon);
+    let below: f64 = probe.get_loss(input.to_vec(), target);
+    (above - below) / (2f64 * epsilon as f64)
   }
 
-  #[test]
-  fn gets_final_value() {
-    let new = &mut NN_MUT.lock().unwrap().clone();
-    for layer in new.weights.iter_mut() {
-      for node in layer.iter_mut() {
-        for weight in node.iter_mut() {
-          *weight = 1f64;
-        }
-      }
-    }
-    new.biases[1][0] = 1f64;
-    new.set_network_values();
-    println!("{:?}", new.values);
-    println!("{:?}", new.weights);
-    assert_eq!(new.get_final_value(), 468f64);
+  fn assert_close(analytic: f64, numeric: f64, what: String) {
+    assert!((analytic - numeric).abs() <= 1e-3 * (1f64 + numeric.abs()), "{}: {} vs {}", what, analytic, numeric);
   }
 
-  #[test]
-  fn gets_error_from_forward_prop() {
-    let new = &mut NN_MUT.lock().unwrap().clone();
-    for layer in new.weights.iter_mut() {
-      for node in layer.iter_mut() {
-        for weight in node.iter_mut() {
-          *weight = 1f64;
+  // compares every backpropagated gradient with the numeric one
+  fn check_gradients(hidden_widths: &[usize], activations: &[Activation]) {
+    let input: Vec<f64> = vec![0.3, -0.7, 1.0];
+    let target: f64 = 0.25;
+    let mut net = seeded_net(&input, hidden_widths, activations[0], 7);
+    for (layer, activation) in net.layers.iter_mut().zip(activations.iter()) {
+      layer.activation = *activation;
+    }
+    net.forward_prop(input.clone());
+    net.set_network_error_signals(target);
+
+    for layer_index in 0..net.layers.len() {
+      for input_index in 0..net.layers[layer_index].inputs() {
+        for output_index in 0..net.layers[layer_index].outputs() {
+          let numeric: f64 = numeric_gradient(&net, &input, target, |probe, delta| *probe.layers[layer_index].weight_mut(input_index, output_index) += delta);
+          assert_close(net.weight_gradient(layer_index, input_index, output_index), numeric, format!("weight {} {} {}", layer_index, input_index, output_index));
         }
       }
+      for node_index in 0..net.layers[layer_index].outputs() {
+        let numeric: f64 = numeric_gradient(&net, &input, target, |probe, delta| probe.layers[layer_index].biases[node_index] += delta);
+        assert_close(net.bias_gradient(layer_index, node_index), numeric, format!("bias {} {}", layer_index, node_index));
+      }
     }
-    new.biases[1][0] = 1f64;
-    let target = 466f64;
-    let input = new.values[0].clone();
-    assert_eq!(new.get_forward_prop_error(input, target), -2f64);
   }
 
   #[test]
-  fn correctly_sets_final_error_signal() {
-    let new = &mut NN_MUT.lock().unwrap().clone();
-    for layer in new.weights.iter_mut() {
-      for node in layer.iter_mut() {
-        for weight in node.iter_mut() {
-          *weight = 1f64;
-        }
-      }
+  fn gradients_match_finite_differences() {
+    for activation in [Activation::Tanh, Activation::Sigmoid, Activation::LeakyRelu, Activation::Identity] {
+      check_gradients(&[4, 4], &[activation; 3]);
     }
-    new.act_fn = identity;
-    new.der_fn = identity_der;
-    new.biases[1][0] = 1f64;
-    let target = 466f64;
-    new.set_network_values();
-    new.set_final_layer_error_signal(target);
-    // tested with identity function returning 2f64 and gave error, so this is correct
-    assert_eq!(new.get_final_layer_error_signal_after_calculated(), -2f64);
+    // a different activation and width on every layer
+    check_gradients(&[5, 2], &[Activation::Relu, Activation::Sigmoid, Activation::Tanh]);
   }
 
   #[test]
-  fn correctly_sets_all_error_signals() {
-    let new = &mut NN_MUT.lock().unwrap().clone();
-    for layer in new.weights.iter_mut() {
-      for node in layer.iter_mut() {
-        for weight in node.iter_mut() {
-          *weight = 1f64;
+  fn policy_gradients_match_finite_differences() {
+    let input: Vec<f64> = vec![0.3, -0.7, 1.0];
+    let moves: Vec<usize> = vec![1, 4, 5];
+    let mut net = seeded_net(&input, &[4, 3], Activation::Tanh, 11);
+    net.add_policy_head(6);
+    let mut rng: StdRng = StdRng::seed_from_u64(12);
+    let policy: &mut Layer = net.policy.as_mut().unwrap();
+    policy.weights.iter_mut().chain(policy.biases.iter_mut()).for_each(|parameter| *parameter = rng.gen_range(-0.5..0.5));
+    net.accumulate_policy_gradients(input.clone(), None, &moves, 1);
+
+    let numeric = |nudge: &dyn Fn(&mut Net, f32)| {
+      let mut probe = net.clone();
+      nudge(&mut probe, 1e-3);
+      let above: f64 = probe.policy_loss(input.clone(), &moves, 1);
+      nudge(&mut probe, -2e-3);
+      let below: f64 = probe.policy_loss(input.clone(), &moves, 1);
+      (above - below) / 2e-3
+    };
+    for layer_index in 0..net.layers.len() {
+      for input_index in 0..net.layers[layer_index].inputs() {
+        for output_index in 0..net.layers[layer_index].outputs() {
+          let expected: f64 = numeric(&|probe, delta| *probe.layers[layer_index].weight_mut(input_index, output_index) += delta);
+          assert_close(net.layers[layer_index].weight_gradients[output_index * net.layers[layer_index].inputs() + input_index] as f64, expected, format!("weight {} {} {}", layer_index, input_index, output_index));
         }
       }
     }
-    new.act_fn = identity;
-    new.der_fn = identity_der;
-    new.biases[1][0] = 1f64;
-    let target = 466f64;
-    new.set_network_values();
-    new.set_network_error_signals(target);
-    println!("error_signals: {:?}\n\n", new.error_signals);
-    println!("weights: {:?}\n\n", new.weights);
-    println!("biases: {:?}\n\n", new.biases);
-    println!("values: {:?}\n\n", new.values);
-    // checked manually
-    assert_eq!(new.get_final_layer_error_signal_after_calculated(), -2f64);
-  }
-
-  fn print_weights(weights: Vec<Vec<Vec<f64>>>, message: &str) {
-    for layer in weights.iter() {
-      println!("{} {:?}\n\n", message, layer);
+    for (index, gradient) in net.policy.as_ref().unwrap().weight_gradients.iter().enumerate() {
+      let expected: f64 = numeric(&|probe, delta| probe.policy.as_mut().unwrap().weights[index] += delta);
+      assert_close(*gradient as f64, expected, format!("policy weight {}", index));
     }
+    // moves that can't be played get no gradient
+    assert!(net.policy.as_ref().unwrap().bias_gradients[0] == 0f32);
   }
 
-  fn print_final_value(final_value: f64, message: &str) {
-    println!("{} {:?}\n\n", message, final_value);
+  #[test]
+  fn policy_probabilities_cover_the_moves_given() {
+    let mut net = Net::with_widths(vec![1f64; 2], &[3], Activation::Identity, 0.1);
+    net.add_policy_head(10);
+    let probabilities: Vec<f64> = net.move_probabilities(vec![1f64; 2], &[2, 7, 9]);
+    assert!(probabilities.iter().all(|probability| (probability - 1.0 / 3.0).abs() < 1e-9));
+    assert!(net.parameters().len() == 2 * 3 + 3 + 3 + 1 + 10 * 3 + 10);
   }
 
-  fn print_error_signals(error_signals: Vec<Vec<f64>>, message: &str) {
-    println!("{} {:?}\n\n", message, error_signals);
+  #[test]
+  fn training_moves_the_biases() {
+    let mut net = Net::create(vec![1f64; 2], 1, 3, Activation::Identity, 0.1);
+    net.run_data(vec![1f64; 2], 1f64);
+    assert!(net.layers[0].biases.iter().all(|bias| *bias == 0f32));
+    assert!(net.layers[1].biases[0] > 0f32);
   }
 
-  fn absolute_difference(first: f64, second: f64) -> f64 {
-    (first - second).abs()
+  #[test]
+  fn batch_of_one_with_sgd_matches_run_data() {
+    let input: Vec<f64> = vec![0.3, -0.7, 1.0];
+    let mut net = seeded_net(&input, &[4, 3], Activation::Tanh, 3);
+    let mut batched = net.clone();
+    net.run_data(input.clone(), 0.5);
+    batched.accumulate_gradients(input, 0.5);
+    batched.apply_gradients(&mut *make_optimizer(OptimizerKind::Sgd, 0f64), 0.1);
+    for (first, second) in net.parameters().iter().zip(batched.parameters().iter()) {
+      assert!((first - second).abs() < 1e-6);
+    }
+    assert!(batched.accumulated_samples == 0);
   }
 
   #[test]
-  #[ignore]
-  fn correctly_adjusts_weights() {
-    let new = &mut NN_RAND.lock().unwrap().clone();
-    new.set_network_values();
-
-    print_final_value(new.get_final_value(), "Previous final value: ");
-
-    let mut msg: &str;
-    let mut msg_string: String;
-    let mut val: f64;
-    let data: Vec<f64> = new.values[0].clone();
-    for target_gradient in -3..4 {
-      val = (target_gradient as f64) * -0.25;
-      for _i in 0..200 {
-        new.run_data(data.clone(), val);
-      }
-      if new.get_final_value().is_nan() {
-        panic!("Network exploded! net.rs, fn correctly_adjusts_weights()")
+  fn parameters_round_trip() {
+    let net = seeded_net(&[0f64; 3], &[4, 2], Activation::Tanh, 5);
+    let mut copy = Net::with_widths(vec![0f64; 3], &[4, 2], Activation::Tanh, 0.1);
+    copy.set_parameters(&net.parameters());
+    assert!(copy == net);
+    assert_eq!(net.parameters().len(), 3 * 4 + 4 * 2 + 2 + 4 + 2 + 1);
+  }
+
+  #[test]
+  fn mini_batches_lower_the_loss() {
+    let samples: Vec<(Vec<f64>, f64)> = vec![(vec![1.0, 0.0], 0.5), (vec![0.0, 1.0], -0.5), (vec![1.0, 1.0], 0.1)];
+    let mut net = Net::create_random(vec![0f64; 2], 1, 6, Activation::Tanh, 0.1);
+    let total_loss = |net: &mut Net| -> f64 { samples.iter().map(|(input, target)| net.get_loss(input.clone(), *target)).sum() };
+    let before: f64 = total_loss(&mut net);
+    let mut optimizer = make_optimizer(OptimizerKind::Adam { beta1: 0.9, beta2: 0.999 }, 0f64);
+    for _epoch in 0..200 {
+      for (input, target) in samples.iter() {
+        net.accumulate_gradients(input.clone(), *target);
       }
-      if absolute_difference(new.get_final_value(), val) > 0.001 {
-        println!("Inaccurate value: {:?}", new.get_final_value());
-        println!("Desired value:    {:?}", val);
-        panic!("");
+      net.apply_gradients(&mut *optimizer, 0.01);
+    }
+    assert!(total_loss(&mut net) < before / 10f64);
+  }
+
+  #[test]
+  fn run_data_learns_a_constant() {
+    let mut net = Net::create_random(vec![1f64; 2], 2, 8, Activation::Tanh, 0.05);
+    for target in [-0.5, 0.25, 0.75] {
+      for _i in 0..300 {
+        net.run_data(vec![1f64; 2], target);
       }
-      msg_string = format!("{:?}: ", val);
-      msg = &msg_string;
-      print_final_value(new.get_final_value(), msg);
+      assert!((net.get_final_value() - target).abs() < 1e-3, "{} vs {}", net.get_final_value(), target);
     }
   }
 }
\ No newline at end of file
@@ -1,222 +1,412 @@
 use std::fs::{File, OpenOptions};
 use std::io::prelude::*;
 use std::path::Path;
-use super::net::{Net, tanh, tanh_der_clipped};
+use super::net::Net;
+use super::activation::Activation;
 use super::eval::NET;
+use super::encoding::InputEncoder;
 use std::io::{Error, SeekFrom};
-use std::collections::HashMap;
+use super::binary_storage::{binary_precision, is_binary_network, network_from_bytes, network_to_bytes, Precision};
 
-pub fn get_network_from_file(path: &str) -> Net {
-  match std::fs::read_to_string(path) {
-    Ok(string) => return get_network_from_string(string),
-    Err(_) => NET.lock().unwrap().clone()
+pub const DEFAULT_NETWORK_PATH: &str = "text_network_storage.txt";
+// environment variable naming the network file when no path is given on the command line
+pub const NETWORK_PATH_VARIABLE: &str = "EVAL_FILE";
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct NetworkOptions {
+  pub path: String,
+  // start from a fresh random network when the file doesn't exist, instead of failing
+  pub allow_random: bool
+}
+
+// the path comes from the command line, else the environment, else the default
+pub fn network_options(flag_path: Option<&str>, allow_random: bool) -> NetworkOptions {
+  let path: String = match flag_path {
+    Some(path) => String::from(path),
+    None => std::env::var(NETWORK_PATH_VARIABLE).unwrap_or(String::from(DEFAULT_NETWORK_PATH))
+  };
+  NetworkOptions {
+    path,
+    allow_random
+  }
+}
+
+// reads either format, telling them apart by the binary magic number
+pub fn load_network_file(path: &str) -> Result<Net, String> {
+  let bytes: Vec<u8> = std::fs::read(path).map_err(|error| format!("couldn't read network {}: {}", path, error))?;
+  read_network_bytes(&bytes).map_err(|error| format!("couldn't load network {}: {}", path, error))
+}
+
+pub fn load_network(options: &NetworkOptions) -> Result<Net, String> {
+  if options.allow_random && !Path::new(&options.path).exists() {
+    eprintln!("Warning: no network at {}, starting from random weights", options.path);
+    return Ok(NET.lock().unwrap().clone());
+  }
+  load_network_file(&options.path).map_err(|error| {
+    if Path::new(&options.path).exists() {
+      error
+    } else {
+      format!("{} (set the path with --eval-file or {}, or pass --random-net to start from random weights)", error, NETWORK_PATH_VARIABLE)
+    }
+  })
+}
+
+pub fn read_network_bytes(bytes: &[u8]) -> Result<Net, String> {
+  if is_binary_network(bytes) {
+    return network_from_bytes(bytes);
+  }
+  match std::str::from_utf8(bytes) {
+    Ok(text) => parse_network_text(text),
+    Err(_) => Err(String::from("neither a binary network nor text"))
   }
 }
 
 fn is_hidden(text: &str) -> bool {
-  text[0..6] == *"Hidden"
+  text.starts_with("Hidden ")
 }
 
 fn is_nodes_per_layer(text: &str) -> bool {
-  text[0..5] == *"Nodes"
+  text.starts_with("Nodes ")
+}
+
+fn is_widths(text: &str) -> bool {
+  text.starts_with("Widths ")
 }
 
 fn is_act_function(text: &str) -> bool {
-  text[0..3] == *"Act"
+  text.starts_with("Act ")
 }
 
 fn is_der_function(text: &str) -> bool {
-  text[0..3] == *"Der"
+  text.starts_with("Der ")
+}
+
+fn is_activation(text: &str) -> bool {
+  text.starts_with("Activation ")
 }
 
 fn is_learning_rate(text: &str) -> bool {
-  text[0..2] == *"Lr"
+  text.starts_with("Lr ")
+}
+
+fn is_encoder(text: &str) -> bool {
+  text.starts_with("Encoder ")
 }
 
 fn is_input_value(text: &str) -> bool {
-  text[0..5] == *"Input"
+  text.starts_with("Input ")
 }
 
 fn is_weight(text: &str) -> bool {
-  text[0..6] == *"Weight"
+  text.starts_with("Weight ")
 }
 
 fn is_bias(text: &str) -> bool {
-  text[0..4] == *"Bias"
+  text.starts_with("Bias ")
 }
 
-fn parse_input_line(full_text: &str) -> (usize, f64) {
-  let number_text = &full_text[6..];
-  let numbers = number_text.split(" ")
-                           .collect::<Vec<&str>>();
-  (numbers[0].parse::<usize>().unwrap(), numbers[1].parse::<f64>().unwrap())
+fn is_policy(text: &str) -> bool {
+  text.starts_with("Policy ")
 }
 
-fn parse_weight(full_text: &str) -> (usize, usize, usize, f64) {
-  let number_text = &full_text[7..];
-  let numbers = number_text.split(" ")
-                           .collect::<Vec<&str>>();
-  (
-    numbers[0].parse::<usize>().unwrap(),
-    numbers[1].parse::<usize>().unwrap(),
-    numbers[2].parse::<usize>().unwrap(),
-    numbers[3].parse::<f64>().unwrap()
-  )
+fn is_policy_weight(text: &str) -> bool {
+  text.starts_with("PolicyWeight ")
+}
+
+fn is_policy_bias(text: &str) -> bool {
+  text.starts_with("PolicyBias ")
+}
+
+fn parse_number<T: std::str::FromStr>(text: &str) -> Result<T, String> {
+  text.parse::<T>().map_err(|_| format!("'{}' is not a valid number", text))
+}
+
+// the numbers after a line's keyword, which must be exactly `count` of them
+fn line_numbers(full_text: &str, count: usize) -> Result<Vec<&str>, String> {
+  let numbers: Vec<&str> = full_text.split_whitespace().skip(1).collect();
+  if numbers.len() != count {
+    return Err(format!("expected {} numbers, found {}", count, numbers.len()));
+  }
+  Ok(numbers)
 }
 
-fn parse_bias(full_text: &str) -> (usize, usize, f64) {
-  let number_text = &full_text[5..];
-  let numbers = number_text.split(" ")
-                           .collect::<Vec<&str>>();
-  (
-    numbers[0].parse::<usize>().unwrap(),
-    numbers[1].parse::<usize>().unwrap(),
-    numbers[2].parse::<f64>().unwrap()
-  )
+fn parse_input_line(full_text: &str) -> Result<(usize, f64), String> {
+  let numbers = line_numbers(full_text, 2)?;
+  Ok((parse_number(numbers[0])?, parse_number(numbers[1])?))
 }
 
-lazy_static! {
-  static ref NAME_TO_FN: HashMap<&'static str, fn(f64) -> f64> = HashMap::from([
-    ("tanh", tanh as fn(f64) -> f64),
-    ("tanh_der_clipped", tanh_der_clipped as fn(f64) -> f64)
-  ]);
+fn parse_weight(full_text: &str) -> Result<(usize, usize, usize, f64), String> {
+  let numbers = line_numbers(full_text, 4)?;
+  Ok((
+    parse_number(numbers[0])?,
+    parse_number(numbers[1])?,
+    parse_number(numbers[2])?,
+    parse_number(numbers[3])?
+  ))
+}
+
+fn parse_bias(full_text: &str) -> Result<(usize, usize, f64), String> {
+  let numbers = line_numbers(full_text, 3)?;
+  Ok((
+    parse_number(numbers[0])?,
+    parse_number(numbers[1])?,
+    parse_number(numbers[2])?
+  ))
+}
+
+pub fn activation_from_name(name: &str) -> Result<Activation, String> {
+  Activation::from_name(name).ok_or(format!("unknown activation '{}'", name))
+}
+
+// older files name one activation function and its derivative for every layer
+pub fn activation_from_legacy_names(act_fn_name: &str, der_fn_name: &str) -> Result<Activation, String> {
+  Activation::from_legacy_names(act_fn_name, der_fn_name).ok_or(format!("unknown activation '{}' with derivative '{}'", act_fn_name, der_fn_name))
 }
 
 fn get_network_from_string(string: String) -> Net {
+  parse_network_text(&string).expect("Malformed network text. network_storage.rs, get_network_from_string")
+}
+
+pub fn parse_network_text(string: &str) -> Result<Net, String> {
   let lines: Vec<_> = string.lines().collect();
   let mut nodes_per_hidden_layer: usize = 0;
   let mut number_of_hidden_layers: usize = 0;
-  let mut act_fn: fn(f64) -> f64 = NAME_TO_FN["tanh"];
-  let mut der_fn: fn(f64) -> f64 = NAME_TO_FN["tanh_der_clipped"];
+  // `Widths` lists every hidden layer's width and replaces `Hidden` and `Nodes`, which
+  // files written before layers could differ in width use
+  let mut hidden_widths: Option<Vec<usize>> = None;
+  let mut act_fn_name: String = String::from("tanh");
+  let mut der_fn_name: String = String::from("tanh_der_clipped");
+  // `Activation <layer> <name>` lines, layer 1 being the first hidden layer
+  let mut layer_activations: Vec<(usize, usize, Activation)> = Vec::new();
   let mut lr: f64 = 0.2;
-
-  assert!(is_hidden("Hidden 90"));
+  // files written before encoders were stored all used the original 768 inputs
+  let mut encoder: InputEncoder = InputEncoder::Board768;
+  // `Policy <moves>` when the network has a policy head
+  let mut policy_moves: Option<usize> = None;
+  let at_line = |line_index: usize, error: String| format!("line {}: {}", line_index + 1, error);
 
   let mut input_count = 0;
-  for line in &lines {
-    if is_hidden(line) { 
-      number_of_hidden_layers = line[7..].parse::<usize>().unwrap();
-    } 
+  for (line_index, line) in lines.iter().enumerate() {
+    if is_hidden(line) {
+      number_of_hidden_layers = parse_number(&line[7..]).map_err(|error| at_line(line_index, error))?;
+    }
     else if is_nodes_per_layer(line) {
-      nodes_per_hidden_layer = line[6..].parse::<usize>().unwrap();
+      nodes_per_hidden_layer = parse_number(&line[6..]).map_err(|error| at_line(line_index, error))?;
+    }
+    else if is_widths(line) {
+      let widths: Vec<usize> = line.split_whitespace().skip(1).map(parse_number).collect::<Result<Vec<usize>, String>>().map_err(|error| at_line(line_index, error))?;
+      hidden_widths = Some(widths);
     }
     else if is_act_function(line) {
-      act_fn = NAME_TO_FN[&line[4..]];
+      act_fn_name = String::from(&line[4..]);
     }
     else if is_der_function(line) {
-      der_fn = NAME_TO_FN[&line[4..]];
+      der_fn_name = String::from(&line[4..]);
     }
     else if is_learning_rate(line) {
-      lr = line[3..].parse::<f64>().unwrap();
+      lr = parse_number(&line[3..]).map_err(|error| at_line(line_index, error))?;
+    }
+    else if is_encoder(line) {
+      encoder = InputEncoder::from_name(&line[8..]).ok_or(at_line(line_index, format!("unknown input encoder '{}'", &line[8..])))?;
+    }
+    else if is_activation(line) {
+      let words: Vec<&str> = line.split_whitespace().collect();
+      if words.len() != 3 {
+        return Err(at_line(line_index, String::from("expected a layer and an activation name")));
+      }
+      let layer: usize = parse_number(words[1]).map_err(|error| at_line(line_index, error))?;
+      let activation: Activation = activation_from_name(words[2]).map_err(|error| at_line(line_index, error))?;
+      layer_activations.push((line_index, layer, activation));
     }
     else if is_input_value(line) {
       input_count += 1;
     }
+    else if is_policy(line) {
+      policy_moves = Some(parse_number(&line[7..]).map_err(|error| at_line(line_index, error))?);
+    }
+    else if !is_weight(line) && !is_bias(line) && !is_policy_weight(line) && !is_policy_bias(line) && !line.trim().is_empty() {
+      return Err(at_line(line_index, format!("unrecognised line '{}'", line)));
+    }
+  }
+  let hidden_widths: Vec<usize> = hidden_widths.unwrap_or(vec![nodes_per_hidden_layer; number_of_hidden_layers]);
+  if hidden_widths.is_empty() || hidden_widths.contains(&0) {
+    return Err(String::from("the network needs positive Widths, or a positive Hidden and Nodes count"));
   }
+  let activation: Activation = activation_from_legacy_names(&act_fn_name, &der_fn_name)?;
 
   let mut input_values: Vec<f64> = vec![0f64; input_count];
-  let mut index_and_value: (usize, f64);
-  for line in &lines {
+  for (line_index, line) in lines.iter().enumerate() {
     if is_input_value(line) {
-      index_and_value = parse_input_line(line);
-      input_values[index_and_value.0] = index_and_value.1;
+      let (index, value) = parse_input_line(line).map_err(|error| at_line(line_index, error))?;
+      *input_values.get_mut(index).ok_or(at_line(line_index, format!("input {} is out of range", index)))? = value;
     }
   }
-  
-  let mut network: Net = Net::create(input_values, number_of_hidden_layers, nodes_per_hidden_layer, act_fn, der_fn, lr);
 
-  // weight indices and value
-  let mut w: (usize, usize, usize, f64);
-  // bias indices and value
-  let mut b: (usize, usize, f64);
+  let mut network: Net = Net::with_widths(input_values, &hidden_widths, activation, lr);
+  network.encoder = encoder;
+  for (line_index, layer, activation) in layer_activations {
+    if layer == 0 || layer > network.layers.len() {
+      return Err(at_line(line_index, format!("layer {} has no activation", layer)));
+    }
+    network.layers[layer - 1].activation = activation;
+  }
+  if let Some(moves) = policy_moves {
+    network.add_policy_head(moves);
+  }
 
-  for line in &lines {
+  for (line_index, line) in lines.iter().enumerate() {
     if is_weight(line) {
-      w = parse_weight(line);
-      network.weights[w.0][w.1][w.2] = w.3;
-    } 
+      let (layer, node, weight, value) = parse_weight(line).map_err(|error| at_line(line_index, error))?;
+      *network.layers.get_mut(layer).filter(|layer| node < layer.inputs() && weight < layer.outputs()).map(|layer| layer.weight_mut(node, weight))
+        .ok_or(at_line(line_index, format!("weight {} {} {} is out of range", layer, node, weight)))? = value as f32;
+    }
     else if is_bias(line) {
-      b = parse_bias(line);
-      network.biases[b.0][b.1] = b.2;
+      let (layer, node, value) = parse_bias(line).map_err(|error| at_line(line_index, error))?;
+      // older files hold biases for the input layer too, which were never used
+      if layer == 0 {
+        continue;
+      }
+      *network.layers.get_mut(layer - 1).and_then(|layer| layer.biases.get_mut(node))
+        .ok_or(at_line(line_index, format!("bias {} {} is out of range", layer, node)))? = value as f32;
+    }
+    else if is_policy_weight(line) {
+      let numbers = line_numbers(line, 3).map_err(|error| at_line(line_index, error))?;
+      let (node, index, value): (usize, usize, f64) = (
+        parse_number(numbers[0]).map_err(|error| at_line(line_index, error))?,
+        parse_number(numbers[1]).map_err(|error| at_line(line_index, error))?,
+        parse_number(numbers[2]).map_err(|error| at_line(line_index, error))?
+      );
+      *network.policy.as_mut().filter(|policy| node < policy.inputs() && index < policy.outputs()).map(|policy| policy.weight_mut(node, index))
+        .ok_or(at_line(line_index, format!("policy weight {} {} is out of range or there's no Policy line", node, index)))? = value as f32;
+    }
+    else if is_policy_bias(line) {
+      let numbers = line_numbers(line, 2).map_err(|error| at_line(line_index, error))?;
+      let (index, value): (usize, f64) = (
+        parse_number(numbers[0]).map_err(|error| at_line(line_index, error))?,
+        parse_number(numbers[1]).map_err(|error| at_line(line_index, error))?
+      );
+      *network.policy.as_mut().and_then(|policy| policy.biases.get_mut(index))
+        .ok_or(at_line(line_index, format!("policy bias {} is out of range or there's no Policy line", index)))? = value as f32;
     }
   }
-  network
+  Ok(network)
 }
 
-pub fn write_network_to_file(network: Net, act_fn_name: &str, der_fn_name: &str, path: &str) {
-  assert!(network.values.len() > 2, "Network must have at least one hidden layer. network_storage.rs, network_to_string");
-  let nodes_per_hidden_layer = network.values[1].len();
-  let number_of_hidden_layers = network.values.len() - 2;
-  
+// writes in the format already at `path`: binary at the precision it was stored in, else
+// text. a new path ending in .bin gets f32 binary. the network goes to a temporary file
+// that's renamed over the old one, so a failed write leaves the old network in place
+pub fn write_network_to_file(network: Net, path: &str) -> Result<(), String> {
+  let existing: Option<Vec<u8>> = std::fs::read(path).ok();
+  let precision: Option<Precision> = match existing {
+    Some(bytes) => binary_precision(&bytes),
+    None if path.ends_with(".bin") => Some(Precision::F32),
+    None => None
+  };
+  let bytes: Vec<u8> = match precision {
+    Some(precision) => network_to_bytes(&network, precision),
+    None => network_to_text(&network).into_bytes()
+  };
+  write_file_atomically(path, &bytes)
+}
+
+pub fn write_file_atomically(path: &str, bytes: &[u8]) -> Result<(), String> {
+  let temporary_path: String = format!("{}.tmp", path);
+  std::fs::write(&temporary_path, bytes).map_err(|error| format!("couldn't write {}: {}", temporary_path, error))?;
+  std::fs::rename(&temporary_path, path).map_err(|error| {
+    let _ = std::fs::remove_file(&temporary_path);
+    format!("couldn't replace {}: {}", path, error)
+  })
+}
+
+pub fn network_to_text(network: &Net) -> String {
+  assert!(network.layers.len() > 1, "Network must have at least one hidden layer. network_storage.rs, network_to_string");
+
   let mut input_values_string = String::new();
-  for (_value, value_index) in network.values[0].iter().zip(0..) {
+  for (value_index, value) in network.input.iter().enumerate() {
     input_values_string.push_str("Input ");
     input_values_string.push_str(&value_index.to_string());
-    input_values_string.push_str(" ");
-    input_values_string.push_str(&network.values[0][value_index].to_string());
+    input_values_string.push(' ');
+    input_values_string.push_str(&value.to_string());
     input_values_string += "\n";
   }
 
   let mut weights_string = String::new();
-  for (layer, layer_index) in network.weights.iter().zip(0..) {
-    for (node, node_index) in layer.iter().zip(0..) {
-      for (_weight, weight_index) in node.iter().zip(0..) {
+  for (layer_index, layer) in network.layers.iter().enumerate() {
+    for node_index in 0..layer.inputs() {
+      for weight_index in 0..layer.outputs() {
         weights_string.push_str("Weight ");
         weights_string.push_str(&layer_index.to_string());
-        weights_string.push_str(" ");
+        weights_string.push(' ');
         weights_string.push_str(&node_index.to_string());
-        weights_string.push_str(" ");
+        weights_string.push(' ');
         weights_string.push_str(&weight_index.to_string());
-        weights_string.push_str(" ");
-        weights_string.push_str(&network.weights[layer_index][node_index][weight_index].to_string());
+        weights_string.push(' ');
+        weights_string.push_str(&layer.weight(node_index, weight_index).to_string());
         weights_string += "\n";
       }
     }
   }
-  
+
+  // numbered like the activations, the first hidden layer being 1
   let mut biases_string = String::new();
-  for (layer, layer_index) in network.biases.iter().zip(0..) {
-    for (_node, node_index) in layer.iter().zip(0..) {
+  for (layer, layer_index) in network.layers.iter().zip(1..) {
+    for (node_index, bias) in layer.biases.iter().enumerate() {
       biases_string.push_str("Bias ");
       biases_string.push_str(&layer_index.to_string());
-      biases_string.push_str(" ");
+      biases_string.push(' ');
       biases_string.push_str(&node_index.to_string());
-      biases_string.push_str(" ");
-      biases_string.push_str(&network.biases[layer_index][node_index].to_string());
+      biases_string.push(' ');
+      biases_string.push_str(&bias.to_string());
       biases_string += "\n";
     }
   }
 
   let mut net: String = String::new();
-  net.push_str("Hidden ");
-  net.push_str(&number_of_hidden_layers.to_string());
-  net += "\n";
-  net.push_str("Nodes ");
-  net.push_str(&nodes_per_hidden_layer.to_string());
-  net += "\n";
-  net.push_str("Act ");
-  net.push_str(&act_fn_name);
-  net += "\n";
-  net.push_str("Der ");
-  net.push_str(&der_fn_name);
+  net.push_str("Widths");
+  for width in network.widths()[1..network.layers.len()].iter() {
+    net.push(' ');
+    net.push_str(&width.to_string());
+  }
   net += "\n";
+  for (layer, layer_index) in network.layers.iter().zip(1..) {
+    net.push_str("Activation ");
+    net.push_str(&layer_index.to_string());
+    net.push(' ');
+    net.push_str(layer.activation.name());
+    net += "\n";
+  }
   net.push_str("Lr ");
   net.push_str(&network.learning_rate.to_string());
   net += "\n";
+  net.push_str("Encoder ");
+  net.push_str(network.encoder.name());
+  net += "\n";
 
   net.push_str(&input_values_string);
   net.push_str(&weights_string);
   net.push_str(&biases_string);
 
-  clear_file(path);
-  std::fs::write(path, net).expect("Unable to write file");
-}
+  // only the nonzero policy weights, since a new head is all zeros and most moves are rare
+  if let Some(policy) = network.policy.as_ref() {
+    net.push_str("Policy ");
+    net.push_str(&policy.outputs().to_string());
+    net += "\n";
+    for index in 0..policy.outputs() {
+      for node_index in 0..policy.inputs() {
+        let weight: f32 = policy.weight(node_index, index);
+        if weight != 0f32 {
+          net.push_str(&format!("PolicyWeight {} {} {}\n", node_index, index, weight));
+        }
+      }
+    }
+    for (index, bias) in policy.biases.iter().enumerate() {
+      if *bias != 0f32 {
+        net.push_str(&format!("PolicyBias {} {}\n", index, bias));
+      }
+    }
+  }
 
-fn clear_file(path: &str) {
-  match std::fs::remove_file(path) {
-    Ok(removed) => removed,
-    Err(_) => {}
-  };
+  net
 }
 
 #[cfg(test)]
@@ -226,19 +416,126 @@ mod tests {
   #[test]
   fn writes_network_to_string() {
     let input_values = vec![0f64; 2];
-    let network = Net::create(input_values, 2, 2, tanh, tanh_der_clipped, 0.2);
-    write_network_to_file(network, "tanh", "tanh_der_clipped", "test_network_storage.txt");
-    get_network_from_file("test_network_storage.txt");
+    let path = std::env::temp_dir().join("test_network_storage.txt");
+    let network = Net::create(input_values, 2, 2, Activation::TanhClipped, 0.2);
+    write_network_to_file(network, path.to_str().unwrap()).unwrap();
+    load_network_file(path.to_str().unwrap()).unwrap();
+  }
+
+  #[test]
+  fn overwrites_in_the_format_already_there() {
+    let path = std::env::temp_dir().join("format_network_storage.bin");
+    let path: &str = path.to_str().unwrap();
+    let network = Net::random_with_widths(vec![0f64; 768], &[4], Activation::Relu, 0.2);
+    super::super::binary_storage::write_binary_network(&network, Precision::F64, path).unwrap();
+    write_network_to_file(network.clone(), path).unwrap();
+    assert!(binary_precision(&std::fs::read(path).unwrap()) == Some(Precision::F64));
+    assert!(load_network_file(path).unwrap() == network);
+    assert!(!Path::new(&format!("{}.tmp", path)).exists());
+    assert!(write_network_to_file(network, "no_such_directory/network.txt").is_err());
+  }
+
+  #[test]
+  fn keeps_the_input_encoder() {
+    let path = std::env::temp_dir().join("encoder_network_storage.txt");
+    let mut network = Net::create(vec![0f64; 769], 1, 2, Activation::TanhClipped, 0.2);
+    network.encoder = InputEncoder::SideToMove;
+    write_network_to_file(network, path.to_str().unwrap()).unwrap();
+    assert!(load_network_file(path.to_str().unwrap()).unwrap().encoder == InputEncoder::SideToMove);
+  }
+
+  #[test]
+  fn keeps_the_policy_head() {
+    let path = std::env::temp_dir().join("policy_network_storage.txt");
+    let mut network = Net::create(vec![0f64; 768], 1, 3, Activation::Tanh, 0.2);
+    network.add_policy_head(20);
+    *network.policy.as_mut().unwrap().weight_mut(2, 17) = 0.25;
+    network.policy.as_mut().unwrap().biases[4] = -0.5;
+    write_network_to_file(network.clone(), path.to_str().unwrap()).unwrap();
+    assert!(load_network_file(path.to_str().unwrap()).unwrap() == network);
+    assert!(parse_network_text("Widths 2\nInput 0 0\nPolicyBias 1 0.5\n").is_err());
+  }
+
+  #[test]
+  fn keeps_each_layers_activation() {
+    let path = std::env::temp_dir().join("activation_network_storage.txt");
+    let mut network = Net::create(vec![0f64; 768], 2, 2, Activation::Relu, 0.2);
+    network.layers[2].activation = Activation::Identity;
+    write_network_to_file(network, path.to_str().unwrap()).unwrap();
+    let loaded = load_network_file(path.to_str().unwrap()).unwrap();
+    assert!(loaded.activations() == vec![Activation::Relu, Activation::Relu, Activation::Identity]);
+  }
+
+  #[test]
+  fn reads_the_old_function_names() {
+    let network = get_network_from_string(String::from("Hidden 1\nNodes 1\nAct tanh\nDer tanh_der\nInput 0 0\n"));
+    assert!(network.activations() == vec![Activation::Tanh; 2]);
+    let network = get_network_from_string(String::from("Hidden 1\nNodes 1\nInput 0 0\n"));
+    assert!(network.activations() == vec![Activation::TanhClipped; 2]);
+  }
+
+  #[test]
+  fn keeps_layers_of_different_widths() {
+    let path = std::env::temp_dir().join("widths_network_storage.txt");
+    let mut network = Net::random_with_widths(vec![0f64; 768], &[8, 3], Activation::Relu, 0.2);
+    network.layers[1].biases[2] = -0.25;
+    write_network_to_file(network.clone(), path.to_str().unwrap()).unwrap();
+    let loaded = load_network_file(path.to_str().unwrap()).unwrap();
+    assert!(loaded.widths() == vec![768, 8, 3, 1]);
+    assert!(loaded.parameters() == network.parameters());
+  }
+
+  #[test]
+  fn reads_uniform_hidden_layers_and_input_biases() {
+    let network = get_network_from_string(String::from("Hidden 2\nNodes 3\nInput 0 0\nBias 0 0 0.5\nBias 2 1 0.25\nWeight 1 2 0 -1\n"));
+    assert!(network.widths() == vec![1, 3, 3, 1]);
+    assert!(network.layers[1].biases[1] == 0.25 && network.layers[1].weight(2, 0) == -1.0);
+  }
+
+  #[test]
+  fn old_files_default_to_board_768() {
+    let network = get_network_from_string(String::from("Hidden 1\nNodes 1\nLr 0.1\nInput 0 0\n"));
+    assert!(network.encoder == InputEncoder::Board768);
+  }
+
+  #[test]
+  fn reports_malformed_lines() {
+    assert!(parse_network_text("Hidden 1\nNodes 1\nInput 0 0\nWeight 0 0 x 0.5\n").err().unwrap().starts_with("line 4"));
+    assert!(parse_network_text("Hidden 1\nNodes 1\nInput 0 0\nWeight 0 3 0 0.5\n").is_err());
+    assert!(parse_network_text("Hidden 1\nNodes 1\nAct relu\nInput 0 0\n").is_err());
+    assert!(parse_network_text("Hidden 1\nNodes 1\nActivation 3 relu\nInput 0 0\n").is_err());
+    assert!(parse_network_text("Hidden 1\nNodes 1\nActivation 1 swish\nInput 0 0\n").is_err());
+    assert!(parse_network_text("Hidden 1\nNodes 1\nWat\n").is_err());
+    assert!(parse_network_text("Nodes 1\nInput 0 0\n").is_err());
+    assert!(parse_network_text("Widths 2 0\nInput 0 0\n").is_err());
+    assert!(parse_network_text("Widths 2\nInput 0 0\nBias 3 0 0.5\n").is_err());
+  }
+
+  #[test]
+  fn missing_files_need_an_opt_in() {
+    let options = network_options(Some("no_such_network.txt"), false);
+    assert!(options.path == "no_such_network.txt");
+    assert!(load_network(&options).unwrap_err().contains("--random-net"));
+    assert!(load_network(&network_options(Some("no_such_network.txt"), true)).is_ok());
+  }
+
+  #[test]
+  fn corrupt_files_are_errors_even_with_the_opt_in() {
+    let path = std::env::temp_dir().join("corrupt_network_storage.txt");
+    std::fs::write(&path, "Hidden two\n").unwrap();
+    let error: String = load_network(&network_options(path.to_str(), true)).unwrap_err();
+    assert!(error.contains("line 1"), "{}", error);
   }
 
   #[test]
   #[ignore]
   fn converts_network_to_file_and_back() {
     let input_values = vec![0f64; 2];
-    let original_network = Net::create(input_values, 2, 2, tanh, tanh_der_clipped, 0.2);
-    write_network_to_file(original_network.clone(), "tanh", "tanh_der_clipped", "text_network_storage.txt");
-    let mut new_net = get_network_from_file("test_network_storage.txt");
-    new_net.weights[2][1][0] = 0.0f64;
+    let path = std::env::temp_dir().join("round_trip_network_storage.txt");
+    let original_network = Net::create(input_values, 2, 2, Activation::TanhClipped, 0.2);
+    write_network_to_file(original_network.clone(), path.to_str().unwrap()).unwrap();
+    let mut new_net = load_network_file(path.to_str().unwrap()).unwrap();
+    *new_net.layers[2].weight_mut(1, 0) = 0.0f32;
     // println!("{:?}", new_net);
     println!("Race conditions resulting in default network being returned.");
     assert!(original_network == new_net);
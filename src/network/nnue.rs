@@ -0,0 +1,538 @@
+use crate::evaluator::{Evaluator, Position, Score};
+use super::encoding::InputEncoder;
+use super::eval::training_target;
+use super::binary_storage::{crc32, push_name, Reader};
+use super::network_storage::write_file_atomically;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::sync::Arc;
+
+// fixed point scales: feature layer outputs are clipped to [0, QA], which stands for [0, 1],
+// and head weights are stored times QB
+const QA: i32 = 255;
+const QB: i32 = 128;
+
+pub const DEFAULT_NNUE_HIDDEN: usize = 128;
+pub const DEFAULT_NNUE_HEAD: usize = 16;
+pub const DEFAULT_NNUE_PATH: &str = "nnue_weights.bin";
+
+// layout, all little-endian:
+//   magic "CENU", format version u16
+//   input encoder name, a u8 length and the bytes
+//   hidden u32, head u32
+//   the float weights as f32: feature weights, feature biases, head weights, head biases,
+//   output weights and the output bias. they're quantised when the file is loaded
+//   CRC-32 of everything before it, u32
+const NNUE_MAGIC: &[u8; 4] = b"CENU";
+pub const NNUE_FORMAT_VERSION: u16 = 1;
+
+// a sparse feature layer, one accumulator of `hidden` values per block of the encoder's
+// inputs, followed by a dense head of `head` neurons and a single output, all with clipped
+// relu. kept in floats for training and quantised into `Nnue` to run in the search
+#[derive(Clone, PartialEq, Debug)]
+pub struct NnueWeights {
+  pub encoder: InputEncoder,
+  pub hidden: usize,
+  pub head: usize,
+  // a column of `hidden` weights per input of one block
+  pub feature_weights: Vec<f32>,
+  pub feature_bias: Vec<f32>,
+  // a row of `perspectives * hidden` weights per head neuron
+  pub head_weights: Vec<f32>,
+  pub head_bias: Vec<f32>,
+  pub output_weights: Vec<f32>,
+  pub output_bias: f32
+}
+
+pub fn make_random_nnue_weights(encoder: InputEncoder, hidden: usize, head: usize, seed: u64) -> NnueWeights {
+  let mut rng: StdRng = StdRng::seed_from_u64(seed);
+  let inputs: usize = encoder.perspectives() * hidden;
+  let mut uniform = |count: usize, range: f32| -> Vec<f32> {
+    (0..count).map(|_| rng.gen_range(-range..range)).collect()
+  };
+  NnueWeights {
+    encoder,
+    hidden,
+    head,
+    feature_weights: uniform(encoder.perspective_size() * hidden, 0.1),
+    feature_bias: uniform(hidden, 0.1).into_iter().map(|bias| bias + 0.2).collect(),
+    head_weights: uniform(head * inputs, 1.0 / (inputs as f32).sqrt()),
+    head_bias: uniform(head, 0.1),
+    output_weights: uniform(head, 2.0 / (head as f32).sqrt()),
+    output_bias: 0.0
+  }
+}
+
+// the side each block of inputs is seen from; the mover's block comes first
+fn block_side(encoder: InputEncoder, position: &Position, block: usize) -> usize {
+  if encoder.relative_to_mover() {
+    (if position.turn_number % 2 == 1 { 0 } else { 1 }) ^ block
+  } else {
+    0
+  }
+}
+
+fn clipped_relu(value: f32) -> f32 {
+  value.clamp(0.0, 1.0)
+}
+
+// the values of one float forward pass, kept for the backward pass
+struct FloatPass {
+  // the features of each block of inputs
+  features: Vec<Vec<usize>>,
+  // every block's accumulator before clipping, then after
+  accumulators: Vec<f32>,
+  inputs: Vec<f32>,
+  // the head's sums before clipping
+  sums: Vec<f32>,
+  // from the encoder's side
+  output: f32
+}
+
+impl NnueWeights {
+  fn forward_float(&self, position: &Position) -> FloatPass {
+    let mut pass: FloatPass = FloatPass { features: Vec::new(), accumulators: Vec::new(), inputs: Vec::new(), sums: Vec::new(), output: self.output_bias };
+    for block in 0..self.encoder.perspectives() {
+      let features: Vec<usize> = self.encoder.perspective_features(position, block_side(self.encoder, position, block));
+      let mut accumulator: Vec<f32> = self.feature_bias.clone();
+      for feature in features.iter() {
+        let column: &[f32] = &self.feature_weights[feature * self.hidden..(feature + 1) * self.hidden];
+        for (value, weight) in accumulator.iter_mut().zip(column.iter()) {
+          *value += weight;
+        }
+      }
+      pass.features.push(features);
+      pass.accumulators.extend(accumulator);
+    }
+    pass.inputs = pass.accumulators.iter().map(|value| clipped_relu(*value)).collect();
+    let inputs: usize = pass.inputs.len();
+    for neuron in 0..self.head {
+      let row: &[f32] = &self.head_weights[neuron * inputs..(neuron + 1) * inputs];
+      let sum: f32 = row.iter().zip(pass.inputs.iter()).map(|(weight, input)| weight * input).sum::<f32>() + self.head_bias[neuron];
+      pass.output += clipped_relu(sum) * self.output_weights[neuron];
+      pass.sums.push(sum);
+    }
+    pass
+  }
+
+  // the unquantised forward pass, in pawns from white's side
+  pub fn evaluate_float(&self, position: &Position) -> f64 {
+    training_target(self.encoder, position, self.forward_float(position).output as f64)
+  }
+
+  // one step of plain gradient descent on half the squared error against `white_target`,
+  // returning that loss from before the step. only the columns of the position's features
+  // change in the feature layer
+  pub fn train_sample(&mut self, position: &Position, white_target: f64, learning_rate: f32) -> f64 {
+    let pass: FloatPass = self.forward_float(position);
+    let error: f32 = pass.output - training_target(self.encoder, position, white_target) as f32;
+    let inputs: usize = pass.inputs.len();
+    let mut input_errors: Vec<f32> = vec![0.0; inputs];
+    for neuron in 0..self.head {
+      let sum: f32 = pass.sums[neuron];
+      let sum_error: f32 = if sum > 0.0 && sum < 1.0 { error * self.output_weights[neuron] } else { 0.0 };
+      self.output_weights[neuron] -= learning_rate * error * clipped_relu(sum);
+      if sum_error == 0.0 {
+        continue;
+      }
+      let row: &mut [f32] = &mut self.head_weights[neuron * inputs..(neuron + 1) * inputs];
+      for ((weight, input), input_error) in row.iter_mut().zip(pass.inputs.iter()).zip(input_errors.iter_mut()) {
+        *input_error += sum_error * *weight;
+        *weight -= learning_rate * sum_error * input;
+      }
+      self.head_bias[neuron] -= learning_rate * sum_error;
+    }
+    self.output_bias -= learning_rate * error;
+    for (block, features) in pass.features.iter().enumerate() {
+      let accumulator_errors: Vec<f32> = (block * self.hidden..(block + 1) * self.hidden)
+        .map(|index| if pass.accumulators[index] > 0.0 && pass.accumulators[index] < 1.0 { input_errors[index] } else { 0.0 })
+        .collect();
+      for feature in features.iter() {
+        for (weight, accumulator_error) in self.feature_weights[feature * self.hidden..(feature + 1) * self.hidden].iter_mut().zip(accumulator_errors.iter()) {
+          *weight -= learning_rate * accumulator_error;
+        }
+      }
+      for (bias, accumulator_error) in self.feature_bias.iter_mut().zip(accumulator_errors.iter()) {
+        *bias -= learning_rate * accumulator_error;
+      }
+    }
+    0.5 * (error * error) as f64
+  }
+
+  // (feature weights, feature biases, head weights, head biases, output weights) counts
+  fn sizes(encoder: InputEncoder, hidden: usize, head: usize) -> [usize; 5] {
+    [encoder.perspective_size() * hidden, hidden, head * encoder.perspectives() * hidden, head, head]
+  }
+}
+
+pub fn nnue_to_bytes(weights: &NnueWeights) -> Vec<u8> {
+  let mut bytes: Vec<u8> = Vec::new();
+  bytes.extend_from_slice(NNUE_MAGIC);
+  bytes.extend_from_slice(&NNUE_FORMAT_VERSION.to_le_bytes());
+  push_name(&mut bytes, weights.encoder.name());
+  bytes.extend_from_slice(&(weights.hidden as u32).to_le_bytes());
+  bytes.extend_from_slice(&(weights.head as u32).to_le_bytes());
+  let values = weights.feature_weights.iter()
+    .chain(weights.feature_bias.iter())
+    .chain(weights.head_weights.iter())
+    .chain(weights.head_bias.iter())
+    .chain(weights.output_weights.iter())
+    .chain(std::iter::once(&weights.output_bias));
+  for value in values {
+    bytes.extend_from_slice(&value.to_le_bytes());
+  }
+  let checksum: u32 = crc32(&bytes);
+  bytes.extend_from_slice(&checksum.to_le_bytes());
+  bytes
+}
+
+pub fn is_nnue_file(bytes: &[u8]) -> bool {
+  bytes.starts_with(NNUE_MAGIC)
+}
+
+pub fn nnue_from_bytes(bytes: &[u8]) -> Result<NnueWeights, String> {
+  let mut reader: Reader = Reader { bytes, offset: 0 };
+  if reader.take(4, "the magic number")? != NNUE_MAGIC {
+    return Err(String::from("not an NNUE file: bad magic number"));
+  }
+  let version: u16 = u16::from_le_bytes(reader.array("the format version")?);
+  if version != NNUE_FORMAT_VERSION {
+    return Err(format!("unsupported NNUE format version {}, expected {}", version, NNUE_FORMAT_VERSION));
+  }
+  if bytes.len() < 4 + reader.offset {
+    return Err(String::from("file too short to hold a checksum"));
+  }
+  let (body, stored) = bytes.split_at(bytes.len() - 4);
+  let stored_checksum: u32 = u32::from_le_bytes(stored.try_into().unwrap());
+  let checksum: u32 = crc32(body);
+  if stored_checksum != checksum {
+    return Err(format!("checksum mismatch: stored {:08x}, computed {:08x}", stored_checksum, checksum));
+  }
+  let mut reader: Reader = Reader { bytes: body, offset: reader.offset };
+  let encoder_name: String = reader.name("the encoder name")?;
+  let encoder: InputEncoder = InputEncoder::from_name(&encoder_name).ok_or(format!("unknown encoder '{}'", encoder_name))?;
+  let hidden: usize = reader.u32("the hidden width")? as usize;
+  let head: usize = reader.u32("the head width")? as usize;
+  if hidden == 0 || head == 0 {
+    return Err(format!("layer widths must be positive, not {} and {}", hidden, head));
+  }
+  let sizes: [usize; 5] = NnueWeights::sizes(encoder, hidden, head);
+  let count: usize = sizes.iter().sum::<usize>() + 1;
+  if body.len() - reader.offset != 4 * count {
+    return Err(format!("expected {} weights, found {} bytes of them", count, body.len() - reader.offset));
+  }
+  let mut next = |count: usize, what: &str| -> Result<Vec<f32>, String> {
+    Ok(reader.take(4 * count, what)?.chunks(4).map(|chunk| f32::from_le_bytes(chunk.try_into().unwrap())).collect())
+  };
+  Ok(NnueWeights {
+    encoder,
+    hidden,
+    head,
+    feature_weights: next(sizes[0], "the feature weights")?,
+    feature_bias: next(sizes[1], "the feature biases")?,
+    head_weights: next(sizes[2], "the head weights")?,
+    head_bias: next(sizes[3], "the head biases")?,
+    output_weights: next(sizes[4], "the output weights")?,
+    output_bias: next(1, "the output bias")?[0]
+  })
+}
+
+pub fn write_nnue_file(weights: &NnueWeights, path: &str) -> Result<(), String> {
+  write_file_atomically(path, &nnue_to_bytes(weights))
+}
+
+pub fn read_nnue_file(path: &str) -> Result<NnueWeights, String> {
+  let bytes: Vec<u8> = std::fs::read(path).map_err(|error| format!("couldn't read NNUE {}: {}", path, error))?;
+  nnue_from_bytes(&bytes).map_err(|error| format!("couldn't load NNUE {}: {}", path, error))
+}
+
+// the quantised network from a weights file, ready for the search
+pub fn load_nnue_evaluator(path: &str) -> Result<NnueEvaluator, String> {
+  Ok(make_nnue_evaluator(Arc::new(quantise_nnue(&read_nnue_file(path)?))))
+}
+
+fn quantise(values: &[f32], scale: i32) -> Vec<i16> {
+  values.iter().map(|value| (value * scale as f32).round().clamp(i16::MIN as f32, i16::MAX as f32) as i16).collect()
+}
+
+// the integer network the search runs
+pub struct Nnue {
+  encoder: InputEncoder,
+  hidden: usize,
+  head: usize,
+  feature_weights: Vec<i16>,
+  feature_bias: Vec<i16>,
+  head_weights: Vec<i16>,
+  head_bias: Vec<i32>,
+  output_weights: Vec<i16>,
+  output_bias: i32
+}
+
+pub fn quantise_nnue(weights: &NnueWeights) -> Nnue {
+  Nnue {
+    encoder: weights.encoder,
+    hidden: weights.hidden,
+    head: weights.head,
+    feature_weights: quantise(&weights.feature_weights, QA),
+    feature_bias: quantise(&weights.feature_bias, QA),
+    head_weights: quantise(&weights.head_weights, QB),
+    head_bias: weights.head_bias.iter().map(|bias| (bias * (QA * QB) as f32).round() as i32).collect(),
+    output_weights: quantise(&weights.output_weights, QB),
+    output_bias: (weights.output_bias * (QA * QB) as f32).round() as i32
+  }
+}
+
+impl Nnue {
+  // number of per-side accumulators to keep: mover-relative encoders need both sides so
+  // the mover's can go first whoever is to move
+  fn sides(&self) -> usize {
+    if self.encoder.relative_to_mover() { 2 } else { 1 }
+  }
+
+  fn column(&self, feature: usize) -> &[i16] {
+    &self.feature_weights[feature * self.hidden..(feature + 1) * self.hidden]
+  }
+
+  fn refresh(&self, position: &Position, side: usize, accumulator: &mut Vec<i32>) {
+    accumulator.clear();
+    accumulator.extend(self.feature_bias.iter().map(|bias| *bias as i32));
+    for feature in self.encoder.perspective_features(position, side) {
+      for (value, weight) in accumulator.iter_mut().zip(self.column(feature).iter()) {
+        *value += *weight as i32;
+      }
+    }
+  }
+
+  // the head on clipped accumulators, one per block of inputs, in pawns from the
+  // encoder's side
+  fn forward(&self, blocks: &[&[i32]]) -> f64 {
+    let inputs: usize = blocks.len() * self.hidden;
+    let mut output: i32 = self.output_bias;
+    for neuron in 0..self.head {
+      let row: &[i16] = &self.head_weights[neuron * inputs..(neuron + 1) * inputs];
+      let mut sum: i32 = self.head_bias[neuron];
+      for (block, weights) in blocks.iter().zip(row.chunks(self.hidden)) {
+        sum += block.iter().zip(weights.iter()).map(|(value, weight)| value.clamp(&0, &QA) * *weight as i32).sum::<i32>();
+      }
+      output += (sum / QB).clamp(0, QA) * self.output_weights[neuron] as i32;
+    }
+    output as f64 / (QA * QB) as f64
+  }
+}
+
+// sorted so two feature lists can be diffed in one pass
+fn sorted_features(encoder: InputEncoder, position: &Position, side: usize) -> Vec<usize> {
+  let mut features: Vec<usize> = encoder.perspective_features(position, side);
+  features.sort_unstable();
+  features
+}
+
+struct Accumulator {
+  position: Position,
+  sides: [Vec<i32>; 2]
+}
+
+// keeps an accumulator for every position on the search path. making a move copies the
+// parent's and adds or subtracts the columns of the features that changed, so a leaf only
+// costs the head
+pub struct NnueEvaluator {
+  nnue: Arc<Nnue>,
+  // the first `depth` entries are the current path; the rest keep their buffers for reuse
+  stack: Vec<Accumulator>,
+  depth: usize
+}
+
+pub fn make_nnue_evaluator(nnue: Arc<Nnue>) -> NnueEvaluator {
+  NnueEvaluator {
+    nnue,
+    stack: Vec::new(),
+    depth: 0
+  }
+}
+
+impl NnueEvaluator {
+  fn slot(&mut self, index: usize) -> &mut Accumulator {
+    while self.stack.len() <= index {
+      self.stack.push(Accumulator { position: Position { state: [0; 13], turn_number: 0 }, sides: [Vec::new(), Vec::new()] });
+    }
+    &mut self.stack[index]
+  }
+
+  fn top_is(&self, position: &Position) -> bool {
+    self.depth > 0 && self.stack[self.depth - 1].position == *position
+  }
+
+  // starts a fresh path at `position`
+  fn reset(&mut self, position: &Position) {
+    let nnue: Arc<Nnue> = self.nnue.clone();
+    let slot: &mut Accumulator = self.slot(0);
+    slot.position = *position;
+    for side in 0..nnue.sides() {
+      nnue.refresh(position, side, &mut slot.sides[side]);
+    }
+    self.depth = 1;
+  }
+}
+
+impl Evaluator for NnueEvaluator {
+  fn evaluate(&mut self, position: &Position) -> Score {
+    if !self.top_is(position) {
+      self.reset(position);
+    }
+    let accumulator: &Accumulator = &self.stack[self.depth - 1];
+    let nnue: &Nnue = &self.nnue;
+    let value: f64 = if nnue.encoder.perspectives() == 2 {
+      let mover: usize = block_side(nnue.encoder, position, 0);
+      nnue.forward(&[&accumulator.sides[mover], &accumulator.sides[mover ^ 1]])
+    } else {
+      nnue.forward(&[&accumulator.sides[block_side(nnue.encoder, position, 0)]])
+    };
+    training_target(nnue.encoder, position, value)
+  }
+
+  fn on_make(&mut self, parent: &Position, child: &Position) {
+    if !self.top_is(parent) {
+      self.reset(parent);
+    }
+    let nnue: Arc<Nnue> = self.nnue.clone();
+    let depth: usize = self.depth;
+    self.slot(depth);
+    let (path, rest) = self.stack.split_at_mut(depth);
+    let (from, to) = (&path[depth - 1], &mut rest[0]);
+    to.position = *child;
+    for side in 0..nnue.sides() {
+      to.sides[side].clone_from(&from.sides[side]);
+      let before: Vec<usize> = sorted_features(nnue.encoder, parent, side);
+      let after: Vec<usize> = sorted_features(nnue.encoder, child, side);
+      let accumulator: &mut Vec<i32> = &mut to.sides[side];
+      let (mut i, mut j) = (0, 0);
+      while i < before.len() || j < after.len() {
+        if j == after.len() || (i < before.len() && before[i] < after[j]) {
+          for (value, weight) in accumulator.iter_mut().zip(nnue.column(before[i]).iter()) {
+            *value -= *weight as i32;
+          }
+          i += 1;
+        } else if i == before.len() || after[j] < before[i] {
+          for (value, weight) in accumulator.iter_mut().zip(nnue.column(after[j]).iter()) {
+            *value += *weight as i32;
+          }
+          j += 1;
+        } else {
+          i += 1;
+          j += 1;
+        }
+      }
+    }
+    self.depth += 1;
+  }
+
+  fn on_unmake(&mut self, child: &Position, _parent: &Position) {
+    if self.top_is(child) {
+      self.depth -= 1;
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use super::super::encoding::ENCODERS;
+  use crate::evaluator::make_position;
+  use crate::fen::state_from_fen;
+  use crate::r#move::states_for_turn;
+
+  const FENS: [&str; 4] = [
+    "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w - -",
+    "r1bq1rk1/pp3ppp/2n1p3/3n4/1b1P4/2NB1N2/PP3PPP/R1BQ1RK1 b - -",
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - -",
+    "4rrk1/pp1n3p/3q2pQ/2p1pb2/2PP4/2P3N1/P2B2PP/4RRK1 b - -"
+  ];
+
+  fn position(fen: &str) -> Position {
+    let (state, turn_number) = state_from_fen(fen).unwrap();
+    make_position(state, turn_number)
+  }
+
+  #[test]
+  fn quantised_network_tracks_the_float_one() {
+    for encoder in ENCODERS.iter() {
+      let weights: NnueWeights = make_random_nnue_weights(*encoder, 64, 16, 7);
+      let mut evaluator: NnueEvaluator = make_nnue_evaluator(Arc::new(quantise_nnue(&weights)));
+      for fen in FENS.iter() {
+        let float: f64 = weights.evaluate_float(&position(fen));
+        let quantised: f64 = evaluator.evaluate(&position(fen));
+        assert!((float - quantised).abs() < 0.05, "{} {}: {} vs {}", encoder.name(), fen, float, quantised);
+      }
+    }
+  }
+
+  // walks every line `depth` plies deep, checking each updated accumulator against a refresh
+  fn check_updates(evaluator: &mut NnueEvaluator, fresh: &mut NnueEvaluator, parent: Position, depth: u8) {
+    for state in states_for_turn(parent.state, parent.turn_number) {
+      let child: Position = make_position(state, parent.turn_number + 1);
+      evaluator.on_make(&parent, &child);
+      assert!(evaluator.depth >= 2);
+      let updated: Vec<Vec<i32>> = evaluator.stack[evaluator.depth - 1].sides.to_vec();
+      fresh.reset(&child);
+      assert!(updated == fresh.stack[0].sides.to_vec());
+      assert!(evaluator.evaluate(&child) == fresh.evaluate(&child));
+      if depth > 1 {
+        check_updates(evaluator, fresh, child, depth - 1);
+      }
+      evaluator.on_unmake(&child, &parent);
+    }
+  }
+
+  #[test]
+  fn incremental_updates_match_a_refresh() {
+    for encoder in ENCODERS.iter() {
+      let nnue: Arc<Nnue> = Arc::new(quantise_nnue(&make_random_nnue_weights(*encoder, 16, 4, 3)));
+      let mut evaluator: NnueEvaluator = make_nnue_evaluator(nnue.clone());
+      let mut fresh: NnueEvaluator = make_nnue_evaluator(nnue);
+      // the king walks, which changes every king bucket feature of its side
+      check_updates(&mut evaluator, &mut fresh, position("r3k2r/p1p2p1p/8/3pP3/8/8/P1P2P1P/R3K2R w - -"), 2);
+      assert!(evaluator.depth == 1);
+    }
+  }
+
+  #[test]
+  fn training_moves_towards_the_target() {
+    for encoder in [InputEncoder::Board768, InputEncoder::KingBuckets] {
+      let mut weights: NnueWeights = make_random_nnue_weights(encoder, 16, 4, 5);
+      let target: Position = position(FENS[1]);
+      let before: f64 = (weights.evaluate_float(&target) - 0.5).abs();
+      for _step in 0..50 {
+        weights.train_sample(&target, 0.5, 0.01);
+      }
+      assert!((weights.evaluate_float(&target) - 0.5).abs() < before, "{}", encoder.name());
+    }
+  }
+
+  #[test]
+  fn weights_round_trip_through_a_file() {
+    let path = std::env::temp_dir().join("round_trip_nnue.bin");
+    let path: &str = path.to_str().unwrap();
+    let weights: NnueWeights = make_random_nnue_weights(InputEncoder::Mirrored, 8, 4, 2);
+    write_nnue_file(&weights, path).unwrap();
+    assert!(read_nnue_file(path).unwrap() == weights);
+    assert!(load_nnue_evaluator(path).unwrap().evaluate(&position(FENS[0])) == make_nnue_evaluator(Arc::new(quantise_nnue(&weights))).evaluate(&position(FENS[0])));
+    let mut bytes: Vec<u8> = nnue_to_bytes(&weights);
+    assert!(nnue_from_bytes(&bytes[..bytes.len() - 8]).unwrap_err().contains("checksum"));
+    bytes[20] ^= 1;
+    assert!(nnue_from_bytes(&bytes).is_err());
+    assert!(nnue_from_bytes(b"CENN\x01\x00").unwrap_err().contains("magic"));
+  }
+
+  #[test]
+  fn mover_relative_scores_flip_with_the_colours() {
+    // the same position with colours swapped and the board flipped, the other side to move
+    let original: Position = position("4k3/2n5/8/8/8/8/3PP3/4K2R w - -");
+    let flipped: Position = position("4k2r/3pp3/8/8/8/8/2N5/4K3 b - -");
+    for encoder in [InputEncoder::Mirrored, InputEncoder::KingBuckets] {
+      let mut evaluator: NnueEvaluator = make_nnue_evaluator(Arc::new(quantise_nnue(&make_random_nnue_weights(encoder, 32, 8, 11))));
+      let score: f64 = evaluator.evaluate(&original);
+      assert!(score != 0.0);
+      assert!(score == -evaluator.evaluate(&flipped), "{}", encoder.name());
+    }
+  }
+}
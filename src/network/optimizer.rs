@@ -0,0 +1,258 @@
+// update rules for a flat list of parameters given their averaged gradients. `step` is
+// called once per mini-batch with that batch's learning rate
+pub trait Optimizer: Send {
+  fn step(&mut self, parameters: &mut [f64], gradients: &[f64], learning_rate: f64);
+  // what the optimiser carries from one step to the next, flattened, so training can stop
+  // and pick up again where it left off
+  fn state(&self) -> Vec<f64>;
+  fn restore_state(&mut self, state: &[f64]) -> Result<(), String>;
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum OptimizerKind {
+  Sgd,
+  Momentum { momentum: f64, nesterov: bool },
+  Adam { beta1: f64, beta2: f64 },
+  // Adam with the weight decay applied straight to the parameters rather than added to
+  // the gradient, so it isn't scaled down by the second moment
+  AdamW { beta1: f64, beta2: f64 }
+}
+
+pub fn make_optimizer(kind: OptimizerKind, weight_decay: f64) -> Box<dyn Optimizer> {
+  match kind {
+    OptimizerKind::Sgd => Box::new(Sgd { weight_decay }),
+    OptimizerKind::Momentum { momentum, nesterov } => Box::new(Momentum {
+      momentum,
+      nesterov,
+      weight_decay,
+      velocity: Vec::new()
+    }),
+    OptimizerKind::Adam { beta1, beta2 } => Box::new(make_adam(beta1, beta2, weight_decay, false)),
+    OptimizerKind::AdamW { beta1, beta2 } => Box::new(make_adam(beta1, beta2, weight_decay, true))
+  }
+}
+
+// L2 weight decay, folded into the gradient
+fn decayed(gradient: f64, parameter: f64, weight_decay: f64) -> f64 {
+  gradient + weight_decay * parameter
+}
+
+pub struct Sgd {
+  weight_decay: f64
+}
+
+impl Optimizer for Sgd {
+  fn step(&mut self, parameters: &mut [f64], gradients: &[f64], learning_rate: f64) {
+    for (parameter, gradient) in parameters.iter_mut().zip(gradients.iter()) {
+      *parameter -= learning_rate * decayed(*gradient, *parameter, self.weight_decay);
+    }
+  }
+
+  fn state(&self) -> Vec<f64> {
+    Vec::new()
+  }
+
+  fn restore_state(&mut self, state: &[f64]) -> Result<(), String> {
+    if state.is_empty() { Ok(()) } else { Err(String::from("sgd keeps no state between steps")) }
+  }
+}
+
+pub struct Momentum {
+  momentum: f64,
+  // looks ahead along the velocity before taking the step
+  nesterov: bool,
+  weight_decay: f64,
+  velocity: Vec<f64>
+}
+
+impl Optimizer for Momentum {
+  fn step(&mut self, parameters: &mut [f64], gradients: &[f64], learning_rate: f64) {
+    self.velocity.resize(parameters.len(), 0.0);
+    for ((parameter, gradient), velocity) in parameters.iter_mut().zip(gradients.iter()).zip(self.velocity.iter_mut()) {
+      let gradient: f64 = decayed(*gradient, *parameter, self.weight_decay);
+      *velocity = self.momentum * *velocity + gradient;
+      let direction: f64 = if self.nesterov { gradient + self.momentum * *velocity } else { *velocity };
+      *parameter -= learning_rate * direction;
+    }
+  }
+
+  fn state(&self) -> Vec<f64> {
+    self.velocity.clone()
+  }
+
+  fn restore_state(&mut self, state: &[f64]) -> Result<(), String> {
+    self.velocity = state.to_vec();
+    Ok(())
+  }
+}
+
+const ADAM_EPSILON: f64 = 1e-8;
+
+pub struct Adam {
+  beta1: f64,
+  beta2: f64,
+  weight_decay: f64,
+  decoupled: bool,
+  first_moment: Vec<f64>,
+  second_moment: Vec<f64>,
+  steps: i32
+}
+
+pub fn make_adam(beta1: f64, beta2: f64, weight_decay: f64, decoupled: bool) -> Adam {
+  Adam {
+    beta1,
+    beta2,
+    weight_decay,
+    decoupled,
+    first_moment: Vec::new(),
+    second_moment: Vec::new(),
+    steps: 0
+  }
+}
+
+impl Optimizer for Adam {
+  fn step(&mut self, parameters: &mut [f64], gradients: &[f64], learning_rate: f64) {
+    self.first_moment.resize(parameters.len(), 0.0);
+    self.second_moment.resize(parameters.len(), 0.0);
+    self.steps += 1;
+    let first_correction: f64 = 1.0 - self.beta1.powi(self.steps);
+    let second_correction: f64 = 1.0 - self.beta2.powi(self.steps);
+    for (index, parameter) in parameters.iter_mut().enumerate() {
+      let gradient: f64 = if self.decoupled { gradients[index] } else { decayed(gradients[index], *parameter, self.weight_decay) };
+      self.first_moment[index] = self.beta1 * self.first_moment[index] + (1.0 - self.beta1) * gradient;
+      self.second_moment[index] = self.beta2 * self.second_moment[index] + (1.0 - self.beta2) * gradient * gradient;
+      let first: f64 = self.first_moment[index] / first_correction;
+      let second: f64 = self.second_moment[index] / second_correction;
+      if self.decoupled {
+        *parameter -= learning_rate * self.weight_decay * *parameter;
+      }
+      *parameter -= learning_rate * first / (second.sqrt() + ADAM_EPSILON);
+    }
+  }
+
+  // the step count, then the first moments, then the second moments
+  fn state(&self) -> Vec<f64> {
+    let mut state: Vec<f64> = vec![self.steps as f64];
+    state.extend(self.first_moment.iter());
+    state.extend(self.second_moment.iter());
+    state
+  }
+
+  fn restore_state(&mut self, state: &[f64]) -> Result<(), String> {
+    if state.len().is_multiple_of(2) {
+      return Err(format!("adam state needs a step count and two moments per parameter, not {} values", state.len()));
+    }
+    let parameters: usize = state.len() / 2;
+    self.steps = state[0] as i32;
+    self.first_moment = state[1..1 + parameters].to_vec();
+    self.second_moment = state[1 + parameters..].to_vec();
+    Ok(())
+  }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum LearningRateSchedule {
+  Constant,
+  // multiplies the rate by `factor` every `every` steps
+  Step { every: usize, factor: f64 },
+  // follows half a cosine from the base rate down to `min_rate` over `total_steps`
+  Cosine { total_steps: usize, min_rate: f64 }
+}
+
+// the rate for optimiser step `step`, counting from 0. during the first `warmup_steps` it
+// climbs linearly towards what the schedule gives
+pub fn learning_rate_at(base_rate: f64, schedule: LearningRateSchedule, warmup_steps: usize, step: usize) -> f64 {
+  let scheduled: f64 = match schedule {
+    LearningRateSchedule::Constant => base_rate,
+    LearningRateSchedule::Step { every, factor } => base_rate * factor.powi((step / every.max(1)) as i32),
+    LearningRateSchedule::Cosine { total_steps, min_rate } => {
+      let progress: f64 = (step as f64 / total_steps.max(1) as f64).min(1.0);
+      min_rate + (base_rate - min_rate) * 0.5 * (1.0 + (std::f64::consts::PI * progress).cos())
+    }
+  };
+  if step < warmup_steps {
+    scheduled * (step + 1) as f64 / warmup_steps as f64
+  } else {
+    scheduled
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  const KINDS: [OptimizerKind; 5] = [
+    OptimizerKind::Sgd,
+    OptimizerKind::Momentum { momentum: 0.9, nesterov: false },
+    OptimizerKind::Momentum { momentum: 0.9, nesterov: true },
+    OptimizerKind::Adam { beta1: 0.9, beta2: 0.999 },
+    OptimizerKind::AdamW { beta1: 0.9, beta2: 0.999 }
+  ];
+
+  // minimises (x - 3)^2 + (y + 1)^2
+  fn minimise(optimizer: &mut dyn Optimizer, learning_rate: f64) -> [f64; 2] {
+    let mut parameters: [f64; 2] = [0.0, 0.0];
+    for _step in 0..500 {
+      let gradients: [f64; 2] = [2.0 * (parameters[0] - 3.0), 2.0 * (parameters[1] + 1.0)];
+      optimizer.step(&mut parameters, &gradients, learning_rate);
+    }
+    parameters
+  }
+
+  #[test]
+  fn every_optimizer_finds_the_minimum() {
+    for kind in KINDS {
+      let parameters: [f64; 2] = minimise(&mut *make_optimizer(kind, 0.0), 0.05);
+      assert!((parameters[0] - 3.0).abs() < 1e-3 && (parameters[1] + 1.0).abs() < 1e-3, "{:?}: {:?}", kind, parameters);
+    }
+  }
+
+  #[test]
+  fn weight_decay_pulls_towards_zero() {
+    for kind in KINDS {
+      let parameters: [f64; 2] = minimise(&mut *make_optimizer(kind, 0.5), 0.05);
+      assert!(parameters[0] < 2.95 && parameters[1] > -0.95, "{:?}: {:?}", kind, parameters);
+    }
+  }
+
+  #[test]
+  fn adam_steps_are_about_the_learning_rate() {
+    let mut adam: Adam = make_adam(0.9, 0.999, 0.0, false);
+    let mut parameters: [f64; 1] = [0.0];
+    adam.step(&mut parameters, &[1000.0], 0.01);
+    assert!((parameters[0] + 0.01).abs() < 1e-6);
+  }
+
+  #[test]
+  fn restored_state_continues_the_same_steps() {
+    for kind in KINDS {
+      let mut optimizer: Box<dyn Optimizer> = make_optimizer(kind, 0.1);
+      let mut parameters: [f64; 2] = [0.5, -0.5];
+      for _step in 0..5 {
+        optimizer.step(&mut parameters, &[1.0, -2.0], 0.05);
+      }
+      let mut restored: Box<dyn Optimizer> = make_optimizer(kind, 0.1);
+      restored.restore_state(&optimizer.state()).unwrap();
+      let mut copy: [f64; 2] = parameters;
+      optimizer.step(&mut parameters, &[0.3, 0.7], 0.05);
+      restored.step(&mut copy, &[0.3, 0.7], 0.05);
+      assert!(parameters == copy, "{:?}", kind);
+    }
+    assert!(make_optimizer(OptimizerKind::Sgd, 0.0).restore_state(&[1.0]).is_err());
+    assert!(make_adam(0.9, 0.999, 0.0, false).restore_state(&[1.0, 2.0]).is_err());
+  }
+
+  #[test]
+  fn schedules_shape_the_rate() {
+    assert!(learning_rate_at(0.1, LearningRateSchedule::Constant, 0, 1000) == 0.1);
+    let step = LearningRateSchedule::Step { every: 10, factor: 0.5 };
+    assert!(learning_rate_at(0.1, step, 0, 9) == 0.1);
+    assert!(learning_rate_at(0.1, step, 0, 25) == 0.025);
+    let cosine = LearningRateSchedule::Cosine { total_steps: 100, min_rate: 0.01 };
+    assert!(learning_rate_at(0.1, cosine, 0, 0) == 0.1);
+    assert!((learning_rate_at(0.1, cosine, 0, 50) - 0.055).abs() < 1e-12);
+    assert!((learning_rate_at(0.1, cosine, 0, 100) - 0.01).abs() < 1e-12);
+    assert!((learning_rate_at(0.1, LearningRateSchedule::Constant, 4, 0) - 0.025).abs() < 1e-12);
+    assert!(learning_rate_at(0.1, LearningRateSchedule::Constant, 4, 4) == 0.1);
+  }
+}
@@ -0,0 +1,148 @@
+use crate::board::get_ally_occupation;
+use crate::constants::*;
+use crate::evaluator::Position;
+use crate::r#move::states_for_turn;
+use super::encoding::InputEncoder;
+
+// the policy head's move space: one entry per from and to square, then every promotion by
+// the file it starts on, the way the pawn goes (left, straight or right) and the piece. a
+// queening counts as a promotion rather than a plain from-to move
+const SQUARE_MOVES: usize = 64 * 64;
+const PROMOTION_PIECES: [u8; 4] = [WQUEEN, WROOK, WBISHOP, WKNIGHT];
+pub const POLICY_SIZE: usize = SQUARE_MOVES + 8 * 3 * PROMOTION_PIECES.len();
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct PolicyMove {
+  pub from: u8,
+  pub to: u8,
+  // the white slice of the piece a pawn becomes
+  pub promotion: Option<u8>
+}
+
+fn slice_on_square(state: [u64; 13], square: u64) -> Option<u8> {
+  (0..12u8).find(|slice_index| state[*slice_index as usize] & square != 0)
+}
+
+// the move that takes `parent` to `child` for the side moving on `turn_number`, when exactly
+// one of its pieces left one square for another
+pub fn move_between(parent: [u64; 13], child: [u64; 13], turn_number: u8) -> Option<PolicyMove> {
+  let before: u64 = get_ally_occupation(parent, turn_number);
+  let after: u64 = get_ally_occupation(child, turn_number);
+  let (from, to) = (before & !after, after & !before);
+  if from.count_ones() != 1 || to.count_ones() != 1 {
+    return None;
+  }
+  let moved: u8 = slice_on_square(parent, from)? % 6;
+  let arrived: u8 = slice_on_square(child, to)? % 6;
+  Some(PolicyMove {
+    from: from.trailing_zeros() as u8,
+    to: to.trailing_zeros() as u8,
+    promotion: if moved == WPAWN && arrived != WPAWN { Some(arrived) } else { None }
+  })
+}
+
+// where `policy_move` sits in the move space. `flip` mirrors the board vertically, for
+// networks that see the board from the side to move
+pub fn policy_index(policy_move: &PolicyMove, flip: bool) -> Option<usize> {
+  let flip_mask: u8 = if flip { 56 } else { 0 };
+  let (from, to) = ((policy_move.from ^ flip_mask) as usize, (policy_move.to ^ flip_mask) as usize);
+  match policy_move.promotion {
+    None => Some(from * 64 + to),
+    Some(piece) => {
+      let direction: usize = (to % 8 + 1).checked_sub(from % 8).filter(|direction| *direction < 3)?;
+      let piece_index: usize = PROMOTION_PIECES.iter().position(|promotion| *promotion == piece)?;
+      Some(SQUARE_MOVES + ((from % 8) * 3 + direction) * PROMOTION_PIECES.len() + piece_index)
+    }
+  }
+}
+
+// whether the encoder's networks see the board flipped for `position`
+pub fn flips_for(encoder: InputEncoder, position: &Position) -> bool {
+  encoder.relative_to_mover() && position.turn_number.is_multiple_of(2)
+}
+
+// the move space index of every child of `parent`, None for a child no single move reaches
+pub fn child_indices(encoder: InputEncoder, parent: &Position, children: &[[u64; 13]]) -> Vec<Option<usize>> {
+  let flip: bool = flips_for(encoder, parent);
+  children.iter()
+    .map(|child| move_between(parent.state, *child, parent.turn_number).and_then(|policy_move| policy_index(&policy_move, flip)))
+    .collect()
+}
+
+// the positions a game passed through paired with the move space index of the move played
+// from each, with every move that could have been played instead. positions whose move
+// can't be encoded are left out
+pub struct PolicySample {
+  pub position: Position,
+  pub moves: Vec<usize>,
+  // index into `moves`
+  pub played: usize
+}
+
+pub fn policy_sample(encoder: InputEncoder, position: Position, next: [u64; 13]) -> Option<PolicySample> {
+  let children: Vec<[u64; 13]> = states_for_turn(position.state, position.turn_number);
+  let played_child: usize = children.iter().position(|child| *child == next)?;
+  let indices: Vec<Option<usize>> = child_indices(encoder, &position, &children);
+  let played_index: usize = indices[played_child]?;
+  let mut moves: Vec<usize> = indices.into_iter().flatten().collect();
+  moves.sort_unstable();
+  moves.dedup();
+  let played: usize = moves.iter().position(|index| *index == played_index)?;
+  Some(PolicySample { position, moves, played })
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::evaluator::make_position;
+  use crate::fen::state_from_fen;
+  use crate::game::setup_board;
+  use crate::notation::state_after_san;
+
+  #[test]
+  fn finds_the_move_between_positions() {
+    let start: [u64; 13] = setup_board();
+    let after: [u64; 13] = state_after_san(start, 1, "e4").unwrap();
+    let policy_move: PolicyMove = move_between(start, after, 1).unwrap();
+    assert!(policy_move.promotion.is_none());
+    assert!(policy_move.from != policy_move.to);
+    // black's mirrored reply lands on the same index when the board is flipped
+    let reply: [u64; 13] = state_after_san(after, 2, "e5").unwrap();
+    let black_move: PolicyMove = move_between(after, reply, 2).unwrap();
+    assert!(policy_index(&black_move, true) == policy_index(&policy_move, false));
+    assert!(move_between(start, start, 1).is_none());
+  }
+
+  #[test]
+  fn every_child_has_its_own_index() {
+    let position: Position = make_position(setup_board(), 1);
+    let children: Vec<[u64; 13]> = states_for_turn(position.state, 1);
+    let mut indices: Vec<usize> = child_indices(InputEncoder::Board768, &position, &children).into_iter().map(|index| index.unwrap()).collect();
+    assert!(indices.iter().all(|index| *index < SQUARE_MOVES));
+    indices.sort_unstable();
+    indices.dedup();
+    assert!(indices.len() == children.len());
+  }
+
+  #[test]
+  fn promotions_have_their_own_entries() {
+    let queening = PolicyMove { from: 52, to: 60, promotion: Some(WQUEEN) };
+    let knighting = PolicyMove { from: 52, to: 61, promotion: Some(WKNIGHT) };
+    let queen: usize = policy_index(&queening, false).unwrap();
+    let knight: usize = policy_index(&knighting, false).unwrap();
+    assert!(queen >= SQUARE_MOVES && knight >= SQUARE_MOVES && queen != knight);
+    assert!(queen < POLICY_SIZE && knight < POLICY_SIZE);
+    assert!(policy_index(&PolicyMove { from: 52, to: 62, promotion: Some(WQUEEN) }, false).is_none());
+  }
+
+  #[test]
+  fn samples_point_at_the_move_played() {
+    let (state, turn_number) = state_from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w - - 0 1").unwrap();
+    let next: [u64; 13] = state_after_san(state, turn_number, "Nf3").unwrap();
+    let sample: PolicySample = policy_sample(InputEncoder::Board768, make_position(state, turn_number), next).unwrap();
+    assert!(sample.moves.len() == states_for_turn(state, turn_number).len());
+    let played: PolicyMove = move_between(state, next, turn_number).unwrap();
+    assert!(sample.moves[sample.played] == policy_index(&played, false).unwrap());
+    assert!(policy_sample(InputEncoder::Board768, make_position(state, turn_number), state).is_none());
+  }
+}
@@ -0,0 +1,256 @@
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use crate::bot::{basic_eval, king_captured, make_bot_with_config, plain_search_config, Bot};
+use crate::constants::*;
+use crate::evaluator::{make_position, Position};
+use crate::game::setup_board;
+use crate::r#move::states_for_turn;
+use super::eval::{make_net_evaluator, training_target};
+use super::net::Net;
+use super::network_storage::{load_network, write_network_to_file, NetworkOptions};
+use super::train::{make_trainer, Trainer, TrainingConfig};
+
+// self-play reinforcement learning. the network plays itself and learns the lambda-return
+// of each position it reached: the search scores of the positions after it, each weighted
+// by lambda once more than the last, with the game's result at the end. every generation
+// the network plays a gating match against the best one so far and replaces it on a win
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TdConfig {
+  pub games: usize,
+  pub depth: u8,
+  // 0 learns from the next position's score alone, 1 from the result alone
+  pub lambda: f64,
+  // random moves at the start of every game
+  pub random_plies: usize,
+  // chance of a random move after those, so the network sees where its own moves don't go
+  pub exploration: f64,
+  // games still going after this many plies are adjudicated on material
+  pub max_plies: usize,
+  pub adjudication_material: f64,
+  // self-play games between gating matches
+  pub games_per_generation: usize,
+  pub gate_games: usize,
+  // share of the gating points the new network needs to be promoted
+  pub gate_threshold: f64,
+  pub seed: u64
+}
+
+pub fn default_td_config() -> TdConfig {
+  TdConfig {
+    games: 100,
+    depth: 1,
+    lambda: 0.7,
+    random_plies: 4,
+    exploration: 0.1,
+    max_plies: 160,
+    adjudication_material: 3.0,
+    games_per_generation: 20,
+    gate_games: 10,
+    gate_threshold: 0.55,
+    seed: 0
+  }
+}
+
+// the positions of one self-play game with the search score of each, from white's side
+#[derive(Clone, PartialEq, Debug)]
+pub struct TdGame {
+  pub positions: Vec<Position>,
+  pub scores: Vec<f64>,
+  // white's score, 1.0 for a white win
+  pub result: f64
+}
+
+// white's score for the position a game stopped in
+fn final_result(state: [u64; 13], config: &TdConfig) -> f64 {
+  if state[WKING as usize] == 0 {
+    0.0
+  } else if state[BKING as usize] == 0 {
+    1.0
+  } else {
+    let material: f64 = basic_eval(state);
+    if material > config.adjudication_material { 1.0 } else if material < -config.adjudication_material { 0.0 } else { 0.5 }
+  }
+}
+
+pub fn net_bot(net: &Net, depth: u8) -> Bot {
+  make_bot_with_config(Box::new(make_net_evaluator(net.clone())), depth, plain_search_config())
+}
+
+// every position is searched, so even an exploring move leaves a score to learn from
+pub fn self_play_td_game(bot: &Bot, config: &TdConfig, rng: &mut StdRng) -> TdGame {
+  let mut state: [u64; 13] = setup_board();
+  let mut game: TdGame = TdGame { positions: Vec::new(), scores: Vec::new(), result: 0.5 };
+  // turn numbers are a u8
+  let max_plies: usize = config.max_plies.min(240);
+  for (ply, turn_number) in (0..max_plies).zip(1u8..) {
+    if king_captured(state) {
+      break;
+    }
+    let children: Vec<[u64; 13]> = states_for_turn(state, turn_number);
+    if children.is_empty() {
+      return game;
+    }
+    let (score, best) = bot.search(state, turn_number);
+    game.positions.push(make_position(state, turn_number));
+    game.scores.push(score.clamp(-1.0, 1.0));
+    state = if ply < config.random_plies || rng.gen::<f64>() < config.exploration {
+      children[rng.gen_range(0..children.len())]
+    } else {
+      best
+    };
+  }
+  game.result = final_result(state, config);
+  game
+}
+
+// the lambda-return of every position, white's side from -1 to 1. each target leans on the
+// next position's score and the next position's own target, the last on `outcome`
+pub fn lambda_targets(scores: &[f64], outcome: f64, lambda: f64) -> Vec<f64> {
+  let mut targets: Vec<f64> = vec![0.0; scores.len()];
+  let mut target: f64 = outcome;
+  for index in (0..scores.len()).rev() {
+    targets[index] = target;
+    target = (1.0 - lambda) * scores[index] + lambda * target;
+  }
+  targets
+}
+
+// one game between two bots from `opening`, white's score
+fn play_gating_game(white: &Bot, black: &Bot, opening: &[[u64; 13]], config: &TdConfig) -> f64 {
+  let mut state: [u64; 13] = opening.last().copied().unwrap_or_else(setup_board);
+  let first_turn: u8 = opening.len() as u8 + 1;
+  for turn_number in first_turn..first_turn.saturating_add(config.max_plies.min(240) as u8) {
+    if king_captured(state) || states_for_turn(state, turn_number).is_empty() {
+      break;
+    }
+    let bot: &Bot = if turn_number % 2 == 1 { white } else { black };
+    state = bot.search(state, turn_number).1;
+  }
+  final_result(state, config)
+}
+
+fn random_opening(config: &TdConfig, rng: &mut StdRng) -> Vec<[u64; 13]> {
+  let mut opening: Vec<[u64; 13]> = Vec::new();
+  let mut state: [u64; 13] = setup_board();
+  for turn_number in 1..=config.random_plies as u8 {
+    let children: Vec<[u64; 13]> = states_for_turn(state, turn_number);
+    if children.is_empty() {
+      break;
+    }
+    state = children[rng.gen_range(0..children.len())];
+    opening.push(state);
+  }
+  opening
+}
+
+// the candidate's share of the points against the best network, each opening played once
+// from either side
+pub fn gate(candidate: &Net, best: &Net, config: &TdConfig, rng: &mut StdRng) -> f64 {
+  let candidate_bot: Bot = net_bot(candidate, config.depth);
+  let best_bot: Bot = net_bot(best, config.depth);
+  let mut points: f64 = 0.0;
+  let mut opening: Vec<[u64; 13]> = Vec::new();
+  for game in 0..config.gate_games {
+    if game % 2 == 0 {
+      opening = random_opening(config, rng);
+      points += play_gating_game(&candidate_bot, &best_bot, &opening, config);
+    } else {
+      points += 1.0 - play_gating_game(&best_bot, &candidate_bot, &opening, config);
+    }
+  }
+  points / config.gate_games.max(1) as f64
+}
+
+// fits the network to a game's lambda-returns
+pub fn learn_from_game(net: &mut Net, trainer: &mut Trainer, game: &TdGame, lambda: f64) {
+  let targets: Vec<f64> = lambda_targets(&game.scores, 2.0 * game.result - 1.0, lambda);
+  for (position, target) in game.positions.iter().zip(targets.iter()) {
+    let input: Vec<f64> = net.encoder.encode(position);
+    let target: f64 = training_target(net.encoder, position, *target);
+    trainer.train_sample(net, input, target);
+  }
+  trainer.finish_batch(net);
+}
+
+// trains the network at the options' path by self-play, writing it back there each time it
+// passes a gating match. returns how many times it did
+pub fn train_by_self_play(config: TdConfig, training: TrainingConfig, options: &NetworkOptions) -> Result<usize, String> {
+  let mut best: Net = load_network(options)?;
+  let mut candidate: Net = best.clone();
+  let mut trainer: Trainer = make_trainer(training);
+  let mut rng: StdRng = StdRng::seed_from_u64(config.seed);
+  let mut promotions: usize = 0;
+  for game_number in 0..config.games {
+    let game: TdGame = self_play_td_game(&net_bot(&candidate, config.depth), &config, &mut rng);
+    learn_from_game(&mut candidate, &mut trainer, &game, config.lambda);
+    println!("Self-play game #{}: {} positions, white scored {}", game_number + 1, game.positions.len(), game.result);
+    if (game_number + 1) % config.games_per_generation.max(1) == 0 {
+      let score: f64 = gate(&candidate, &best, &config, &mut rng);
+      if score >= config.gate_threshold {
+        best = candidate.clone();
+        write_network_to_file(best.clone(), &options.path)?;
+        promotions += 1;
+        println!("Gating: {:.2} of the points, promoted to {}", score, options.path);
+      } else {
+        println!("Gating: {:.2} of the points, kept the previous best", score);
+      }
+    }
+  }
+  Ok(promotions)
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::network::activation::Activation;
+  use crate::network::train::default_training_config;
+
+  fn small_net() -> Net {
+    Net::random_with_widths(vec![0.0; 768], &[8], Activation::Tanh, 0.0)
+  }
+
+  fn short_games() -> TdConfig {
+    TdConfig { max_plies: 30, depth: 0, gate_games: 4, ..default_td_config() }
+  }
+
+  #[test]
+  fn lambda_returns_run_from_next_score_to_result() {
+    let scores: [f64; 3] = [0.2, 0.4, -0.6];
+    assert!(lambda_targets(&scores, 1.0, 0.0) == vec![0.4, -0.6, 1.0]);
+    assert!(lambda_targets(&scores, 1.0, 1.0) == vec![1.0, 1.0, 1.0]);
+    let halfway: Vec<f64> = lambda_targets(&scores, 1.0, 0.5);
+    assert!(halfway[2] == 1.0 && halfway[1] == 0.2 && (halfway[0] - 0.3).abs() < 1e-12);
+    assert!(lambda_targets(&[], 0.0, 0.5).is_empty());
+  }
+
+  #[test]
+  fn self_play_scores_every_position() {
+    let config: TdConfig = short_games();
+    let game: TdGame = self_play_td_game(&net_bot(&small_net(), config.depth), &config, &mut StdRng::seed_from_u64(1));
+    assert!(!game.positions.is_empty() && game.positions.len() == game.scores.len());
+    assert!(game.positions[0] == make_position(setup_board(), 1));
+    assert!(game.scores.iter().all(|score| (-1.0..=1.0).contains(score)));
+    assert!([0.0, 0.5, 1.0].contains(&game.result));
+  }
+
+  #[test]
+  fn learning_moves_towards_the_returns() {
+    let config: TdConfig = TdConfig { lambda: 1.0, ..short_games() };
+    let mut net: Net = small_net();
+    let game: TdGame = TdGame { positions: vec![make_position(setup_board(), 1)], scores: vec![0.0], result: 1.0 };
+    let before: f64 = net.forward_prop_to_value(net.encoder.encode(&game.positions[0]));
+    let mut trainer: Trainer = make_trainer(TrainingConfig { learning_rate: 0.05, ..default_training_config() });
+    for _round in 0..20 {
+      learn_from_game(&mut net, &mut trainer, &game, config.lambda);
+    }
+    assert!(net.forward_prop_to_value(net.encoder.encode(&game.positions[0])) > before);
+  }
+
+  #[test]
+  fn gating_splits_the_points() {
+    let config: TdConfig = short_games();
+    let net: Net = small_net();
+    let score: f64 = gate(&net, &net, &config, &mut StdRng::seed_from_u64(2));
+    assert!((0.0..=1.0).contains(&score));
+  }
+}
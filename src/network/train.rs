@@ -1,41 +1,465 @@
 use crate::{bot, game};
 use crate::game::{RANDOM_BOT, play_engine_turn_quiet};
 use super::{net, network_storage};
-use super::network_storage::{get_network_from_file, write_network_to_file};
+use super::network_storage::{load_network, write_file_atomically, write_network_to_file, NetworkOptions};
 use std::collections::HashMap;
-use super::eval::convert_positions_to_input_layer;
 use crate::network::net::Net;
+use super::eval::{make_net_evaluator, training_target, NetEvaluator};
+use crate::evaluator::{make_position, Position};
+use super::nnue::{write_nnue_file, NnueWeights};
+use super::encoding::InputEncoder;
+use super::optimizer::{learning_rate_at, make_optimizer, LearningRateSchedule, Optimizer, OptimizerKind};
+use super::dataset::{open_dataset_file, Sample};
+use super::policy::{policy_sample, PolicySample, POLICY_SIZE};
+use crate::pgn::{read_pgn_file, replay_game, ReplayedGame};
+use crate::zobrist;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
 
-lazy_static! {
-  pub static ref NET_FOR_EVAL: Net = get_network_from_file("text_network_storage.txt");
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TrainingConfig {
+  // samples whose gradients are averaged into each optimiser step
+  pub batch_size: usize,
+  pub optimizer: OptimizerKind,
+  pub learning_rate: f64,
+  pub schedule: LearningRateSchedule,
+  // optimiser steps over which the rate ramps up from near zero
+  pub warmup_steps: usize,
+  pub weight_decay: f64
 }
 
-pub fn learn_bot_eval(state: [u64; 13]) -> f64 {
-  let input = convert_positions_to_input_layer(state);
-  NET_FOR_EVAL.clone().forward_prop_to_value(input)
+// one sample per step with plain SGD, as training always did
+pub fn default_training_config() -> TrainingConfig {
+  TrainingConfig {
+    batch_size: 1,
+    optimizer: OptimizerKind::Sgd,
+    learning_rate: 0.02,
+    schedule: LearningRateSchedule::Constant,
+    warmup_steps: 0,
+    weight_decay: 0.0
+  }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct RunConfig {
+  // training stops after this epoch, counting the epochs of a resumed run
+  pub epochs: usize,
+  // share of the positions held out to measure validation loss
+  pub validation_fraction: f64,
+  // epochs without a better validation loss before training stops, 0 to never stop early
+  pub patience: usize,
+  // where each epoch's losses are appended as csv
+  pub log_path: Option<String>,
+  // picks up from the checkpoint and its optimiser state rather than starting over
+  pub resume: bool
+}
+
+pub fn default_run_config() -> RunConfig {
+  RunConfig {
+    epochs: 1,
+    validation_fraction: 0.1,
+    patience: 3,
+    log_path: None,
+    resume: false
+  }
+}
+
+// what a checkpoint needs besides the network to carry on training
+#[derive(Clone, PartialEq, Debug)]
+pub struct TrainingState {
+  pub epoch: usize,
+  pub steps: usize,
+  pub best_loss: f64,
+  // the optimiser kind's debug name, so a resume can't mix up their states
+  pub optimizer: String,
+  pub optimizer_state: Vec<f64>
+}
+
+// the checkpoint's training state sits next to its network
+pub fn training_state_path(checkpoint_path: &str) -> String {
+  format!("{}.state", checkpoint_path)
 }
 
-pub fn train_network_with_games(number_of_games: usize) {
-  let mut training_game_number = 0;
-  let mut net = get_network_from_file("text_network_storage.txt");
-  let mut states_and_evaluations: HashMap<[u64; 13], f64>;
-  for _game_number in 0..number_of_games {
-    println!("Training game #{}", training_game_number);
-    training_game_number += 1;
-    states_and_evaluations = get_random_game_states_with_adjustments();
-    for (state, eval) in states_and_evaluations {
-      net.run_data(convert_positions_to_input_layer(state), eval);
+pub fn write_training_state(state: &TrainingState, path: &str) -> Result<(), String> {
+  let mut text: String = format!("Epoch {}\nSteps {}\nBestLoss {}\nOptimizer {}\nState", state.epoch, state.steps, state.best_loss, state.optimizer);
+  for value in state.optimizer_state.iter() {
+    text.push(' ');
+    text.push_str(&value.to_string());
+  }
+  text.push('\n');
+  write_file_atomically(path, text.as_bytes())
+}
+
+pub fn read_training_state(path: &str) -> Result<TrainingState, String> {
+  let text: String = std::fs::read_to_string(path).map_err(|error| format!("couldn't read {}: {}", path, error))?;
+  let mut state: TrainingState = TrainingState { epoch: 0, steps: 0, best_loss: f64::INFINITY, optimizer: String::new(), optimizer_state: Vec::new() };
+  for (line_index, line) in text.lines().enumerate() {
+    let (name, value) = line.split_once(' ').unwrap_or((line, ""));
+    let bad_value = || format!("{} line {}: bad value '{}'", path, line_index + 1, value);
+    match name {
+      "Epoch" => state.epoch = value.parse().map_err(|_| bad_value())?,
+      "Steps" => state.steps = value.parse().map_err(|_| bad_value())?,
+      "BestLoss" => state.best_loss = value.parse().map_err(|_| bad_value())?,
+      "Optimizer" => state.optimizer = value.to_string(),
+      "State" => state.optimizer_state = value.split_whitespace().map(|number| number.parse()).collect::<Result<Vec<f64>, _>>().map_err(|_| bad_value())?,
+      "" => {},
+      _ => return Err(format!("{} line {}: unknown entry '{}'", path, line_index + 1, name))
     }
   }
-  write_network_to_file(net, "tanh", "tanh_der_clipped", "text_network_storage.txt");
+  Ok(state)
+}
+
+// feeds samples into a net a mini-batch at a time, stepping the optimiser and the schedule
+pub struct Trainer {
+  config: TrainingConfig,
+  optimizer: Box<dyn Optimizer>,
+  steps: usize
+}
+
+pub fn make_trainer(config: TrainingConfig) -> Trainer {
+  Trainer {
+    config,
+    optimizer: make_optimizer(config.optimizer, config.weight_decay),
+    steps: 0
+  }
 }
 
-fn train_network_with_one_game() {
-  let mut net = get_network_from_file("text_network_storage.txt");
+impl Trainer {
+  pub fn train_sample(&mut self, net: &mut Net, input: Vec<f64>, target: f64) {
+    net.accumulate_gradients(input, target);
+    if net.accumulated_samples >= self.config.batch_size {
+      self.finish_batch(net);
+    }
+  }
+
+  // like `train_sample` for the policy head, see `Net::accumulate_policy_gradients`
+  pub fn train_policy_sample(&mut self, net: &mut Net, input: Vec<f64>, target: Option<f64>, moves: &[usize], played: usize) -> Vec<f64> {
+    let probabilities: Vec<f64> = net.accumulate_policy_gradients(input, target, moves, played);
+    if net.accumulated_samples >= self.config.batch_size {
+      self.finish_batch(net);
+    }
+    probabilities
+  }
+
+  // applies whatever is left of a partly filled batch
+  pub fn finish_batch(&mut self, net: &mut Net) {
+    if net.accumulated_samples == 0 {
+      return;
+    }
+    let learning_rate: f64 = learning_rate_at(self.config.learning_rate, self.config.schedule, self.config.warmup_steps, self.steps);
+    net.apply_gradients(&mut *self.optimizer, learning_rate);
+    self.steps += 1;
+  }
+
+  pub fn training_state(&self, epoch: usize, best_loss: f64) -> TrainingState {
+    TrainingState {
+      epoch,
+      steps: self.steps,
+      best_loss,
+      optimizer: format!("{:?}", self.config.optimizer),
+      optimizer_state: self.optimizer.state()
+    }
+  }
+
+  pub fn restore(&mut self, state: &TrainingState) -> Result<(), String> {
+    let optimizer: String = format!("{:?}", self.config.optimizer);
+    if state.optimizer != optimizer {
+      return Err(format!("the checkpoint was trained with {}, not {}", state.optimizer, optimizer));
+    }
+    self.optimizer.restore_state(&state.optimizer_state)?;
+    self.steps = state.steps;
+    Ok(())
+  }
+}
+
+// a position and its target from white's side, -1 to 1
+pub type TrainingSample = (Position, f64);
+pub type SampleStream = Box<dyn Iterator<Item = Result<TrainingSample, String>>>;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct FitSummary {
+  // the last epoch trained, counting from 1
+  pub epochs: usize,
+  pub best_epoch: usize,
+  pub best_loss: f64,
+  pub stopped_early: bool
+}
+
+// a position's hash decides which side of the split it falls on, so the split is the same
+// every epoch and every run
+pub fn is_validation_position(position: &Position, validation_fraction: f64) -> bool {
+  zobrist::hash(position.state, position.turn_number) % 10_000 < (validation_fraction * 10_000.0) as u64
+}
+
+fn mean_loss(loss: f64, samples: usize) -> f64 {
+  if samples == 0 { f64::NAN } else { loss / samples as f64 }
+}
+
+fn open_loss_log(path: &str, append: bool) -> Result<File, String> {
+  let existing: bool = append && std::path::Path::new(path).exists();
+  let mut file: File = OpenOptions::new().create(true).append(existing).write(true).truncate(!existing).open(path)
+    .map_err(|error| format!("couldn't open {}: {}", path, error))?;
+  if !existing {
+    writeln!(file, "epoch,training_samples,training_loss,validation_samples,validation_loss").map_err(|error| format!("couldn't write {}: {}", path, error))?;
+  }
+  Ok(file)
+}
+
+// what the epoch loop needs from the model it trains: a step on one training sample and a
+// score for one held-out sample, both returning the sample's loss from before any step, and
+// a way to keep the model after an improvement
+trait EpochModel {
+  fn train_one(&mut self, position: &Position, white_score: f64) -> f64;
+  fn score_one(&mut self, position: &Position, white_score: f64) -> f64;
+  // runs after the last sample of an epoch
+  fn finish_epoch(&mut self) {}
+  fn keep_best(&mut self, epoch: usize, loss: f64) -> Result<(), String>;
+}
+
+// runs epochs over the stream `samples` opens from after `summary.epochs` until `run.epochs`,
+// or until the validation loss stops improving on `summary.best_loss`, logging each epoch's
+// losses and keeping every improvement. without a validation split the training loss stands
+// in for it
+fn run_epochs(model: &mut dyn EpochModel, run: &RunConfig, mut summary: FitSummary, append_log: bool, samples: &mut dyn FnMut() -> Result<SampleStream, String>) -> Result<FitSummary, String> {
+  let mut log: Option<File> = match &run.log_path {
+    Some(path) => Some(open_loss_log(path, append_log)?),
+    None => None
+  };
+  for epoch in summary.epochs + 1..=run.epochs {
+    let (mut training_loss, mut training_samples) = (0.0, 0usize);
+    let (mut validation_loss, mut validation_samples) = (0.0, 0usize);
+    for sample in samples()? {
+      let (position, white_score) = sample?;
+      if is_validation_position(&position, run.validation_fraction) {
+        validation_loss += model.score_one(&position, white_score);
+        validation_samples += 1;
+      } else {
+        training_loss += model.train_one(&position, white_score);
+        training_samples += 1;
+      }
+    }
+    model.finish_epoch();
+    let training_mean: f64 = mean_loss(training_loss, training_samples);
+    let validation_mean: f64 = mean_loss(validation_loss, validation_samples);
+    println!("Epoch {}: training loss {:.5} over {} samples, validation loss {:.5} over {} samples", epoch, training_mean, training_samples, validation_mean, validation_samples);
+    if let (Some(file), Some(path)) = (log.as_mut(), run.log_path.as_ref()) {
+      writeln!(file, "{},{},{},{},{}", epoch, training_samples, training_mean, validation_samples, validation_mean).map_err(|error| format!("couldn't write {}: {}", path, error))?;
+    }
+
+    summary.epochs = epoch;
+    let loss: f64 = if validation_samples > 0 { validation_mean } else { training_mean };
+    if loss < summary.best_loss {
+      summary.best_loss = loss;
+      summary.best_epoch = epoch;
+      model.keep_best(epoch, loss)?;
+    } else if run.patience > 0 && epoch - summary.best_epoch >= run.patience {
+      println!("Stopping early, no better validation loss since epoch {}", summary.best_epoch);
+      summary.stopped_early = true;
+      break;
+    }
+  }
+  Ok(summary)
+}
+
+// the loss of a model before any training, as `run_epochs` would measure it: over the
+// validation split, or over every sample when there's none
+fn starting_loss(model: &mut dyn EpochModel, run: &RunConfig, samples: &mut dyn FnMut() -> Result<SampleStream, String>) -> Result<f64, String> {
+  let (mut loss, mut scored) = (0.0, 0usize);
+  let (mut validation_loss, mut validation_samples) = (0.0, 0usize);
+  for sample in samples()? {
+    let (position, white_score) = sample?;
+    let sample_loss: f64 = model.score_one(&position, white_score);
+    loss += sample_loss;
+    scored += 1;
+    if is_validation_position(&position, run.validation_fraction) {
+      validation_loss += sample_loss;
+      validation_samples += 1;
+    }
+  }
+  Ok(if validation_samples > 0 { mean_loss(validation_loss, validation_samples) } else { mean_loss(loss, scored) })
+}
+
+struct NetFit<'a> {
+  net: &'a mut Net,
+  trainer: Trainer,
+  checkpoint_path: &'a str
+}
+
+impl EpochModel for NetFit<'_> {
+  fn train_one(&mut self, position: &Position, white_score: f64) -> f64 {
+    let target: f64 = training_target(self.net.encoder, position, white_score);
+    self.trainer.train_sample(self.net, self.net.encoder.encode(position), target);
+    0.5 * (target - self.net.get_final_value()).powi(2)
+  }
+
+  fn score_one(&mut self, position: &Position, white_score: f64) -> f64 {
+    let target: f64 = training_target(self.net.encoder, position, white_score);
+    self.net.get_loss(self.net.encoder.encode(position), target)
+  }
+
+  fn finish_epoch(&mut self) {
+    self.trainer.finish_batch(self.net);
+  }
+
+  fn keep_best(&mut self, epoch: usize, loss: f64) -> Result<(), String> {
+    write_network_to_file(self.net.clone(), self.checkpoint_path)?;
+    write_training_state(&self.trainer.training_state(epoch, loss), &training_state_path(self.checkpoint_path))
+  }
+}
+
+// `run_epochs` on a network. every improvement is written to `checkpoint_path` along with the
+// training state, so the file always holds the best network seen and a run can resume from it
+pub fn fit(net: &mut Net, config: TrainingConfig, run: &RunConfig, checkpoint_path: &str, samples: &mut dyn FnMut() -> Result<SampleStream, String>) -> Result<FitSummary, String> {
+  let mut trainer: Trainer = make_trainer(config);
+  let mut summary: FitSummary = FitSummary { epochs: 0, best_epoch: 0, best_loss: f64::INFINITY, stopped_early: false };
+  if run.resume {
+    let state: TrainingState = read_training_state(&training_state_path(checkpoint_path))?;
+    trainer.restore(&state)?;
+    summary = FitSummary { epochs: state.epoch, best_epoch: state.epoch, best_loss: state.best_loss, stopped_early: false };
+    println!("Resuming after epoch {}, best loss {:.5}", state.epoch, state.best_loss);
+  }
+  run_epochs(&mut NetFit { net, trainer, checkpoint_path }, run, summary, run.resume, samples)
+}
+
+pub fn make_learn_bot_evaluator(options: &NetworkOptions) -> Result<NetEvaluator, String> {
+  Ok(make_net_evaluator(load_network(options)?))
+}
+
+// plays `number_of_games` random games and trains the network at the options' path on their
+// positions, keeping the best checkpoint there
+pub fn train_network_with_games(number_of_games: usize, config: TrainingConfig, run: &RunConfig, options: &NetworkOptions) -> Result<FitSummary, String> {
+  let mut net = load_network(options)?;
+  let mut positions: Vec<TrainingSample> = Vec::new();
+  for game_number in 0..number_of_games {
+    println!("Training game #{}", game_number);
+    positions.extend(get_random_game_states_with_adjustments());
+  }
+  fit(&mut net, config, run, &options.path, &mut || Ok(Box::new(positions.clone().into_iter().map(Ok)) as SampleStream))
+}
+
+// white's side target for a dataset sample: `result_weight` of the game result, mapped to
+// -1..1, and the rest from the search score where the sample has one
+pub fn sample_target(sample: &Sample, result_weight: f64) -> f64 {
+  let result: f64 = 2.0 * sample.result - 1.0;
+  match sample.score {
+    Some(score) => result_weight * result + (1.0 - result_weight) * normalize_evaluation(score),
+    None => result
+  }
+}
+
+// one pass over the dataset at `path`, with the targets of `sample_target`
+fn dataset_stream(path: &str, result_weight: f64) -> Result<SampleStream, String> {
+  let dataset_path: String = path.to_string();
+  let samples = open_dataset_file(path)?.map(move |sample| match sample {
+    Ok(sample) => Ok((make_position(sample.state, sample.turn_number), sample_target(&sample, result_weight))),
+    Err(error) => Err(format!("{}: {}", dataset_path, error))
+  });
+  Ok(Box::new(samples))
+}
+
+// streams the dataset through the network at the options' path once an epoch, keeping the
+// best checkpoint there
+pub fn train_network_on_dataset(path: &str, config: TrainingConfig, run: &RunConfig, result_weight: f64, options: &NetworkOptions) -> Result<FitSummary, String> {
+  let mut net = load_network(options)?;
+  fit(&mut net, config, run, &options.path, &mut || dataset_stream(path, result_weight))
+}
+
+struct NnueFit<'a> {
+  weights: &'a mut NnueWeights,
+  learning_rate: f32,
+  out_path: &'a str
+}
+
+impl EpochModel for NnueFit<'_> {
+  fn train_one(&mut self, position: &Position, white_score: f64) -> f64 {
+    self.weights.train_sample(position, white_score, self.learning_rate)
+  }
+
+  fn score_one(&mut self, position: &Position, white_score: f64) -> f64 {
+    0.5 * (self.weights.evaluate_float(position) - white_score).powi(2)
+  }
+
+  fn keep_best(&mut self, _epoch: usize, _loss: f64) -> Result<(), String> {
+    write_nnue_file(self.weights, self.out_path)
+  }
+}
+
+// `fit` for the NNUE, with plain gradient descent at `learning_rate`. the weights are
+// written to `out_path` on every improvement; there's no optimizer state to resume. when
+// `continuing` the weights are the ones already at `out_path`, so they're scored first and
+// only an epoch that beats them replaces the file, and the log is appended to
+pub fn fit_nnue(weights: &mut NnueWeights, continuing: bool, learning_rate: f32, run: &RunConfig, out_path: &str, samples: &mut dyn FnMut() -> Result<SampleStream, String>) -> Result<FitSummary, String> {
+  let mut model: NnueFit = NnueFit { weights, learning_rate, out_path };
+  let mut summary: FitSummary = FitSummary { epochs: 0, best_epoch: 0, best_loss: f64::INFINITY, stopped_early: false };
+  if continuing {
+    summary.best_loss = starting_loss(&mut model, run, samples)?;
+    println!("Continuing from the weights at {}, loss {:.5}", out_path, summary.best_loss);
+  }
+  run_epochs(&mut model, run, summary, continuing, samples)
+}
+
+// trains `weights` on a dataset and keeps the best of them at `out_path`, see `fit_nnue`
+pub fn train_nnue_on_dataset(path: &str, out_path: &str, mut weights: NnueWeights, continuing: bool, learning_rate: f32, run: &RunConfig, result_weight: f64) -> Result<FitSummary, String> {
+  fit_nnue(&mut weights, continuing, learning_rate, run, out_path, &mut || dataset_stream(path, result_weight))
+}
+
+// the move played from every position of the games in `paths` that the move space can
+// hold, with white's score in the game when it has one
+pub fn policy_samples_from_pgn_files(paths: &[String], encoder: InputEncoder) -> Result<Vec<(PolicySample, Option<f64>)>, String> {
+  let mut samples: Vec<(PolicySample, Option<f64>)> = Vec::new();
+  for path in paths.iter() {
+    for (index, game) in read_pgn_file(path)?.iter().enumerate() {
+      let replayed: ReplayedGame = replay_game(game).map_err(|error| format!("{} game {}: {}", path, index + 1, error))?;
+      // the position after the last move isn't kept, so its move is lost
+      for pair in replayed.positions.windows(2) {
+        if let Some(sample) = policy_sample(encoder, make_position(pair[0].0, pair[0].1), pair[1].0) {
+          samples.push((sample, game.result.map(|result| 2.0 * result - 1.0)));
+        }
+      }
+    }
+  }
+  Ok(samples)
+}
+
+// trains the policy head of the network at the options' path on the moves played in PGN
+// games, and the output node on their results when `with_value` is set, then writes it
+// back there. a network without a policy head gets a new one
+pub fn train_policy_on_games(paths: &[String], epochs: usize, config: TrainingConfig, with_value: bool, options: &NetworkOptions) -> Result<(), String> {
+  let mut net = load_network(options)?;
+  match net.policy.as_ref().map(|policy| policy.outputs()) {
+    None => net.add_policy_head(POLICY_SIZE),
+    Some(POLICY_SIZE) => {},
+    Some(moves) => return Err(format!("the policy head has {} moves, not the {} of the move encoding", moves, POLICY_SIZE))
+  }
+  let samples: Vec<(PolicySample, Option<f64>)> = policy_samples_from_pgn_files(paths, net.encoder)?;
+  if samples.is_empty() {
+    return Err(String::from("no playable moves in the games given"));
+  }
+  let mut trainer: Trainer = make_trainer(config);
+  for epoch in 0..epochs {
+    let (mut cross_entropy, mut correct) = (0.0, 0usize);
+    for (sample, result) in samples.iter() {
+      let input: Vec<f64> = net.encoder.encode(&sample.position);
+      let target: Option<f64> = result.filter(|_| with_value).map(|result| training_target(net.encoder, &sample.position, result));
+      let probabilities: Vec<f64> = trainer.train_policy_sample(&mut net, input, target, &sample.moves, sample.played);
+      cross_entropy -= probabilities[sample.played].ln();
+      if probabilities.iter().all(|probability| *probability <= probabilities[sample.played]) {
+        correct += 1;
+      }
+    }
+    trainer.finish_batch(&mut net);
+    println!("Epoch {}: cross-entropy {:.4}, played move ranked first {:.1}% of {} positions", epoch + 1, cross_entropy / samples.len() as f64, 100.0 * correct as f64 / samples.len() as f64, samples.len());
+  }
+  write_network_to_file(net, &options.path)
+}
+
+fn train_network_with_one_game(options: &NetworkOptions) -> Result<(), String> {
+  let mut net = load_network(options)?;
   let states_and_evaluations = get_random_game_states_with_adjustments();
-  for (state, eval) in states_and_evaluations {
-    net.run_data(convert_positions_to_input_layer(state), eval);
+  for (position, eval) in states_and_evaluations {
+    net.run_data(net.encoder.encode(&position), training_target(net.encoder, &position, eval));
   }
+  Ok(())
 }
 
 fn normalize_evaluation(eval: f64) -> f64 {
@@ -50,20 +474,168 @@ fn normalize_evaluation(eval: f64) -> f64 {
   }
 }
 
-fn get_random_game_states_with_adjustments() -> HashMap<[u64; 13], f64> {
-  let mut states: Vec<[u64; 13]> = Vec::new();
+// positions of a random game, each valued from white's side
+fn get_random_game_states_with_adjustments() -> HashMap<Position, f64> {
+  let mut states: Vec<Position> = Vec::new();
   let mut state = game::setup_board();
   let mut turn_number: u8 = 1;
 
   for _move_number in 1..31 {
     play_engine_turn_quiet(&RANDOM_BOT, &mut state, &mut turn_number);
-    states.push(state.clone());
+    states.push(make_position(state, turn_number));
   }
   
-  let mut states_and_values: HashMap<[u64; 13], f64> = HashMap::new();
-  let final_evaluation = bot::center_squares_worth(states[states.len() - 1]);
+  let mut states_and_values: HashMap<Position, f64> = HashMap::new();
+  let final_evaluation = bot::center_squares_worth(states[states.len() - 1].state);
   for (state, index) in states.iter().zip(0..) {
     states_and_values.insert(*state, normalize_evaluation(final_evaluation * 0.75f64.powf(index as f64)));
   }
   states_and_values
+}
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::game::setup_board;
+  use crate::network::activation::Activation;
+  use crate::network::optimizer::OptimizerKind;
+  use crate::network::nnue::read_nnue_file;
+
+  fn small_net() -> Net {
+    Net::random_with_widths(vec![0.0; 768], &[8], Activation::Tanh, 0.0)
+  }
+
+  fn random_samples() -> Vec<TrainingSample> {
+    (0..3).flat_map(|_game| get_random_game_states_with_adjustments()).collect()
+  }
+
+  fn temp_path(name: &str) -> String {
+    std::env::temp_dir().join(name).to_str().unwrap().to_string()
+  }
+
+  fn log_lines(path: &str) -> usize {
+    std::fs::read_to_string(path).unwrap().lines().count()
+  }
+
+  #[test]
+  fn policy_training_learns_the_moves_played() {
+    let path: String = temp_path("policy_training.pgn");
+    std::fs::write(&path, "1. e4 e5 2. Nf3 Nc6 3. Bb5 a6 1-0\n\n1. e4 e5 2. Nf3 Nc6 3. Bc4 Bc5 1/2-1/2\n").unwrap();
+    let samples: Vec<(PolicySample, Option<f64>)> = policy_samples_from_pgn_files(std::slice::from_ref(&path), InputEncoder::Board768).unwrap();
+    // the last move of each game has no position after it
+    assert!(samples.len() == 10);
+    assert!(samples[0].1 == Some(1.0) && samples[5].1 == Some(0.0));
+
+    let mut net: Net = small_net();
+    net.add_policy_head(POLICY_SIZE);
+    let mut trainer: Trainer = make_trainer(TrainingConfig { learning_rate: 0.1, ..default_training_config() });
+    let loss = |net: &mut Net| samples.iter().map(|(sample, _)| net.policy_loss(net.encoder.encode(&sample.position), &sample.moves, sample.played)).sum::<f64>();
+    let before: f64 = loss(&mut net);
+    for _epoch in 0..5 {
+      for (sample, result) in samples.iter() {
+        let input: Vec<f64> = net.encoder.encode(&sample.position);
+        trainer.train_policy_sample(&mut net, input, *result, &sample.moves, sample.played);
+      }
+    }
+    assert!(loss(&mut net) < before);
+    let _ = std::fs::remove_file(path);
+  }
+
+  #[test]
+  fn training_state_round_trips() {
+    let path: String = temp_path("round_trip_training.state");
+    let state: TrainingState = TrainingState { epoch: 4, steps: 120, best_loss: 0.0125, optimizer: String::from("Adam { beta1: 0.9, beta2: 0.999 }"), optimizer_state: vec![3.0, 0.5, -0.25] };
+    write_training_state(&state, &path).unwrap();
+    assert!(read_training_state(&path).unwrap() == state);
+    std::fs::write(&path, "Epoch four\n").unwrap();
+    assert!(read_training_state(&path).is_err());
+    let _ = std::fs::remove_file(&path);
+  }
+
+  #[test]
+  fn stops_when_validation_loss_stalls() {
+    let checkpoint: String = temp_path("stalling_training.txt");
+    let log: String = temp_path("stalling_training.csv");
+    let samples: Vec<TrainingSample> = random_samples();
+    // a rate of zero never improves on the first epoch
+    let config: TrainingConfig = TrainingConfig { learning_rate: 0.0, ..default_training_config() };
+    let run: RunConfig = RunConfig { epochs: 10, validation_fraction: 0.5, patience: 2, log_path: Some(log.clone()), resume: false };
+    let summary: FitSummary = fit(&mut small_net(), config, &run, &checkpoint, &mut || Ok(Box::new(samples.clone().into_iter().map(Ok)) as SampleStream)).unwrap();
+    assert!(summary.stopped_early && summary.epochs == 3 && summary.best_epoch == 1);
+    assert!(log_lines(&log) == 4);
+    assert!(read_training_state(&training_state_path(&checkpoint)).unwrap().epoch == 1);
+    assert!(crate::network::network_storage::load_network_file(&checkpoint).is_ok());
+    for path in [checkpoint.clone(), training_state_path(&checkpoint), log] {
+      let _ = std::fs::remove_file(path);
+    }
+  }
+
+  #[test]
+  fn resumes_from_the_checkpoint() {
+    let checkpoint: String = temp_path("resumed_training.txt");
+    let log: String = temp_path("resumed_training.csv");
+    let samples: Vec<TrainingSample> = random_samples();
+    let config: TrainingConfig = TrainingConfig { optimizer: OptimizerKind::Adam { beta1: 0.9, beta2: 0.999 }, learning_rate: 0.001, ..default_training_config() };
+    let mut run: RunConfig = RunConfig { epochs: 2, validation_fraction: 0.0, patience: 0, log_path: Some(log.clone()), resume: false };
+    let mut stream = || Ok(Box::new(samples.clone().into_iter().map(Ok)) as SampleStream);
+    fit(&mut small_net(), config, &run, &checkpoint, &mut stream).unwrap();
+    let state: TrainingState = read_training_state(&training_state_path(&checkpoint)).unwrap();
+    assert!(state.steps > 0 && state.optimizer_state[0] == state.steps as f64);
+
+    run.epochs = 4;
+    run.resume = true;
+    let mut net: Net = crate::network::network_storage::load_network_file(&checkpoint).unwrap();
+    let summary: FitSummary = fit(&mut net, config, &run, &checkpoint, &mut stream).unwrap();
+    assert!(summary.epochs == 4 && !summary.stopped_early);
+    assert!(log_lines(&log) == 5);
+    assert!(read_training_state(&training_state_path(&checkpoint)).unwrap().steps > state.steps);
+
+    let sgd: TrainingConfig = default_training_config();
+    assert!(fit(&mut net, sgd, &run, &checkpoint, &mut stream).is_err());
+    for path in [checkpoint.clone(), training_state_path(&checkpoint), log] {
+      let _ = std::fs::remove_file(path);
+    }
+  }
+
+  #[test]
+  fn fits_the_nnue_and_keeps_the_best() {
+    let out: String = temp_path("fitted_nnue.bin");
+    // fixed positions and seeded weights, so every run takes the same steps
+    let (queen_up, turn_number) = crate::fen::state_from_fen("4k3/8/8/3q4/8/8/8/3RK3 w - -").unwrap();
+    let samples: Vec<TrainingSample> = vec![(make_position(setup_board(), 1), 0.0), (make_position(queen_up, turn_number), -0.8)];
+    let mut weights = crate::network::nnue::make_random_nnue_weights(InputEncoder::KingBuckets, 16, 4, 9);
+    let run: RunConfig = RunConfig { epochs: 3, validation_fraction: 0.0, patience: 0, log_path: None, resume: false };
+    let summary: FitSummary = fit_nnue(&mut weights, false, 0.01, &run, &out, &mut || Ok(Box::new(samples.clone().into_iter().map(Ok)) as SampleStream)).unwrap();
+    // small steps on two positions lower the loss every epoch, so the last is the best
+    assert!(summary.epochs == 3 && summary.best_epoch == 3);
+    assert!(read_nnue_file(&out).unwrap() == weights);
+    let _ = std::fs::remove_file(&out);
+  }
+
+  #[test]
+  fn keeps_existing_nnue_weights_that_no_epoch_beats() {
+    let out: String = temp_path("existing_nnue.bin");
+    let log: String = temp_path("existing_nnue.csv");
+    let samples: Vec<TrainingSample> = random_samples();
+    let mut weights: NnueWeights = crate::network::nnue::make_random_nnue_weights(InputEncoder::KingBuckets, 8, 4, 5);
+    write_nnue_file(&weights, &out).unwrap();
+    std::fs::write(&log, "epoch,training_samples,training_loss,validation_samples,validation_loss\n1,10,0.5,0,NaN\n").unwrap();
+    // every position is held out, so the epochs score exactly what the file already scores
+    let run: RunConfig = RunConfig { epochs: 2, validation_fraction: 1.0, patience: 0, log_path: Some(log.clone()), resume: false };
+    let summary: FitSummary = fit_nnue(&mut weights, true, 0.01, &run, &out, &mut || Ok(Box::new(samples.clone().into_iter().map(Ok)) as SampleStream)).unwrap();
+    assert!(summary.epochs == 2 && summary.best_epoch == 0 && summary.best_loss.is_finite());
+    assert!(log_lines(&log) == 4);
+    assert!(read_nnue_file(&out).unwrap() == weights);
+    for path in [out, log] {
+      let _ = std::fs::remove_file(path);
+    }
+  }
+
+  #[test]
+  fn targets_blend_result_and_score() {
+    let sample: Sample = Sample { state: setup_board(), turn_number: 1, result: 1.0, score: Some(-7.5), ply: 20 };
+    assert!(sample_target(&sample, 1.0) == 1.0);
+    assert!(sample_target(&sample, 0.0) == -0.5);
+    assert!(sample_target(&sample, 0.5) == 0.25);
+    assert!(sample_target(&Sample { score: None, result: 0.5, ..sample }, 0.0) == 0.0);
+  }
 }
\ No newline at end of file
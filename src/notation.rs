@@ -0,0 +1,185 @@
+use crate::constants::*;
+use crate::board::get_enemy_occupation;
+use crate::attacks::is_in_check;
+use crate::r#move::states_for_turn;
+use crate::utility::bit_to_index;
+
+// slice index, start square and end square of the move leading from `parent` to `child`
+pub fn move_from_states(parent: [u64; 13], child: [u64; 13], turn_number: u8) -> Option<(u8, u8, u8)> {
+  let slices = if turn_number % 2 == 1 { 0..6 } else { 6..12 };
+  for slice_index in slices {
+    let from: u64 = parent[slice_index as usize] & !child[slice_index as usize];
+    let to: u64 = child[slice_index as usize] & !parent[slice_index as usize];
+    if from != 0 && to != 0 {
+      return Some((slice_index, bit_to_index(from) - 1, bit_to_index(to) - 1));
+    }
+  }
+  None
+}
+
+pub fn square_name(square: u8) -> String {
+  format!("{}{}", (b'a' + square % 8) as char, square / 8 + 1)
+}
+
+fn piece_letter(slice_index: u8) -> Option<char> {
+  match slice_index % 6 {
+    0 => Some('R'),
+    1 => Some('B'),
+    2 => Some('Q'),
+    4 => Some('N'),
+    5 => Some('K'),
+    _ => None
+  }
+}
+
+fn is_capture(parent: [u64; 13], child: [u64; 13], turn_number: u8) -> bool {
+  get_enemy_occupation(parent, turn_number) & !get_enemy_occupation(child, turn_number) != 0
+}
+
+// coordinate notation as typed into the console, e.g. "e2e4"
+pub fn coordinate_move(parent: [u64; 13], child: [u64; 13], turn_number: u8) -> String {
+  match move_from_states(parent, child, turn_number) {
+    Some((_, from, to)) => format!("{}{}", square_name(from), square_name(to)),
+    None => String::from("0000")
+  }
+}
+
+pub fn san_move(parent: [u64; 13], child: [u64; 13], turn_number: u8) -> String {
+  let (slice_index, from, to) = match move_from_states(parent, child, turn_number) {
+    Some(found) => found,
+    None => return String::from("--")
+  };
+  let capture: bool = is_capture(parent, child, turn_number);
+  let mut san: String = String::new();
+
+  match piece_letter(slice_index) {
+    Some(letter) => {
+      san.push(letter);
+      let rivals: Vec<u8> = states_for_turn(parent, turn_number).into_iter()
+        .filter_map(|sibling| move_from_states(parent, sibling, turn_number))
+        .filter(|(s, f, t)| *s == slice_index && *t == to && *f != from)
+        .map(|(_, f, _)| f)
+        .collect();
+      if !rivals.is_empty() {
+        let from_name: String = square_name(from);
+        if rivals.iter().all(|f| f % 8 != from % 8) {
+          san.push_str(&from_name[0..1]);
+        } else if rivals.iter().all(|f| f / 8 != from / 8) {
+          san.push_str(&from_name[1..2]);
+        } else {
+          san.push_str(&from_name);
+        }
+      }
+    },
+    None => {
+      if capture {
+        san.push_str(&square_name(from)[0..1]);
+      }
+    }
+  }
+  if capture {
+    san.push('x');
+  }
+  san.push_str(&square_name(to));
+  if is_in_check(child, turn_number + 1) {
+    // mate when every reply still leaves the king attacked
+    let escapes: bool = states_for_turn(child, turn_number + 1).into_iter().any(|reply| !is_in_check(reply, turn_number + 1));
+    san.push(if escapes { '+' } else { '#' });
+  }
+  san
+}
+
+fn parse_square(text: &str) -> Option<u8> {
+  let bytes: &[u8] = text.as_bytes();
+  if bytes.len() != 2 || !(b'a'..=b'h').contains(&bytes[0]) || !(b'1'..=b'8').contains(&bytes[1]) {
+    return None;
+  }
+  Some((bytes[1] - b'1') * 8 + (bytes[0] - b'a'))
+}
+
+// whether the move from `parent` to `child` is the one written in standard algebraic notation.
+// castling and promotions never match because the move generator produces neither.
+pub fn san_matches(parent: [u64; 13], child: [u64; 13], turn_number: u8, san: &str) -> bool {
+  let (slice_index, from, to) = match move_from_states(parent, child, turn_number) {
+    Some(found) => found,
+    None => return false
+  };
+  let core: String = san.chars().filter(|ch| !"+#!?".contains(*ch)).collect();
+  if core.starts_with('O') || core.starts_with('0') || core.contains('=') || core.len() < 2 {
+    return false;
+  }
+  let destination: Option<u8> = parse_square(&core[core.len() - 2..]);
+  if destination != Some(to) {
+    return false;
+  }
+
+  let mut prefix: &str = &core[..core.len() - 2];
+  let wanted_piece: Option<char> = match prefix.chars().next() {
+    Some(ch) if "KQRBN".contains(ch) => {
+      prefix = &prefix[1..];
+      Some(ch)
+    },
+    _ => None
+  };
+  if wanted_piece != piece_letter(slice_index) {
+    return false;
+  }
+
+  let from_name: String = square_name(from);
+  prefix.chars().filter(|ch| *ch != 'x' && *ch != '-').all(|ch| {
+    if ch.is_ascii_digit() { from_name.ends_with(ch) } else { from_name.starts_with(ch) }
+  })
+}
+
+pub fn state_after_san(state: [u64; 13], turn_number: u8, san: &str) -> Option<[u64; 13]> {
+  let matches: Vec<[u64; 13]> = states_for_turn(state, turn_number).into_iter()
+    .filter(|child| san_matches(state, *child, turn_number, san))
+    .collect();
+  if matches.len() == 1 { Some(matches[0]) } else { None }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::game::setup_board;
+  use crate::fen::state_from_fen;
+
+  #[test]
+  fn names_squares() {
+    assert!(square_name(0) == "a1");
+    assert!(square_name(63) == "h8");
+    assert!(parse_square("e4") == Some(28));
+  }
+
+  #[test]
+  fn finds_pawn_and_knight_moves_from_san() {
+    let state: [u64; 13] = setup_board();
+    let after_e4: [u64; 13] = state_after_san(state, 1, "e4").unwrap();
+    assert!(after_e4[WPAWN as usize] & (1 << 28) != 0);
+    let after_nf6: [u64; 13] = state_after_san(after_e4, 2, "Nf6").unwrap();
+    assert!(after_nf6[BKNIGHT as usize] & (1 << 45) != 0);
+    assert!(coordinate_move(state, after_e4, 1) == "e2e4");
+    assert!(san_move(after_e4, after_nf6, 2) == "Nf6");
+  }
+
+  #[test]
+  fn disambiguates_and_marks_checks() {
+    let (state, turn_number) = state_from_fen("7k/5ppp/8/8/8/1K6/8/R6R w - -").unwrap();
+    let after: [u64; 13] = state_after_san(state, turn_number, "Rhe1").unwrap();
+    assert!(after[WROOK as usize] == (1 | (1 << 4)));
+    assert!(state_after_san(state, turn_number, "Re1").is_none());
+    let mate: [u64; 13] = state_after_san(state, turn_number, "Ra8#").unwrap();
+    assert!(san_move(state, mate, turn_number) == "Ra8#");
+    assert!(san_move(state, after, turn_number) == "Rhe1");
+    let (open_king, turn_number) = state_from_fen("7k/8/8/8/8/1K6/8/R7 w - -").unwrap();
+    let check: [u64; 13] = state_after_san(open_king, turn_number, "Ra8+").unwrap();
+    assert!(san_move(open_king, check, turn_number) == "Ra8+");
+  }
+
+  #[test]
+  fn never_matches_castling_or_promotion() {
+    let state: [u64; 13] = setup_board();
+    assert!(state_after_san(state, 1, "O-O").is_none());
+    assert!(state_after_san(state, 1, "e8=Q").is_none());
+  }
+}
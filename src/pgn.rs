@@ -0,0 +1,229 @@
+use crate::fen::state_from_fen;
+use crate::game::setup_board;
+use crate::notation::state_after_san;
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct PgnGame {
+  pub tags: Vec<(String, String)>,
+  // standard algebraic notation, without move numbers, comments or variations
+  pub moves: Vec<String>,
+  // white's score, 1.0 for a white win, None for an unfinished game
+  pub result: Option<f64>
+}
+
+impl PgnGame {
+  pub fn tag(&self, name: &str) -> Option<&str> {
+    self.tags.iter().find(|(tag, _)| tag == name).map(|(_, value)| value.as_str())
+  }
+}
+
+// the positions of a game before each of its moves, as far as they can be replayed
+#[derive(Clone, PartialEq, Debug)]
+pub struct ReplayedGame {
+  pub positions: Vec<([u64; 13], u8)>,
+  // false when a move couldn't be played, e.g. castling or a promotion, which the move
+  // generator doesn't produce; the positions stop there
+  pub complete: bool
+}
+
+fn parse_result(token: &str) -> Option<Option<f64>> {
+  match token {
+    "1-0" => Some(Some(1.0)),
+    "0-1" => Some(Some(0.0)),
+    "1/2-1/2" => Some(Some(0.5)),
+    "*" => Some(None),
+    _ => None
+  }
+}
+
+// `[Name "value"]`
+fn parse_tag(line: &str) -> Result<(String, String), String> {
+  let inner: &str = line.trim().trim_start_matches('[').trim_end_matches(']').trim();
+  let (name, value) = match inner.find(char::is_whitespace) {
+    Some(index) => (&inner[..index], inner[index..].trim()),
+    None => return Err(format!("tag '{}' has no value", line))
+  };
+  if value.len() < 2 || !value.starts_with('"') || !value.ends_with('"') {
+    return Err(format!("tag '{}' needs a quoted value", line));
+  }
+  Ok((name.to_string(), value[1..value.len() - 1].replace("\\\"", "\"")))
+}
+
+// move text tokens, with comments, variations, annotations and move numbers dropped
+fn movetext_tokens(text: &str) -> Result<Vec<String>, String> {
+  let mut tokens: Vec<String> = Vec::new();
+  let mut current: String = String::new();
+  let mut variation_depth: usize = 0;
+  let mut chars = text.chars();
+  while let Some(ch) = chars.next() {
+    match ch {
+      '{' => {
+        if !chars.by_ref().any(|ch| ch == '}') {
+          return Err(String::from("unterminated comment"));
+        }
+      },
+      ';' => {
+        chars.by_ref().find(|ch| *ch == '\n');
+      },
+      '(' => variation_depth += 1,
+      ')' => {
+        if variation_depth == 0 {
+          return Err(String::from("')' without a variation to close"));
+        }
+        variation_depth -= 1;
+      },
+      _ if variation_depth > 0 => {},
+      ch if ch.is_whitespace() => {
+        if !current.is_empty() {
+          tokens.push(std::mem::take(&mut current));
+        }
+      },
+      _ => current.push(ch)
+    }
+    if variation_depth > 0 && !current.is_empty() {
+      tokens.push(std::mem::take(&mut current));
+    }
+  }
+  if variation_depth > 0 {
+    return Err(String::from("unterminated variation"));
+  }
+  if !current.is_empty() {
+    tokens.push(current);
+  }
+  // "$1" is an annotation
+  Ok(tokens.iter()
+           .map(|token| without_move_number(token).to_string())
+           .filter(|token| !token.is_empty() && !token.starts_with('$'))
+           .collect())
+}
+
+// "12." and "12..." stand alone or are glued to the move; results like "1-0" are left alone
+fn without_move_number(token: &str) -> &str {
+  let digits: &str = token.trim_start_matches(|ch: char| ch.is_ascii_digit());
+  if digits.starts_with('.') { digits.trim_start_matches('.') } else { token }
+}
+
+// a result token ends a game, so games without tags can follow one another
+fn parse_games(tags: Vec<(String, String)>, movetext: &str) -> Result<Vec<PgnGame>, String> {
+  let mut games: Vec<PgnGame> = Vec::new();
+  let mut tags: Vec<(String, String)> = tags;
+  let mut moves: Vec<String> = Vec::new();
+  for token in movetext_tokens(movetext)? {
+    match parse_result(&token) {
+      Some(result) => games.push(PgnGame { tags: std::mem::take(&mut tags), moves: std::mem::take(&mut moves), result }),
+      None => moves.push(token)
+    }
+  }
+  if !moves.is_empty() || (games.is_empty() && !tags.is_empty()) {
+    // the tag is the authority when the move text leaves the result off
+    let result: Option<f64> = tags.iter().find(|(tag, _)| tag == "Result").and_then(|(_, value)| parse_result(value)).flatten();
+    games.push(PgnGame { tags, moves, result });
+  }
+  Ok(games)
+}
+
+pub fn parse_pgn(text: &str) -> Result<Vec<PgnGame>, String> {
+  let mut games: Vec<PgnGame> = Vec::new();
+  let mut tags: Vec<(String, String)> = Vec::new();
+  let mut movetext: String = String::new();
+  let mut game_line: usize = 1;
+  for (index, line) in text.lines().enumerate() {
+    let trimmed: &str = line.trim();
+    if trimmed.starts_with('[') && !movetext.trim().is_empty() {
+      games.extend(parse_games(std::mem::take(&mut tags), &movetext).map_err(|error| format!("game at line {}: {}", game_line, error))?);
+      movetext.clear();
+    }
+    if trimmed.starts_with('[') {
+      if tags.is_empty() {
+        game_line = index + 1;
+      }
+      tags.push(parse_tag(trimmed).map_err(|error| format!("line {}: {}", index + 1, error))?);
+    } else if !trimmed.starts_with('%') {
+      movetext.push_str(line);
+      movetext.push('\n');
+    }
+  }
+  if !tags.is_empty() || !movetext.trim().is_empty() {
+    games.extend(parse_games(tags, &movetext).map_err(|error| format!("game at line {}: {}", game_line, error))?);
+  }
+  Ok(games)
+}
+
+pub fn read_pgn_file(path: &str) -> Result<Vec<PgnGame>, String> {
+  match std::fs::read_to_string(path) {
+    Ok(text) => parse_pgn(&text),
+    Err(error) => Err(format!("couldn't read {}: {}", path, error))
+  }
+}
+
+// plays the game's moves from its FEN tag, or the starting position, for as long as the
+// move generator can follow them
+pub fn replay_game(game: &PgnGame) -> Result<ReplayedGame, String> {
+  let (mut state, mut turn_number) = match game.tag("FEN") {
+    Some(fen) => state_from_fen(fen)?,
+    None => (setup_board(), 1)
+  };
+  let mut positions: Vec<([u64; 13], u8)> = Vec::new();
+  for san in game.moves.iter() {
+    // turn numbers are a u8; stop well before they wrap
+    if turn_number >= 250 {
+      return Ok(ReplayedGame { positions, complete: false });
+    }
+    match state_after_san(state, turn_number, san) {
+      Some(next) => {
+        positions.push((state, turn_number));
+        state = next;
+        turn_number += 1;
+      },
+      None => return Ok(ReplayedGame { positions, complete: false })
+    }
+  }
+  Ok(ReplayedGame { positions, complete: true })
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  const GAMES: &str = "[Event \"Test\"]\n[White \"A\"]\n[Result \"1-0\"]\n\n1. e4 {best by test} e5 2. Nf3 (2. f4 exf4) Nc6 $1 3. Bb5 a6 1-0\n\n[Event \"Second\"]\n[Result \"1/2-1/2\"]\n\n1.d4 d5 2.c4 ; a gambit\n2...e6 1/2-1/2\n";
+
+  #[test]
+  fn parses_tags_moves_and_results() {
+    let games: Vec<PgnGame> = parse_pgn(GAMES).unwrap();
+    assert!(games.len() == 2);
+    assert!(games[0].tag("White") == Some("A"));
+    assert!(games[0].moves == vec!["e4", "e5", "Nf3", "Nc6", "Bb5", "a6"]);
+    assert!(games[0].result == Some(1.0));
+    assert!(games[1].moves == vec!["d4", "d5", "c4", "e6"]);
+    assert!(games[1].result == Some(0.5));
+  }
+
+  #[test]
+  fn replays_until_an_unsupported_move() {
+    let games: Vec<PgnGame> = parse_pgn(GAMES).unwrap();
+    let replayed: ReplayedGame = replay_game(&games[0]).unwrap();
+    assert!(replayed.complete && replayed.positions.len() == 6);
+    assert!(replayed.positions[0] == (setup_board(), 1));
+    assert!(replayed.positions[5].1 == 6);
+
+    let castles: PgnGame = parse_pgn("1. e4 e5 2. Nf3 Nc6 3. Bc4 Bc5 4. O-O *").unwrap().remove(0);
+    assert!(castles.result.is_none());
+    let replayed: ReplayedGame = replay_game(&castles).unwrap();
+    assert!(!replayed.complete && replayed.positions.len() == 6);
+  }
+
+  #[test]
+  fn starts_from_the_fen_tag() {
+    let game: PgnGame = parse_pgn("[FEN \"7k/5ppp/8/8/8/8/8/R1K5 w - - 0 1\"]\n[Result \"1-0\"]\n\n1. Ra8# 1-0\n").unwrap().remove(0);
+    let replayed: ReplayedGame = replay_game(&game).unwrap();
+    assert!(replayed.complete && replayed.positions.len() == 1);
+    assert!(replayed.positions[0].0[0] == 1);
+  }
+
+  #[test]
+  fn reports_malformed_games() {
+    assert!(parse_pgn("[Event Test]\n\n1. e4 *").is_err());
+    assert!(parse_pgn("1. e4 {never closed").is_err());
+    assert!(parse_pgn("1. e4 (1. d4 *").is_err());
+  }
+}
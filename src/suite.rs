@@ -0,0 +1,164 @@
+use crate::bot::Bot;
+use crate::epd::{EpdEntry, read_epd_file};
+use crate::notation::{san_matches, san_move};
+use std::time::{Duration, Instant};
+
+// how much searching each position gets. the search stops as soon as the budget is spent,
+// and the depth it was cut off in doesn't count
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SearchBudget {
+  Nodes(u64),
+  Time(Duration)
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct PositionResult {
+  pub id: String,
+  pub solved: bool,
+  pub found: String,
+  pub depth: u8,
+  pub nodes: u64,
+  // time at which the search settled on a solving move and kept it until the end
+  pub time_to_solution: Option<Duration>
+}
+
+fn solves(entry: &EpdEntry, state: [u64; 13]) -> bool {
+  let matches = |san: &String| san_matches(entry.state, state, entry.turn_number, san);
+  (entry.best_moves.is_empty() || entry.best_moves.iter().any(matches))
+    && !entry.avoid_moves.iter().any(matches)
+}
+
+// iterative deepening with `bot`, whose depth is changed as the search deepens
+pub fn run_position(entry: &EpdEntry, bot: &mut Bot, budget: SearchBudget, max_depth: u8) -> PositionResult {
+  let start: Instant = Instant::now();
+  let mut result: PositionResult = PositionResult {
+    id: entry.id.clone(),
+    solved: false,
+    found: String::from("--"),
+    depth: 0,
+    nodes: 0,
+    time_to_solution: None
+  };
+
+  let deadline: Option<Instant> = match budget {
+    SearchBudget::Time(time) => Some(start + time),
+    SearchBudget::Nodes(_) => None
+  };
+  for depth in 0..=max_depth {
+    let node_limit: Option<u64> = match budget {
+      SearchBudget::Nodes(nodes) => Some(nodes.saturating_sub(result.nodes)),
+      SearchBudget::Time(_) => None
+    };
+    bot.set_depth(depth);
+    bot.reset_nodes();
+    bot.set_limits(node_limit, deadline);
+    let (_evaluation, state) = bot.search(entry.state, entry.turn_number);
+    result.nodes += bot.nodes();
+    // a cut-off depth only counts when there's nothing else to show
+    if bot.stopped() && depth > 0 {
+      break;
+    }
+    result.depth = depth;
+    result.found = san_move(entry.state, state, entry.turn_number);
+    result.solved = solves(entry, state);
+    if !result.solved {
+      result.time_to_solution = None;
+    } else if result.time_to_solution.is_none() {
+      result.time_to_solution = Some(start.elapsed());
+    }
+    if bot.stopped() {
+      break;
+    }
+  }
+  bot.set_limits(None, None);
+  result
+}
+
+pub fn print_result(entry: &EpdEntry, result: &PositionResult) {
+  let expected: String = if entry.best_moves.is_empty() {
+    format!("am {}", entry.avoid_moves.join(" "))
+  } else {
+    format!("bm {}", entry.best_moves.join(" "))
+  };
+  let time: String = match result.time_to_solution {
+    Some(time) => format!("{:.3}s", time.as_secs_f64()),
+    None => String::from("-")
+  };
+  println!("{:<12} {:<9} {:<12} found {:<8} depth {:<3} nodes {:<10} time {}",
+           result.id, if result.solved { "solved" } else { "unsolved" }, expected, result.found, result.depth, result.nodes, time);
+}
+
+pub fn run_suite(entries: &[EpdEntry], bot: &mut Bot, budget: SearchBudget, max_depth: u8) -> Vec<PositionResult> {
+  let mut results: Vec<PositionResult> = Vec::new();
+  for entry in entries.iter() {
+    let result: PositionResult = run_position(entry, bot, budget, max_depth);
+    print_result(entry, &result);
+    results.push(result);
+  }
+  results
+}
+
+// one point per solved position
+pub fn score(results: &[PositionResult]) -> usize {
+  results.iter().filter(|result| result.solved).count()
+}
+
+pub fn print_summary(results: &[PositionResult]) {
+  let solution_time: f64 = results.iter()
+    .filter_map(|result| result.time_to_solution)
+    .map(|time| time.as_secs_f64())
+    .sum();
+  let nodes: u64 = results.iter().map(|result| result.nodes).sum();
+  println!("Score: {}/{} solved, {:.3}s total time to solution, {} nodes", score(results), results.len(), solution_time, nodes);
+}
+
+pub fn run_epd_file(path: &str, bot: &mut Bot, budget: SearchBudget, max_depth: u8) -> Result<Vec<PositionResult>, String> {
+  let entries: Vec<EpdEntry> = read_epd_file(path)?;
+  let results: Vec<PositionResult> = run_suite(&entries, bot, budget, max_depth);
+  print_summary(&results);
+  Ok(results)
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::bot::make_bot;
+  use crate::evaluator::BasicEvaluator;
+  use crate::epd::parse_epd;
+
+  const SUITE: &str = "7k/5ppp/8/8/8/1K6/8/R7 w - - bm Ra8#; id \"mate.001\";\n\
+                       k7/8/4p3/3n4/8/2N5/8/3R3K w - - bm Nxd5; id \"win.001\";\n\
+                       k7/8/4p3/3p4/8/8/8/3Q3K w - - am Qxd5; id \"avoid.001\";\n\
+                       k7/8/8/8/8/8/8/7K w - - bm O-O; id \"castle.001\";\n";
+
+  #[test]
+  fn solves_suite_positions_under_a_node_budget() {
+    let entries: Vec<EpdEntry> = parse_epd(SUITE).unwrap();
+    let results: Vec<PositionResult> = run_suite(&entries, &mut make_bot(Box::new(BasicEvaluator), 0), SearchBudget::Nodes(20000), 2);
+    assert!(results[0].solved);
+    assert!(results[0].found == "Ra8#");
+    assert!(results[1].solved);
+    assert!(results[2].solved);
+    assert!(results[0].time_to_solution.is_some());
+    assert!(!results[3].solved);
+    assert!(results[3].time_to_solution.is_none());
+    assert!(score(&results) == 3);
+  }
+
+  #[test]
+  fn stops_deepening_once_the_node_budget_is_spent() {
+    let entries: Vec<EpdEntry> = parse_epd(SUITE).unwrap();
+    let result: PositionResult = run_position(&entries[0], &mut make_bot(Box::new(BasicEvaluator), 0), SearchBudget::Nodes(1), 5);
+    assert!(result.depth == 0);
+  }
+
+  #[test]
+  fn stops_inside_a_depth_at_the_budget() {
+    let entries: Vec<EpdEntry> = parse_epd(SUITE).unwrap();
+    let result: PositionResult = run_position(&entries[1], &mut make_bot(Box::new(BasicEvaluator), 0), SearchBudget::Nodes(2000), 12);
+    assert!(result.nodes < 4000 && result.found != "--");
+    let start: Instant = Instant::now();
+    run_position(&entries[1], &mut make_bot(Box::new(BasicEvaluator), 0), SearchBudget::Time(Duration::from_millis(50)), 12);
+    assert!(start.elapsed() < Duration::from_secs(2));
+  }
+}
@@ -0,0 +1,210 @@
+use crate::evaluation::evaluate_with_params;
+use crate::evaluation::params::EvalParams;
+use crate::fen::state_from_fen;
+
+// a quiet position and the result of the game it came from, 1.0 when white won
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TuningPosition {
+  pub state: [u64; 13],
+  pub result: f64
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TuneConfig {
+  // passes over every parameter; a pass that improves nothing stops the search early
+  pub max_iterations: usize,
+  // how far each parameter is nudged per try, in pawns
+  pub step: f64
+}
+
+pub fn default_tune_config() -> TuneConfig {
+  TuneConfig {
+    max_iterations: 100,
+    step: 0.01
+  }
+}
+
+// parameters whose gradient is always zero or that only make sense hand set
+const FROZEN_PARAMETERS: [&str; 3] = ["material.king.mg", "material.king.eg", "king_attack_unit_cap"];
+
+fn parse_result(token: &str) -> Option<f64> {
+  match token.trim_matches(|ch: char| ch == '[' || ch == ']' || ch == '"' || ch == ';') {
+    "1-0" | "1" | "1.0" => Some(1.0),
+    "0-1" | "0" | "0.0" => Some(0.0),
+    "1/2-1/2" | "0.5" => Some(0.5),
+    _ => None
+  }
+}
+
+// `<fen> <result>`, where the fen has at least its first four fields and the result is the
+// line's last token: 1-0, 0-1 or 1/2-1/2, or 1.0, 0.5 or 0.0, optionally in brackets or quotes
+pub fn parse_tuning_line(line: &str) -> Result<Option<TuningPosition>, String> {
+  let line: &str = line.trim();
+  if line.is_empty() || line.starts_with('#') {
+    return Ok(None);
+  }
+  let tokens: Vec<&str> = line.split_whitespace().collect();
+  if tokens.len() < 5 {
+    return Err(format!("expected a FEN followed by a result: {}", line));
+  }
+  let result: f64 = match parse_result(tokens[tokens.len() - 1]) {
+    Some(result) => result,
+    None => return Err(format!("no game result at the end of: {}", line))
+  };
+  let (state, _turn_number) = state_from_fen(&tokens[0..4].join(" "))?;
+  Ok(Some(TuningPosition { state, result }))
+}
+
+pub fn parse_tuning_positions(text: &str) -> Result<Vec<TuningPosition>, String> {
+  let mut positions: Vec<TuningPosition> = Vec::new();
+  for (line_number, line) in text.lines().enumerate() {
+    match parse_tuning_line(line) {
+      Ok(Some(position)) => positions.push(position),
+      Ok(None) => (),
+      Err(error) => return Err(format!("line {}: {}", line_number + 1, error))
+    }
+  }
+  Ok(positions)
+}
+
+pub fn read_tuning_file(path: &str) -> Result<Vec<TuningPosition>, String> {
+  match std::fs::read_to_string(path) {
+    Ok(text) => parse_tuning_positions(&text),
+    Err(error) => Err(format!("couldn't read {}: {}", path, error))
+  }
+}
+
+// expected score for white of an evaluation in pawns
+pub fn win_probability(eval: f64, k: f64) -> f64 {
+  1.0 / (1.0 + 10f64.powf(-k * eval / 4.0))
+}
+
+pub fn mean_squared_error(positions: &[TuningPosition], params: &EvalParams, k: f64) -> f64 {
+  if positions.is_empty() {
+    return 0.0;
+  }
+  let total: f64 = positions.iter()
+    .map(|position| {
+      let error: f64 = position.result - win_probability(evaluate_with_params(position.state, params), k);
+      error * error
+    })
+    .sum();
+  total / positions.len() as f64
+}
+
+fn error_for_evals(positions: &[TuningPosition], evals: &[f64], k: f64) -> f64 {
+  let total: f64 = positions.iter().zip(evals.iter())
+    .map(|(position, eval)| (position.result - win_probability(*eval, k)).powi(2))
+    .sum();
+  total / positions.len().max(1) as f64
+}
+
+// the K that makes the current evaluation best predict the results, found by scanning
+// ever finer steps around the best value so far
+pub fn fit_scaling_constant(positions: &[TuningPosition], params: &EvalParams) -> f64 {
+  let evals: Vec<f64> = positions.iter().map(|position| evaluate_with_params(position.state, params)).collect();
+  let mut best_k: f64 = 1.0;
+  let mut best_error: f64 = error_for_evals(positions, &evals, best_k);
+  let mut step: f64 = 1.0;
+  let mut low: f64 = 0.0;
+  let mut high: f64 = 10.0;
+  while step >= 0.001 {
+    let mut k: f64 = low;
+    while k <= high {
+      let error: f64 = error_for_evals(positions, &evals, k);
+      if error < best_error {
+        best_error = error;
+        best_k = k;
+      }
+      k += step;
+    }
+    low = (best_k - step).max(0.0);
+    high = best_k + step;
+    step /= 10.0;
+  }
+  best_k
+}
+
+// local search: nudges every parameter up and down by `step`, keeping whichever change lowers
+// the error, until a full pass changes nothing
+pub fn tune(positions: &[TuningPosition], start: &EvalParams, k: f64, config: TuneConfig) -> EvalParams {
+  let names: Vec<String> = start.named_values().into_iter().map(|(name, _value)| name).collect();
+  let mut values: Vec<f64> = start.values();
+  let mut best_error: f64 = mean_squared_error(positions, start, k);
+  println!("Starting error: {:.8}", best_error);
+
+  for iteration in 1..=config.max_iterations {
+    let mut improved: bool = false;
+    for index in 0..values.len() {
+      if FROZEN_PARAMETERS.contains(&names[index].as_str()) {
+        continue;
+      }
+      let original: f64 = values[index];
+      for candidate in [original + config.step, original - config.step] {
+        values[index] = candidate;
+        let error: f64 = mean_squared_error(positions, &start.with_values(&values), k);
+        if error < best_error {
+          best_error = error;
+          improved = true;
+          break;
+        }
+        values[index] = original;
+      }
+    }
+    println!("Iteration {}: error {:.8}", iteration, best_error);
+    if !improved {
+      break;
+    }
+  }
+  start.with_values(&values)
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::evaluation::params::{default_eval_params, PAWN};
+  use crate::evaluation::tapered;
+
+  const POSITIONS: &str = "\
+# white is a pawn up in every won game
+4k3/8/8/8/8/8/PP6/4K3 w - - 1-0
+4k3/8/8/8/8/8/1PP5/4K3 b - - [1.0]
+4k3/p7/8/8/8/8/PP6/4K3 w - - c9 \"1/2-1/2\";
+4k3/pp6/8/8/8/8/P7/4K3 w - - 0-1
+";
+
+  #[test]
+  fn parses_results_in_several_styles() {
+    let positions: Vec<TuningPosition> = parse_tuning_positions(POSITIONS).unwrap();
+    let results: Vec<f64> = positions.iter().map(|position| position.result).collect();
+    assert!(results == vec![1.0, 1.0, 0.5, 0.0]);
+    assert!(parse_tuning_line("4k3/8/8/8/8/8/8/4K3 w - - 2-0").is_err());
+  }
+
+  #[test]
+  fn probability_is_centred_on_a_level_position() {
+    assert!(win_probability(0.0, 1.3) == 0.5);
+    assert!(win_probability(2.0, 1.3) > 0.5);
+    assert!((win_probability(2.0, 1.3) + win_probability(-2.0, 1.3) - 1.0).abs() < 1e-12);
+  }
+
+  #[test]
+  fn fitted_constant_beats_its_neighbours() {
+    let positions: Vec<TuningPosition> = parse_tuning_positions(POSITIONS).unwrap();
+    let params: EvalParams = default_eval_params();
+    let k: f64 = fit_scaling_constant(&positions, &params);
+    let error: f64 = mean_squared_error(&positions, &params, k);
+    assert!(error <= mean_squared_error(&positions, &params, k + 0.1));
+    assert!(error <= mean_squared_error(&positions, &params, (k - 0.1).max(0.0)));
+  }
+
+  #[test]
+  fn tuning_lowers_the_error() {
+    let positions: Vec<TuningPosition> = parse_tuning_positions(POSITIONS).unwrap();
+    let mut start: EvalParams = default_eval_params();
+    start.material[PAWN] = tapered(0.1, 0.1);
+    let config: TuneConfig = TuneConfig { max_iterations: 2, step: 0.05 };
+    let tuned: EvalParams = tune(&positions, &start, 1.0, config);
+    assert!(mean_squared_error(&positions, &tuned, 1.0) < mean_squared_error(&positions, &start, 1.0));
+  }
+}
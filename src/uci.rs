@@ -0,0 +1,397 @@
+use crate::bot::{make_bot, Bot};
+use crate::evaluation::{eval_trace_with_params, format_eval_trace};
+use crate::evaluation::params::EvalParams;
+use crate::evaluator::{make_hand_crafted_evaluator, Evaluator};
+use crate::fen::state_from_fen;
+use crate::game::setup_board;
+use crate::mcts::{default_mcts_config, make_mcts, Mcts, MctsConfig};
+use crate::network::eval::make_net_evaluator;
+use crate::network::network_storage::load_network_file;
+use crate::network::nnue::{is_nnue_file, load_nnue_evaluator};
+use crate::notation::coordinate_move;
+use crate::r#move::states_for_turn;
+use std::io::{BufRead, Write};
+use std::time::{Duration, Instant};
+
+// depth searched by `go` without a depth, node or time limit
+pub const DEFAULT_UCI_DEPTH: u8 = 4;
+// deepest iteration a time or node limit lets the search reach
+const MAX_UCI_DEPTH: u8 = 32;
+// share of the remaining clock spent on one move when the gui only sends wtime/btime
+const MOVES_TO_GO: u32 = 30;
+const MAX_UCI_PLAYOUTS: usize = 1000000;
+
+// the search `go` runs, picked with the Engine option
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum SearchKind {
+  Minimax,
+  Mcts
+}
+
+// a minimal front end for guis and match runners. commands are handled one at a time, so a
+// search runs until its own limit and `stop` has nothing to interrupt. castling and
+// promotions aren't generated by the engine, so they're never sent and never understood
+pub struct Uci {
+  state: [u64; 13],
+  turn_number: u8,
+  params: EvalParams,
+  // the learned network searched with instead of the hand-crafted evaluation
+  eval_file: Option<String>,
+  search: SearchKind,
+  depth: u8,
+  playouts: usize,
+  // whichever the search kind needs, rebuilt after the options change
+  bot: Option<Bot>,
+  mcts: Option<Mcts>
+}
+
+pub fn make_uci(params: EvalParams) -> Uci {
+  Uci {
+    state: setup_board(),
+    turn_number: 1,
+    params,
+    eval_file: None,
+    search: SearchKind::Minimax,
+    depth: DEFAULT_UCI_DEPTH,
+    playouts: default_mcts_config().playouts,
+    bot: None,
+    mcts: None
+  }
+}
+
+// the evaluator for an EvalFile: the nnue when the file holds one, otherwise the learned
+// net in either of its formats
+pub fn load_eval_file(path: &str) -> Result<Box<dyn Evaluator>, String> {
+  let bytes: Vec<u8> = std::fs::read(path).map_err(|error| format!("couldn't read network {}: {}", path, error))?;
+  if is_nnue_file(&bytes) {
+    Ok(Box::new(load_nnue_evaluator(path)?))
+  } else {
+    Ok(Box::new(make_net_evaluator(load_network_file(path)?)))
+  }
+}
+
+fn state_after_uci_move(state: [u64; 13], turn_number: u8, name: &str) -> Option<[u64; 13]> {
+  states_for_turn(state, turn_number).into_iter()
+    .find(|child| coordinate_move(state, *child, turn_number) == name)
+}
+
+fn token_value<T: std::str::FromStr>(tokens: &[&str], name: &str) -> Result<Option<T>, String> {
+  match tokens.iter().position(|token| *token == name) {
+    Some(index) => match tokens.get(index + 1).map(|value| value.parse::<T>()) {
+      Some(Ok(value)) => Ok(Some(value)),
+      _ => Err(format!("invalid value for {}", name))
+    },
+    None => Ok(None)
+  }
+}
+
+impl Uci {
+  // answers one line from the gui, false once it asks to quit. unknown commands are
+  // ignored, as the protocol asks
+  pub fn handle(&mut self, line: &str, output: &mut dyn Write) -> Result<bool, String> {
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+    match tokens.first().copied() {
+      Some("uci") => {
+        write_line(output, "id name chess-engine")?;
+        write_line(output, &format!("option name Depth type spin default {} min 1 max {}", DEFAULT_UCI_DEPTH, MAX_UCI_DEPTH))?;
+        write_line(output, &format!("option name EvalFile type string default {}", self.eval_file.as_deref().unwrap_or("<empty>")))?;
+        write_line(output, "option name Engine type combo default minimax var minimax var mcts")?;
+        write_line(output, &format!("option name Playouts type spin default {} min 1 max {}", self.playouts, MAX_UCI_PLAYOUTS))?;
+        write_line(output, "uciok")?;
+      },
+      Some("isready") => write_line(output, "readyok")?,
+      Some("ucinewgame") => {
+        self.state = setup_board();
+        self.turn_number = 1;
+      },
+      Some("setoption") => self.set_option(&tokens)?,
+      Some("position") => self.set_position(&tokens[1..])?,
+      Some("go") => self.go(&tokens[1..], output)?,
+      // debug extension: the hand-crafted evaluation's terms for the current position
+      Some("eval") => {
+        for row in format_eval_trace(&eval_trace_with_params(self.state, &self.params)).lines().filter(|row| !row.is_empty()) {
+          write_line(output, &format!("info string {}", row))?;
+        }
+      },
+      Some("quit") => return Ok(false),
+      _ => {}
+    }
+    Ok(true)
+  }
+
+  // the file is read as soon as it's set, so a bad one is reported to the gui straight away
+  // and the evaluation searched with stays as it was. an empty value goes back to the
+  // hand-crafted evaluation
+  pub fn set_eval_file(&mut self, path: Option<&str>) -> Result<(), String> {
+    let previous: Option<String> = std::mem::replace(&mut self.eval_file, path.map(String::from));
+    self.rebuild().inspect_err(|_| self.eval_file = previous)
+  }
+
+  fn make_evaluator(&self) -> Result<Box<dyn Evaluator>, String> {
+    match &self.eval_file {
+      Some(path) => load_eval_file(path),
+      None => Ok(Box::new(make_hand_crafted_evaluator(self.params.clone())))
+    }
+  }
+
+  // builds the engine the options ask for, dropping the other one and any tree kept from
+  // earlier searches
+  fn rebuild(&mut self) -> Result<(), String> {
+    let evaluator: Box<dyn Evaluator> = self.make_evaluator()?;
+    (self.bot, self.mcts) = match self.search {
+      SearchKind::Minimax => (Some(make_bot(evaluator, 0)), None),
+      SearchKind::Mcts => {
+        // learned evaluations already score in -1..1, the hand-crafted one in pawns
+        let value_scale: f64 = if self.eval_file.is_some() { 1.0 } else { 10.0 };
+        (None, Some(make_mcts(evaluator, MctsConfig { playouts: self.playouts, value_scale, ..default_mcts_config() })))
+      }
+    };
+    Ok(())
+  }
+
+  // setoption name <NAME> value <VALUE>
+  fn set_option(&mut self, tokens: &[&str]) -> Result<(), String> {
+    let value_index: usize = tokens.iter().position(|token| *token == "value").unwrap_or(tokens.len());
+    let name: String = tokens.get(2..value_index).unwrap_or(&[]).join(" ");
+    let value: String = tokens.get(value_index + 1..).unwrap_or(&[]).join(" ");
+    match name.as_str() {
+      "Depth" => match value.parse::<u8>() {
+        Ok(depth) if (1..=MAX_UCI_DEPTH).contains(&depth) => self.depth = depth,
+        _ => return Err(format!("invalid Depth '{}'", value))
+      },
+      "EvalFile" => self.set_eval_file(Some(value.as_str()).filter(|path| !path.is_empty() && *path != "<empty>"))?,
+      "Engine" => {
+        self.search = match value.as_str() {
+          "minimax" => SearchKind::Minimax,
+          "mcts" => SearchKind::Mcts,
+          _ => return Err(format!("invalid Engine '{}'", value))
+        };
+        self.rebuild()?;
+      },
+      "Playouts" => match value.parse::<usize>() {
+        Ok(playouts) if (1..=MAX_UCI_PLAYOUTS).contains(&playouts) => {
+          self.playouts = playouts;
+          if let Some(mcts) = self.mcts.as_mut() {
+            mcts.config.playouts = playouts;
+          }
+        },
+        _ => return Err(format!("invalid Playouts '{}'", value))
+      },
+      _ => return Err(format!("unknown option '{}'", name))
+    }
+    Ok(())
+  }
+
+  // position startpos|fen <FEN> [moves <MOVE>...]
+  fn set_position(&mut self, tokens: &[&str]) -> Result<(), String> {
+    let moves_index: usize = tokens.iter().position(|token| *token == "moves").unwrap_or(tokens.len());
+    let (mut state, mut turn_number) = match tokens.first().copied() {
+      Some("startpos") => (setup_board(), 1),
+      Some("fen") => state_from_fen(&tokens[1..moves_index].join(" "))?,
+      _ => return Err(String::from("position needs startpos or fen"))
+    };
+    for name in tokens.iter().skip(moves_index + 1) {
+      state = state_after_uci_move(state, turn_number, name).ok_or(format!("illegal move '{}'", name))?;
+      // only the parity matters, and a long game would wrap the u8
+      turn_number = if turn_number % 2 == 1 { 2 } else { 1 };
+    }
+    self.state = state;
+    self.turn_number = turn_number;
+    Ok(())
+  }
+
+  // go [depth D] [nodes N] [movetime T] [wtime T btime T [winc I binc I]]
+  fn go(&mut self, tokens: &[&str], output: &mut dyn Write) -> Result<(), String> {
+    if self.bot.is_none() && self.mcts.is_none() {
+      self.rebuild()?;
+    }
+    let best_move: String = match self.search {
+      SearchKind::Minimax => self.go_minimax(tokens, output)?,
+      SearchKind::Mcts => self.go_mcts(tokens, output)?
+    };
+    write_line(output, &format!("bestmove {}", best_move))
+  }
+
+  // iterative deepening that keeps the last depth the limits let it finish
+  fn go_minimax(&mut self, tokens: &[&str], output: &mut dyn Write) -> Result<String, String> {
+    let start: Instant = Instant::now();
+    let (clock, increment) = if self.turn_number % 2 == 1 { ("wtime", "winc") } else { ("btime", "binc") };
+    let move_time: Option<u64> = match (token_value::<u64>(tokens, "movetime")?, token_value::<u64>(tokens, clock)?) {
+      (Some(time), _) => Some(time),
+      (None, Some(remaining)) => Some(remaining / MOVES_TO_GO as u64 + token_value::<u64>(tokens, increment)?.unwrap_or(0) / 2),
+      (None, None) => None
+    };
+    let node_limit: Option<u64> = token_value(tokens, "nodes")?;
+    let max_depth: u8 = match token_value::<u8>(tokens, "depth")? {
+      Some(depth) => depth.clamp(1, MAX_UCI_DEPTH),
+      None if move_time.is_some() || node_limit.is_some() => MAX_UCI_DEPTH,
+      None => self.depth
+    };
+    let (state, turn_number) = (self.state, self.turn_number);
+    let deadline: Option<Instant> = move_time.map(|time| start + Duration::from_millis(time));
+    let bot: &mut Bot = self.bot.as_mut().ok_or("no minimax engine")?;
+
+    let mut best: Option<[u64; 13]> = None;
+    let mut nodes: u64 = 0;
+    for depth in 1..=max_depth {
+      bot.set_depth(depth - 1);
+      bot.reset_nodes();
+      bot.set_limits(node_limit.map(|limit| limit.saturating_sub(nodes)), deadline);
+      let (evaluation, child) = bot.search(state, turn_number);
+      nodes += bot.nodes();
+      // a cut-off depth only counts when there's nothing else to play
+      if bot.stopped() && best.is_some() {
+        break;
+      }
+      best = Some(child);
+      // scores are in pawns from white's side, uci wants centipawns for the side to move
+      let score: f64 = if turn_number % 2 == 1 { evaluation } else { -evaluation };
+      write_line(output, &format!("info depth {} score cp {} nodes {} time {} pv {}",
+                                  depth, (score * 100.0).round() as i64, nodes, start.elapsed().as_millis(), coordinate_move(state, child, turn_number)))?;
+      if bot.stopped() {
+        break;
+      }
+    }
+    bot.set_limits(None, None);
+    Ok(match best {
+      Some(child) => coordinate_move(state, child, turn_number),
+      None => String::from("0000")
+    })
+  }
+
+  // the Playouts option's number of playouts, or `nodes` of them. the tree search has no
+  // clock, so depth and time limits don't apply to it
+  fn go_mcts(&mut self, tokens: &[&str], output: &mut dyn Write) -> Result<String, String> {
+    let start: Instant = Instant::now();
+    let playouts: usize = match token_value::<usize>(tokens, "nodes")? {
+      Some(nodes) => nodes.clamp(1, MAX_UCI_PLAYOUTS),
+      None => self.playouts
+    };
+    let (state, turn_number) = (self.state, self.turn_number);
+    let mcts: &mut Mcts = self.mcts.as_mut().ok_or("no mcts engine")?;
+    mcts.config.playouts = playouts;
+    let child: [u64; 13] = mcts.search(state, turn_number);
+    mcts.config.playouts = self.playouts;
+    // the value is from white's side in -1..1, scaled back to the evaluation's units
+    let value: f64 = mcts.root_value() * mcts.config.value_scale;
+    let score: f64 = if turn_number % 2 == 1 { value } else { -value };
+    write_line(output, &format!("info score cp {} nodes {} time {} pv {}",
+                                (score * 100.0).round() as i64, playouts, start.elapsed().as_millis(), coordinate_move(state, child, turn_number)))?;
+    Ok(coordinate_move(state, child, turn_number))
+  }
+}
+
+fn write_line(output: &mut dyn Write, line: &str) -> Result<(), String> {
+  writeln!(output, "{}", line).and_then(|_| output.flush()).map_err(|error| format!("couldn't write to the gui: {}", error))
+}
+
+// reads commands until quit or the end of the input. a bad command is reported to the gui
+// as an info string and the session carries on
+pub fn run_uci(uci: &mut Uci, input: &mut dyn BufRead, output: &mut dyn Write) -> Result<(), String> {
+  let mut line: String = String::new();
+  loop {
+    line.clear();
+    if input.read_line(&mut line).map_err(|error| format!("couldn't read from the gui: {}", error))? == 0 {
+      return Ok(());
+    }
+    match uci.handle(&line, output) {
+      Ok(true) => {},
+      Ok(false) => return Ok(()),
+      Err(error) => write_line(output, &format!("info string error: {}", error))?
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::evaluation::params::default_eval_params;
+  use crate::network::nnue::{make_random_nnue_weights, write_nnue_file};
+  use crate::network::encoding::InputEncoder;
+
+  fn session(commands: &str) -> Vec<String> {
+    let mut output: Vec<u8> = Vec::new();
+    run_uci(&mut make_uci(default_eval_params()), &mut commands.as_bytes(), &mut output).unwrap();
+    String::from_utf8(output).unwrap().lines().map(String::from).collect()
+  }
+
+  #[test]
+  fn introduces_itself() {
+    let lines: Vec<String> = session("uci\nisready\nquit\n");
+    assert!(lines[0] == "id name chess-engine");
+    assert!(lines.contains(&String::from("uciok")));
+    assert!(lines.last().unwrap() == "readyok");
+  }
+
+  #[test]
+  fn plays_a_move_after_the_moves_given() {
+    let lines: Vec<String> = session("position startpos moves e2e4 e7e5 g1f3\ngo depth 1\n");
+    let best_move: &str = lines.last().unwrap().strip_prefix("bestmove ").unwrap();
+    let (state, turn_number) = state_from_fen("rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2").unwrap();
+    assert!(state_after_uci_move(state, turn_number, best_move).is_some());
+  }
+
+  #[test]
+  fn plays_on_after_a_long_game() {
+    let shuffle: [&str; 4] = ["g1f3", "g8f6", "f3g1", "f6g8"];
+    let moves: Vec<&str> = shuffle.iter().cycle().take(260).copied().collect();
+    let lines: Vec<String> = session(&format!("position startpos moves {}\ngo depth 1\n", moves.join(" ")));
+    assert!(!lines.iter().any(|line| line.starts_with("info string error")));
+    assert!(lines.last().unwrap().starts_with("bestmove "));
+    assert!(lines.last().unwrap() != "bestmove 0000");
+  }
+
+  #[test]
+  fn takes_the_hanging_queen() {
+    let lines: Vec<String> = session("position fen 4k3/8/8/3q4/8/8/8/3RK3 w - - 0 1\ngo depth 2\n");
+    assert!(lines.last().unwrap() == "bestmove d1d5");
+    assert!(lines.iter().any(|line| line.starts_with("info depth 2 score cp")));
+  }
+
+  #[test]
+  fn stops_at_the_node_limit() {
+    let lines: Vec<String> = session("go nodes 500\n");
+    assert!(lines.last().unwrap().starts_with("bestmove "));
+    assert!(lines.last().unwrap() != "bestmove 0000");
+  }
+
+  #[test]
+  fn traces_the_evaluation_as_info_strings() {
+    let lines: Vec<String> = session("position startpos\neval\n");
+    assert!(!lines.is_empty());
+    assert!(lines.iter().all(|line| line.starts_with("info string ")));
+  }
+
+  #[test]
+  fn searches_with_the_eval_file() {
+    let path = std::env::temp_dir().join("uci_eval_file.bin");
+    let path: &str = path.to_str().unwrap();
+    write_nnue_file(&make_random_nnue_weights(InputEncoder::KingBuckets, 8, 4, 3), path).unwrap();
+    let lines: Vec<String> = session(&format!("setoption name EvalFile value {}\nuci\ngo depth 1\n", path));
+    assert!(lines.contains(&format!("option name EvalFile type string default {}", path)));
+    assert!(lines.last().unwrap().starts_with("bestmove "));
+    assert!(!lines.iter().any(|line| line.starts_with("info string error")));
+    std::fs::remove_file(path).unwrap();
+  }
+
+  #[test]
+  fn keeps_the_evaluation_when_the_eval_file_is_missing() {
+    let mut uci: Uci = make_uci(default_eval_params());
+    assert!(uci.set_eval_file(Some("no_such_network.txt")).is_err());
+    assert!(uci.eval_file.is_none() && uci.bot.is_none());
+  }
+
+  #[test]
+  fn plays_with_the_tree_search_when_asked() {
+    let lines: Vec<String> = session("setoption name Engine value mcts\nsetoption name Playouts value 200\nposition fen 4k3/8/8/3q4/8/8/8/3RK3 w - - 0 1\ngo\n");
+    assert!(lines.iter().any(|line| line.starts_with("info score cp") && line.contains("nodes 200")));
+    assert!(lines.last().unwrap() == "bestmove d1d5");
+  }
+
+  #[test]
+  fn reports_bad_commands_and_carries_on() {
+    let lines: Vec<String> = session("position startpos moves e2e5\nsetoption name Depth value 0\nsetoption name Engine value alphazero\nisready\n");
+    assert!(lines.iter().filter(|line| line.starts_with("info string error")).count() == 3);
+    assert!(lines.last().unwrap() == "readyok");
+  }
+}
@@ -2,12 +2,13 @@ use std::io;
 use std::collections::HashMap;
 use crate::utility::{find_occupied_slice_index, print_board_pieces};
 use crate::constants::WHOLE_BOARD;
+use crate::evaluation::{eval_trace, print_eval_trace};
 
 pub fn get_legal_input_state(current_state: [u64; 13], legal_states: Vec<[u64; 13]>) -> [u64; 13] {
   let mut user_input: String;
   let mut user_state: [u64; 13];
   loop {
-    user_input = get_format_matched_user_input();
+    user_input = get_format_matched_user_input(current_state);
     user_state = update_board_by_string(current_state, user_input.clone());
     print_board_pieces(user_state);
     if legal_states.iter().any(|&s| s == user_state) {
@@ -70,11 +71,16 @@ fn input_matches_format(input: String) -> bool {
   (input.len() == 4) && evaluate_characters(input)
 }
 
-fn get_format_matched_user_input() -> String {
+// `eval` prints the evaluation breakdown of the current position instead of moving
+fn get_format_matched_user_input(current_state: [u64; 13]) -> String {
   let mut user_input: String;
   loop {
     user_input = input_wrapper();
     println!("user_input: {}", user_input);
+    if user_input == "eval" {
+      print_eval_trace(&eval_trace(current_state));
+      continue;
+    }
     if input_matches_format(user_input.clone()) {
       return user_input.clone();
     }
@@ -0,0 +1,80 @@
+use crate::constants::*;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+pub struct ZobristKeys {
+  pub pieces: [[u64; 64]; 12],
+  pub black_to_move: u64
+}
+
+fn make_zobrist_keys() -> ZobristKeys {
+  // fixed seed so hashes written to files stay valid between runs
+  let mut rng: StdRng = StdRng::seed_from_u64(0x5EED_C4E5_5000_0001);
+  let mut pieces: [[u64; 64]; 12] = [[0; 64]; 12];
+  for slice in pieces.iter_mut() {
+    for key in slice.iter_mut() {
+      *key = rng.gen::<u64>();
+    }
+  }
+  ZobristKeys {
+    pieces,
+    black_to_move: rng.gen::<u64>()
+  }
+}
+
+lazy_static! {
+  pub static ref ZOBRIST_KEYS: ZobristKeys = make_zobrist_keys();
+}
+
+fn hash_slices(state: [u64; 13], slices: &[u8]) -> u64 {
+  let mut hash: u64 = 0;
+  for slice_index in slices.iter() {
+    let mut slice: u64 = state[*slice_index as usize];
+    while slice != 0 {
+      hash ^= ZOBRIST_KEYS.pieces[*slice_index as usize][slice.trailing_zeros() as usize];
+      slice &= slice - 1;
+    }
+  }
+  hash
+}
+
+pub fn hash(state: [u64; 13], turn_number: u8) -> u64 {
+  let pieces: u64 = hash_slices(state, &[WROOK, WBISHOP, WQUEEN, WPAWN, WKNIGHT, WKING, BROOK, BBISHOP, BQUEEN, BPAWN, BKNIGHT, BKING]);
+  if turn_number % 2 == 1 { pieces } else { pieces ^ ZOBRIST_KEYS.black_to_move }
+}
+
+// key of the pawn placement alone, for caching pawn structure
+pub fn pawn_hash(state: [u64; 13]) -> u64 {
+  hash_slices(state, &[WPAWN, BPAWN])
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use crate::game::setup_board;
+
+  #[test]
+  fn side_to_move_changes_the_hash() {
+    assert!(hash(setup_board(), 1) != hash(setup_board(), 2));
+    assert!(hash(setup_board(), 1) == hash(setup_board(), 3));
+  }
+
+  #[test]
+  fn pawn_hash_ignores_pieces() {
+    let mut state: [u64; 13] = setup_board();
+    let before: u64 = pawn_hash(state);
+    state[WKNIGHT as usize] = 1 << 21;
+    assert!(pawn_hash(state) == before);
+    state[WPAWN as usize] ^= (1 << 12) | (1 << 28);
+    assert!(pawn_hash(state) != before);
+  }
+
+  #[test]
+  fn hash_is_incremental_by_xor() {
+    let state: [u64; 13] = setup_board();
+    let mut moved: [u64; 13] = state;
+    moved[WPAWN as usize] ^= (1 << 12) | (1 << 28);
+    let expected: u64 = hash(state, 1) ^ ZOBRIST_KEYS.pieces[WPAWN as usize][12] ^ ZOBRIST_KEYS.pieces[WPAWN as usize][28];
+    assert!(hash(moved, 1) == expected);
+  }
+}